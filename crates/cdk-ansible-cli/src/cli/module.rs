@@ -1,6 +1,11 @@
+mod cache;
+mod collection;
+
 use crate::utils::ACRONYM_WITH_TWO_LOWER;
 use anyhow::{Context as _, Result, bail};
-use clap::{Args, ValueEnum};
+use cache::{AnsibleVersions, Cache, CacheCmd};
+use clap::{Args, Subcommand, ValueEnum};
+use collection::Collections;
 use convert_case::{Boundary, Case, Casing as _};
 use core::fmt;
 use indexmap::IndexMap;
@@ -12,7 +17,13 @@ use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::{fs, io::AsyncWriteExt as _, process::Command, sync::Semaphore, task::JoinSet};
+use tokio::{
+    fs,
+    io::AsyncWriteExt as _,
+    process::Command,
+    sync::{Semaphore, mpsc},
+    task::JoinSet,
+};
 
 // FIXME: should be configurable
 /// The name of the submodule
@@ -21,6 +32,8 @@ static SUB_MOD_NAME: &str = "m";
 
 #[derive(Args, Debug, Clone)]
 pub struct ModuleCmd {
+    #[command(subcommand)]
+    pub command: Option<ModuleSubcommand>,
     /// Prefix of the generated package name.
     ///
     /// The default value `cdkam` means CDK Ansible Module.
@@ -53,13 +66,30 @@ pub struct ModuleCmd {
     #[arg(long, required = false, verbatim_doc_comment)]
     pub no_cache: bool,
     /// Directory to store cache files of ansible-doc output.
+    /// Entries are invalidated when ansible-core or the collection of the module is upgraded.
     #[arg(
         long,
         required = false,
         verbatim_doc_comment,
+        global = true,
         default_value = ".cdk-ansible.cache.out"
     )]
     pub cache_dir: PathBuf,
+    /// Read ansible-doc JSON from a file or a directory of `*.json` files, instead of running `ansible-doc`.
+    /// Each file is the output of `ansible-doc --json <module>...` or `ansible-doc --metadata-dump`.
+    /// Modules are then listed and generated from these files only, without Ansible installed.
+    #[arg(long, required = false, verbatim_doc_comment)]
+    pub doc_json: Option<PathBuf>,
+    /// Get the documentation of all modules from a single `ansible-doc --metadata-dump`,
+    /// instead of `ansible-doc --list` and `ansible-doc --json <module>...`.
+    /// The cache is not used.
+    #[arg(
+        long,
+        required = false,
+        verbatim_doc_comment,
+        conflicts_with = "doc_json"
+    )]
+    pub metadata_dump: bool,
     /// Specify the ansible module name. (e.g. `ansible.builtin.debug`)
     /// If not specified, all modules accessible from your ansible environment will be generated.
     #[arg(
@@ -93,40 +123,57 @@ pub struct ModuleCmd {
     pub max_procs: usize,
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum ModuleSubcommand {
+    /// Manage the cache of ansible-doc output.
+    Cache(CacheCmd),
+}
+
 impl ModuleCmd {
     pub async fn run(self) -> Result<()> {
+        if let Some(ModuleSubcommand::Cache(cmd)) = self.command {
+            return cmd.run(&self.cache_dir).await;
+        }
         let config = Arc::new(ModuleSettings::resolve(self));
-        let exclude_regexes = config.module_name_exclude.clone().unwrap_or_default();
-        let ans_modu_names = match (
-            config.module_name.as_ref(),
-            config.module_name_regex.as_ref(),
-        ) {
-            (Some(modu_name), None) => {
-                vec![
-                    AnsibleModuleName::new(modu_name)
-                        .with_context(|| format!("failed to parse module name: {modu_name}"))?,
-                ]
-            }
-            (None, Some(regex)) => match_module_name(regex, &exclude_regexes).await?,
-            (None, None) => match_module_name("*", &exclude_regexes).await?,
-            (Some(_), Some(_)) => {
-                // Already rejected at argument parsing
-                bail!("failed to specify both module_name and module_name_regex");
-            }
+        let filter = ModuleFilter::new(&config)?;
+        // Versions embedded in the generated crates, unless generating without Ansible
+        let collections = if config.doc_json.is_some() {
+            None
+        } else {
+            Some(Arc::new(Collections::detect().await?))
         };
 
         // define semaphore for file read/write
         let file_rw_semaphore = Arc::new(Semaphore::new(1));
         let child_process_semaphore = Arc::new(Semaphore::new(config.max_procs));
+
+        // Generate each module as soon as its documentation is parsed
+        let (tx, mut rx) = mpsc::channel(MODULE_DOC_CHANNEL_SIZE);
+        let sender = tokio::spawn(send_module_docs(
+            Arc::clone(&config),
+            collections.clone(),
+            filter,
+            child_process_semaphore,
+            tx,
+        ));
+        // Bound the generation tasks, each of which runs rustfmt
+        let generate_semaphore = Arc::new(Semaphore::new(config.max_procs.max(1)));
         let mut join_set: JoinSet<Result<()>> = JoinSet::new();
-        for ans_modu_name in ans_modu_names {
-            join_set.spawn(create_rust_package_project(
-                Arc::clone(&child_process_semaphore),
+        while let Some((name, doc)) = rx.recv().await {
+            let permit = Arc::clone(&generate_semaphore).acquire_owned().await?;
+            let project = create_rust_package_project(
                 Arc::clone(&file_rw_semaphore),
                 Arc::clone(&config),
-                ans_modu_name,
-            ));
+                collections.clone(),
+                name,
+                doc,
+            );
+            join_set.spawn(async move {
+                let _permit = permit;
+                project.await
+            });
         }
+        (sender.await?)?;
         while let Some(res) = join_set.join_next().await {
             (res?)?;
         }
@@ -159,6 +206,10 @@ pub struct ModuleSettings {
     pub use_cache: bool,
     /// The cache directory.
     pub cache_dir: PathBuf,
+    /// The file or directory of ansible-doc JSON to read instead of running `ansible-doc`.
+    pub doc_json: Option<PathBuf>,
+    /// Whether to get the documentation from `ansible-doc --metadata-dump`.
+    pub metadata_dump: bool,
     /// The name of the module.
     pub module_name: Option<String>,
     /// The regex for the module name.
@@ -183,6 +234,8 @@ impl ModuleSettings {
             },
             use_cache: !args.no_cache,
             cache_dir: args.cache_dir,
+            doc_json: args.doc_json,
+            metadata_dump: args.metadata_dump,
             module_name: args.module_name,
             module_name_regex: args.module_name_regex,
             module_name_exclude: args.module_name_exclude,
@@ -204,42 +257,56 @@ pub enum PkgUnit {
     None,
 }
 
-///
-/// # Arguments
-///
-/// * `name_regex` - e.g. '<namespace>\.<collection>\.*', '<namespace>\.*'
-///
-async fn match_module_name(
-    name_regex: &str,
-    exclude_regex: &[String],
-) -> Result<Vec<AnsibleModuleName>> {
-    let regex = Regex::new(format!("^{name_regex}$").as_str())
-        .with_context(|| format!("failed to parse match regex: {name_regex}"))?;
-
-    let exclude_regexes = exclude_regex
-        .iter()
-        .map(|exclude_regex| {
-            Regex::new(format!("^{exclude_regex}$").as_str())
-                .with_context(|| format!("failed to parse exclude regex: {exclude_regex}"))
+/// Modules to generate, selected by `--module-name`, `--module-name-regex` and `--module-name-exclude`.
+#[derive(Debug, Clone)]
+struct ModuleFilter {
+    /// e.g. '<namespace>\.<collection>\..*', '<namespace>\..*'.
+    regex: Regex,
+    exclude_regexes: Vec<Regex>,
+}
+
+impl ModuleFilter {
+    fn new(config: &ModuleSettings) -> Result<Self> {
+        let name_regex = match (
+            config.module_name.as_ref(),
+            config.module_name_regex.as_ref(),
+        ) {
+            (Some(modu_name), None) => {
+                AnsibleModuleName::new(modu_name)
+                    .with_context(|| format!("failed to parse module name: {modu_name}"))?;
+                regex::escape(modu_name)
+            }
+            (None, Some(regex)) => regex.to_owned(),
+            (None, None) => ".*".to_owned(),
+            (Some(_), Some(_)) => {
+                // Already rejected at argument parsing
+                bail!("failed to specify both module_name and module_name_regex");
+            }
+        };
+        let regex = Regex::new(format!("^{name_regex}$").as_str())
+            .with_context(|| format!("failed to parse match regex: {name_regex}"))?;
+        let exclude_regexes = config
+            .module_name_exclude
+            .iter()
+            .flatten()
+            .map(|exclude_regex| {
+                Regex::new(format!("^{exclude_regex}$").as_str())
+                    .with_context(|| format!("failed to parse exclude regex: {exclude_regex}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            regex,
+            exclude_regexes,
         })
-        .collect::<Result<Vec<_>>>()?;
+    }
 
-    let list_lines = get_ansible_modules_list().await?;
-    let ans_modu_names = list_lines
-        .iter()
-        .filter(|line| regex.is_match(line))
-        .filter(|line| {
-            !exclude_regexes
+    fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+            && !self
+                .exclude_regexes
                 .iter()
-                .any(|exclude_regex| exclude_regex.is_match(line))
-        })
-        .map(|line| {
-            let am_name = AnsibleModuleName::new(line)
-                .with_context(|| format!("failed to parse module name: {line}"))?;
-            Ok(am_name)
-        })
-        .collect::<Result<Vec<_>>>()?;
-    Ok(ans_modu_names)
+                .any(|exclude_regex| exclude_regex.is_match(name))
+    }
 }
 
 /// Create a module file written by Rust from the module json.
@@ -307,6 +374,11 @@ impl AnsibleModuleName {
         format!("{}.{}.{}", self.namespace, self.collection, self.module)
     }
 
+    /// e.g. `ansible.builtin`.
+    pub fn collection_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.collection)
+    }
+
     /// e.g.
     /// - `<pkg_prefix>_<namespace>`,
     /// - `<pkg_prefix>_<namespace>_<collection>`,
@@ -368,20 +440,18 @@ impl fmt::Display for AnsibleModuleName {
 ///                    |-- <module>.rs
 ///
 async fn create_rust_package_project(
-    child_process_semaphore: Arc<Semaphore>,
     file_rw_semaphore: Arc<Semaphore>,
     config: Arc<ModuleSettings>,
-    am_name: AnsibleModuleName,
+    collections: Option<Arc<Collections>>,
+    name: String,
+    doc: serde_json::Value,
 ) -> Result<()> {
+    let am_name = AnsibleModuleName::new(&name)
+        .with_context(|| format!("failed to parse module name: {name}"))?;
     println!("generate '{am_name}'");
-    let module_json = get_module_json(
-        Arc::clone(&child_process_semaphore),
-        &am_name,
-        config.use_cache,
-        &config.cache_dir,
-    )
-    .await
-    .with_context(|| format!("failed to get module json: {am_name}"))?;
+    let item: AnsModuleItem = serde_json::from_value(doc)
+        .with_context(|| format!("failed to parse ansible-doc output: {am_name}"))?;
+    let module_json = AnsModuleJson::from([(name, item)]);
 
     let pkg_unit = config.pkg_unit.as_ref();
     let pkg_prefix = config.pkg_prefix.as_str();
@@ -402,12 +472,17 @@ async fn create_rust_package_project(
     //     ansible/builtin/mod.rs -> pub mod debug;
     //
 
+    let collection_meta = collections
+        .as_deref()
+        .and_then(|collections| collections.get(&am_name.collection_name()));
+
     // Add 'pub use m::<namespace>::*' to root/src/lib.rs
     create_lib_rs(
         Arc::clone(&file_rw_semaphore),
         &lib_rs_path,
         &am_name,
         pkg_unit,
+        collection_meta.is_some(),
     )
     .await?;
     // Add 'pub mod <namespace>' to root/src/m/mod.rs
@@ -439,6 +514,19 @@ async fn create_rust_package_project(
         }),
     )
     .await?;
+    if let Some(meta) = collection_meta.as_ref() {
+        // Add 'pub const COLLECTION_VERSION' to root/src/m/<namespace>/<collection>/mod.rs
+        set_collection_version(
+            Arc::clone(&file_rw_semaphore),
+            &sub_mod_dir
+                .join(&am_name.namespace)
+                .join(&am_name.collection)
+                .join("mod.rs"),
+            &am_name,
+            &meta.version,
+        )
+        .await?;
+    }
 
     fs::create_dir_all(&pkg_dir).await.with_context(|| {
         format!(
@@ -451,6 +539,8 @@ async fn create_rust_package_project(
         &am_name,
         &pkg_dir,
         &pkg_name,
+        pkg_unit,
+        collections.as_deref(),
     )
     .await?;
 
@@ -480,6 +570,8 @@ async fn create_or_edit_cargo_toml(
     am_name: &AnsibleModuleName,
     pkg_dir: &Path,
     pkg_name: &str,
+    pkg_unit: Option<&PkgUnitSetting>,
+    collections: Option<&Collections>,
 ) -> Result<()> {
     let cargo_toml_path = pkg_dir.join("Cargo.toml");
 
@@ -567,7 +659,7 @@ rust-version = \"1.85\"
     let mut override_toml = toml_text
         .parse::<::toml_edit::DocumentMut>()
         .with_context(|| {
-            format!("Failed to parse toml as toml_edit::DocumentMut: {toml_text:?}",)
+            format!("Failed to parse toml as toml_edit::DocumentMut: {toml_text:?}")
         })?;
 
     // Set '[features]' as table
@@ -615,6 +707,9 @@ rust-version = \"1.85\"
 
         override_toml["features"] = ::toml_edit::Item::Table(features_table);
     };
+    if let Some(collections) = collections {
+        set_ansible_metadata(&mut override_toml, am_name, pkg_unit, collections);
+    }
 
     fs::write(&cargo_toml_path, override_toml.to_string())
         .await
@@ -623,6 +718,112 @@ rust-version = \"1.85\"
     Ok(())
 }
 
+/// Record the versions and galaxy metadata of ansible-core and the collection of a module.
+///
+/// ```toml
+/// [package]
+/// version = "9.5.0" # the collection version, if the package has a single collection
+///
+/// [package.metadata.ansible]
+/// ansible-core = "2.17.1"
+///
+/// [package.metadata.ansible.collections."community.general"]
+/// version = "9.5.0"
+/// authors = ["Ansible (https://github.com/ansible)"]
+/// license = ["GPL-3.0-or-later"]
+/// dependencies = {}
+/// ```
+#[expect(clippy::indexing_slicing, reason = "toml_edit convention")]
+fn set_ansible_metadata(
+    doc: &mut ::toml_edit::DocumentMut,
+    am_name: &AnsibleModuleName,
+    pkg_unit: Option<&PkgUnitSetting>,
+    collections: &Collections,
+) {
+    let implicit_table = || {
+        let mut table = ::toml_edit::Table::new();
+        table.set_implicit(true);
+        ::toml_edit::Item::Table(table)
+    };
+    let package = &mut doc["package"];
+    let ansible =
+        package["metadata"].or_insert(implicit_table())["ansible"].or_insert(::toml_edit::table());
+    ansible["ansible-core"] = ::toml_edit::value(collections.ansible_core.as_str());
+
+    let collection_name = am_name.collection_name();
+    let Some(meta) = collections.get(&collection_name) else {
+        return;
+    };
+    let mut table = ::toml_edit::Table::new();
+    table["version"] = ::toml_edit::value(meta.version.as_str());
+    if !meta.authors.is_empty() {
+        table["authors"] = ::toml_edit::value(meta.authors.iter().collect::<::toml_edit::Array>());
+    }
+    if !meta.license.is_empty() {
+        table["license"] = ::toml_edit::value(meta.license.iter().collect::<::toml_edit::Array>());
+    }
+    if let Some(dependencies) = meta.dependencies.as_ref() {
+        table["dependencies"] = ::toml_edit::value(
+            dependencies
+                .iter()
+                .map(|(name, version)| (name.as_str(), version.as_str()))
+                .collect::<::toml_edit::InlineTable>(),
+        );
+    }
+    ansible["collections"].or_insert(implicit_table())[&collection_name] =
+        ::toml_edit::Item::Table(table);
+
+    // A namespace package has the modules of several collections, so its version is kept
+    let single_collection = matches!(
+        pkg_unit,
+        Some(&PkgUnitSetting::Collection | &PkgUnitSetting::Module)
+    );
+    // Non-semver versions (such as '*' of a collection without a version) are not usable
+    if let (true, Ok(version)) = (single_collection, ::semver::Version::parse(&meta.version)) {
+        package["version"] = ::toml_edit::value(version.to_string());
+    }
+}
+
+/// Set `pub const COLLECTION_VERSION` in the `mod.rs` of a collection, replacing the previous one.
+async fn set_collection_version(
+    file_rw_semaphore: Arc<Semaphore>,
+    mod_rs_path: &Path,
+    am_name: &AnsibleModuleName,
+    version: &str,
+) -> Result<()> {
+    let doc = format!(
+        " Version of the `{}` collection the modules were generated from.",
+        am_name.collection_name()
+    );
+
+    let _permit = file_rw_semaphore.acquire_owned().await?;
+    let content = fs::read_to_string(mod_rs_path)
+        .await
+        .with_context(|| format!("failed to read mod.rs: {}", mod_rs_path.display()))?;
+    let content = {
+        let mut file = syn::parse_file(&content)
+            .with_context(|| format!("failed to parse mod.rs: {}", mod_rs_path.display()))?;
+        file.items.retain(|item| {
+            !matches!(item, syn::Item::Const(item_const) if item_const.ident == "COLLECTION_VERSION")
+        });
+        file.items.insert(
+            0,
+            syn::parse2(quote! {
+                #[doc = #doc]
+                pub const COLLECTION_VERSION: &str = #version;
+            })?,
+        );
+        quote! { #file }.to_string()
+    };
+    let formatted_content = format_code(&content)
+        .await
+        .with_context(|| format!("failed to format mod.rs: {}", mod_rs_path.display()))?;
+    fs::write(mod_rs_path, formatted_content)
+        .await
+        .with_context(|| format!("failed to write to mod.rs: {}", mod_rs_path.display()))?;
+    Ok(())
+}
+
 fn add_str_and_sort_array_without_duplication(
     arr_item: Option<&::toml_edit::Item>,
     values: Vec<String>,
@@ -665,8 +866,12 @@ fn add_str_and_sort_array_without_duplication(
 /// pub use m::<namespace>::<collection>::*;
 /// // or
 /// pub use m::<namespace>::<collection>::<module>::*;
+/// pub use m::<namespace>::<collection>::COLLECTION_VERSION;
 /// ```
 ///
+/// `COLLECTION_VERSION` of a collection is re-exported by the glob of the other units,
+/// as `<collection>::COLLECTION_VERSION` or `COLLECTION_VERSION`.
+///
 /// This function completely overwrites the existing lib.rs file.
 ///
 async fn create_lib_rs(
@@ -674,6 +879,7 @@ async fn create_lib_rs(
     lib_rs_path: &Path,
     am_name: &AnsibleModuleName,
     pkg_unit: Option<&PkgUnitSetting>,
+    collection_version: bool,
 ) -> Result<()> {
     let content = {
         let sub_mod_path = syn::parse_str::<syn::Path>(SUB_MOD_NAME)
@@ -703,10 +909,29 @@ async fn create_lib_rs(
             ),
         }
         .context("failed to parse pub use target path")?;
+        let pub_use_collection_version = match pkg_unit {
+            // pub use m::<namespace>::<collection>::COLLECTION_VERSION;
+            Some(&PkgUnitSetting::Module) if collection_version => {
+                let collection_path = syn::parse_str::<syn::Path>(
+                    format!(
+                        "crate::{}::{}::{}",
+                        SUB_MOD_NAME, am_name.namespace, am_name.collection
+                    )
+                    .as_str(),
+                )
+                .context("failed to parse collection path")?;
+                quote! { pub use #collection_path::COLLECTION_VERSION; }
+            }
+            Some(
+                &PkgUnitSetting::Namespace | &PkgUnitSetting::Collection | &PkgUnitSetting::Module,
+            )
+            | None => quote! {},
+        };
 
         quote! {
             mod #sub_mod_path;
             pub use #pub_use_target_path::*;
+            #pub_use_collection_version
         }
     }
     .to_string();
@@ -806,63 +1031,302 @@ async fn create_mod_rs(
     Ok(())
 }
 
-/// Get module json.
-///
-/// # Arguments
-///
-/// * `name` - [`AnsibleModuleName`]
-/// * `use_cache` - Whether to use cache
-/// * `cache_dir` - Cache directory
+/// Number of parsed module docs waiting for generation.
+const MODULE_DOC_CHANNEL_SIZE: usize = 64;
+
+/// ansible-doc JSON of a module, `(<module>, {"doc": ..., "examples": ..., "return": ...})`.
+type ModuleDoc = (String, serde_json::Value);
+
+/// Send the ansible-doc JSON of each module to generate.
 ///
-async fn get_module_json(
+/// Sources, in order of precedence:
+/// - `--doc-json`: files of ansible-doc JSON
+/// - `--metadata-dump`: a single `ansible-doc --metadata-dump`
+/// - otherwise: the cache, then `ansible-doc --json <module>...` for the rest,
+///   split into at most `--max-procs` processes
+async fn send_module_docs(
+    config: Arc<ModuleSettings>,
+    collections: Option<Arc<Collections>>,
+    filter: ModuleFilter,
     child_process_semaphore: Arc<Semaphore>,
-    name: &AnsibleModuleName,
-    use_cache: bool,
-    cache_dir: &PathBuf,
-) -> Result<AnsModuleJson> {
-    let name = name.fqdn();
-    let cache_file_path = cache_dir.join(&name);
-    let output_str = if use_cache && cache_file_path.exists() {
-        fs::read_to_string(&cache_file_path)
-            .await
-            .with_context(|| format!("failed to read cache file: {}", &cache_file_path.display()))?
-    } else {
-        let output = {
-            let _permit = child_process_semaphore.acquire_owned().await?;
-            Command::new("ansible-doc")
-                .args(["--json", name.as_str()])
-                .output()
-                .await
-                .with_context(|| format!("running 'ansible-doc --json {name}'"))?
+    tx: mpsc::Sender<ModuleDoc>,
+) -> Result<()> {
+    if let Some(doc_json) = config.doc_json.clone() {
+        let sent = tokio::task::spawn_blocking(move || send_doc_snapshot(&doc_json, &filter, &tx))
+            .await??;
+        return check_module_name_sent(config.module_name.as_deref(), &sent);
+    }
+    if config.metadata_dump {
+        let _permit = child_process_semaphore.acquire_owned().await?;
+        let sent = tokio::task::spawn_blocking(move || {
+            stream_ansible_doc(
+                &[
+                    "--metadata-dump".to_owned(),
+                    "--no-fail-on-errors".to_owned(),
+                ],
+                &filter,
+                None,
+                &tx,
+            )
+        })
+        .await??;
+        return check_module_name_sent(config.module_name.as_deref(), &sent);
+    }
+
+    let names = match config.module_name.clone() {
+        Some(name) => vec![name],
+        None => get_ansible_modules_list()
+            .await?
+            .into_iter()
+            .filter(|name| filter.is_match(name))
+            .collect(),
+    };
+    let cache = collections
+        .as_deref()
+        .filter(|_| config.use_cache)
+        .map(|collections| Cache::open(&config.cache_dir, AnsibleVersions::from(collections)))
+        .transpose()?
+        .map(Arc::new);
+    let mut uncached = Vec::new();
+    for name in names {
+        let doc = match cache.as_deref() {
+            Some(cache) => cache.get(&name)?,
+            None => None,
         };
-        if !output.status.success() {
-            bail!(
-                "failed to execute 'ansible-doc --json {name}': {}\n---stdout---\n{}\n---stderr---\n{}",
-                output.status,
-                String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr)
-            );
+        match doc {
+            Some(doc) => tx.send((name, doc)).await?,
+            None => uncached.push(name),
         }
-        let output_str = String::from_utf8_lossy(&output.stdout).to_string();
-        if use_cache {
-            // If use_cache is False, do not save to cache
-            fs::create_dir_all(cache_dir).await.with_context(|| {
-                format!("failed to create cache directory: {}", &cache_dir.display())
-            })?;
+    }
+    if uncached.is_empty() {
+        return Ok(());
+    }
 
-            // NOTE: No need to semaphore because it is only called at once for each module
-            fs::write(cache_file_path.with_extension("json"), &output_str)
-                .await
-                .with_context(|| {
-                    format!("failed to write cache file: {}", &cache_file_path.display())
-                })?;
-        }
+    let chunk_size = uncached.len().div_ceil(config.max_procs.max(1));
+    let mut join_set: JoinSet<Result<BTreeSet<String>>> = JoinSet::new();
+    for chunk in uncached.chunks(chunk_size) {
+        let mut args = vec![
+            "--json".to_owned(),
+            "--type".to_owned(),
+            "module".to_owned(),
+        ];
+        args.extend(chunk.iter().cloned());
+        let semaphore = Arc::clone(&child_process_semaphore);
+        let filter = filter.clone();
+        let cache = cache.clone();
+        let tx = tx.clone();
+        join_set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            tokio::task::spawn_blocking(move || {
+                stream_ansible_doc(&args, &filter, cache.as_deref(), &tx)
+            })
+            .await?
+        });
+    }
+    let mut sent = BTreeSet::new();
+    while let Some(res) = join_set.join_next().await {
+        sent.extend(res??);
+    }
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    let missing: Vec<&String> = uncached
+        .iter()
+        .filter(|name| !sent.contains(*name))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "ansible-doc returned no documentation for: {}",
+            missing
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Fail if the module given by `--module-name` was not found.
+fn check_module_name_sent(module_name: Option<&str>, sent: &BTreeSet<String>) -> Result<()> {
+    match module_name {
+        Some(name) if !sent.contains(name) => bail!("module '{name}' was not found"),
+        Some(_) | None => Ok(()),
+    }
+}
 
-        output_str
+/// Send the modules in ansible-doc JSON files: a file, or the `*.json` files in a directory (in name order).
+///
+/// Returns the names of the sent modules.
+fn send_doc_snapshot(
+    path: &Path,
+    filter: &ModuleFilter,
+    tx: &mpsc::Sender<ModuleDoc>,
+) -> Result<BTreeSet<String>> {
+    let files = if fs_err::metadata(path)?.is_dir() {
+        let mut files = Vec::new();
+        for entry in fs_err::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|ext| ext == "json") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
     };
-    let module_json: AnsModuleJson = serde_json::from_str(&output_str)
-        .with_context(|| format!("failed to parse ansible-doc output: {output_str}"))?;
-    Ok(module_json)
+
+    let mut names = BTreeSet::new();
+    let mut sent = BTreeSet::new();
+    for file in files {
+        let reader = std::io::BufReader::new(fs_err::File::open(&file)?);
+        for_each_module_doc(reader, |name, doc| {
+            if !names.insert(name.clone()) {
+                bail!("module '{name}' is defined again in {}", file.display());
+            }
+            if filter.is_match(&name) {
+                sent.insert(name.clone());
+                tx.blocking_send((name, doc))
+                    .map_err(|_closed| anyhow::anyhow!("module generation stopped"))?;
+            }
+            Ok(())
+        })
+        .with_context(|| format!("failed to parse ansible-doc json: {}", file.display()))?;
+    }
+    Ok(sent)
+}
+
+/// Run `ansible-doc <args>` and send each module in its output while parsing it.
+///
+/// Also puts each module in `cache` if given. Returns the names of the sent modules.
+fn stream_ansible_doc(
+    args: &[String],
+    filter: &ModuleFilter,
+    cache: Option<&Cache>,
+    tx: &mpsc::Sender<ModuleDoc>,
+) -> Result<BTreeSet<String>> {
+    let command = format!("ansible-doc {}", args.join(" "));
+    let mut child = std::process::Command::new("ansible-doc")
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running '{command}'"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .with_context(|| format!("failed to take stdout: {command}"))?;
+
+    let mut sent = BTreeSet::new();
+    let parsed = for_each_module_doc(std::io::BufReader::new(stdout), |name, doc| {
+        if !filter.is_match(&name) {
+            return Ok(());
+        }
+        if let Some(cache) = cache {
+            cache.put(&name, &doc)?;
+        }
+        sent.insert(name.clone());
+        tx.blocking_send((name, doc))
+            .map_err(|_closed| anyhow::anyhow!("module generation stopped"))
+    });
+    // stdout is closed, so the process exits even if parsing failed
+    let status = child
+        .wait()
+        .with_context(|| format!("running '{command}'"))?;
+    parsed.with_context(|| format!("failed to parse output of '{command}'"))?;
+    if !status.success() {
+        bail!("failed to execute '{command}': {status}");
+    }
+    Ok(sent)
+}
+
+/// Call `f` with each module in ansible-doc JSON, without reading the whole JSON into memory.
+///
+/// Accepts the output of `ansible-doc --json <module>...` (`{"<module>": {...}}`)
+/// and of `ansible-doc --metadata-dump` (`{"all": {"module": {"<module>": {...}}}, "errors": [...]}`).
+fn for_each_module_doc<R, F>(reader: R, mut f: F) -> Result<()>
+where
+    R: std::io::Read,
+    F: FnMut(String, serde_json::Value) -> Result<()>,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    serde::de::DeserializeSeed::deserialize(
+        ModuleDocVisitor {
+            level: ModuleDocLevel::Root,
+            f: &mut f,
+        },
+        &mut deserializer,
+    )?;
+    deserializer.end()?;
+    Ok(())
+}
+
+/// Where [`ModuleDocVisitor`] is in ansible-doc JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModuleDocLevel {
+    /// `{"<module>": ..., "all": ..., "errors": ...}`.
+    Root,
+    /// `{"module": ..., "role": ..., ...}` in `all` of `--metadata-dump`.
+    PluginTypes,
+    /// `{"<module>": ...}` in `all.module` of `--metadata-dump`.
+    Modules,
+}
+
+/// Streaming visitor of [`for_each_module_doc`].
+struct ModuleDocVisitor<'a, F> {
+    level: ModuleDocLevel,
+    f: &'a mut F,
+}
+
+impl<'de, F> serde::de::DeserializeSeed<'de> for ModuleDocVisitor<'_, F>
+where
+    F: FnMut(String, serde_json::Value) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "only a map is expected")]
+impl<'de, F> serde::de::Visitor<'de> for ModuleDocVisitor<'_, F>
+where
+    F: FnMut(String, serde_json::Value) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ansible-doc json")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            let level = match (self.level, key.as_str()) {
+                (ModuleDocLevel::Root, "all") => Some(ModuleDocLevel::PluginTypes),
+                (ModuleDocLevel::PluginTypes, "module") => Some(ModuleDocLevel::Modules),
+                (ModuleDocLevel::Root | ModuleDocLevel::Modules, _) if key.contains('.') => None,
+                // `errors`, other plugin types
+                (
+                    ModuleDocLevel::Root | ModuleDocLevel::PluginTypes | ModuleDocLevel::Modules,
+                    _,
+                ) => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                    continue;
+                }
+            };
+            if let Some(level) = level {
+                map.next_value_seed(ModuleDocVisitor {
+                    level,
+                    f: &mut *self.f,
+                })?;
+            } else {
+                let doc = map.next_value::<serde_json::Value>()?;
+                (self.f)(key, doc).map_err(|e| serde::de::Error::custom(format!("{e:#}")))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 /// list all ansible module names accessible by ansible-doc.
@@ -895,24 +1359,459 @@ type AnsModuleJson = IndexMap<String, AnsModuleItem>;
 struct AnsModuleItem {
     /// 'doc' field.
     pub doc: AnsModuleDoc,
+    /// 'examples' field.
+    #[serde(default)]
+    pub examples: Option<String>,
+    /// 'return' field.
+    #[serde(default, rename = "return")]
+    pub return_: Option<IndexMap<String, AnsReturnValue>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// return value field.
+struct AnsReturnValue {
+    /// 'description' field.
+    #[serde(default, deserialize_with = "deserialize_paragraphs")]
+    pub description: Vec<String>,
+    /// 'returned' field.
+    #[serde(default)]
+    pub returned: Option<serde_json::Value>,
+    /// 'type' field.
+    #[serde(default, rename = "type")]
+    pub type_: Option<String>,
+    /// 'elements' field, the type of the elements of a list.
+    #[serde(default)]
+    pub elements: Option<String>,
+    /// 'sample' field.
+    #[serde(default)]
+    pub sample: Option<serde_json::Value>,
+    /// 'contains' field, the values in a dict (or in the elements of a list).
+    #[serde(default)]
+    pub contains: IndexMap<String, Self>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// doc field.
 struct AnsModuleDoc {
+    /// '`short_description`' field.
+    #[serde(default)]
+    pub short_description: Option<String>,
+    /// 'description' field.
+    #[serde(default, deserialize_with = "deserialize_paragraphs")]
+    pub description: Vec<String>,
+    /// 'notes' field.
+    #[serde(default, deserialize_with = "deserialize_paragraphs")]
+    pub notes: Vec<String>,
+    /// '`version_added`' field.
+    #[serde(default)]
+    pub version_added: Option<serde_json::Value>,
+    /// 'deprecated' field.
+    #[serde(default)]
+    pub deprecated: Option<serde_json::Value>,
     /// 'options' field.
     pub options: Option<IndexMap<String, AnsModuleDocOption>>,
+    /// '`mutually_exclusive`' field.
+    #[serde(default)]
+    pub mutually_exclusive: Vec<Vec<String>>,
+    /// '`required_together`' field.
+    #[serde(default)]
+    pub required_together: Vec<Vec<String>>,
+    /// '`required_one_of`' field.
+    #[serde(default)]
+    pub required_one_of: Vec<Vec<String>>,
+    /// '`required_if`' field. Each item is `[key, value, [requirements], is_one_of?]`.
+    #[serde(default)]
+    pub required_if: Vec<Vec<serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// doc option field.
 struct AnsModuleDocOption {
-    // TODO: add description field
-    // #[serde(default)]
-    // pub description: Vec<String>,
+    /// 'aliases' field.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// 'deprecated' field.
+    #[serde(default)]
+    pub deprecated: Option<serde_json::Value>,
+    /// '`removed_in_version`' field.
+    #[serde(default)]
+    pub removed_in_version: Option<serde_json::Value>,
+    /// '`removed_at_date`' field.
+    #[serde(default)]
+    pub removed_at_date: Option<serde_json::Value>,
+    /// 'description' field.
+    #[serde(default, deserialize_with = "deserialize_paragraphs")]
+    pub description: Vec<String>,
+    /// '`version_added`' field.
+    #[serde(default)]
+    pub version_added: Option<serde_json::Value>,
     /// 'type' field.
     #[serde(default, rename = "type")]
     pub type_: Option<String>,
+    /// 'required' field.
+    #[serde(default)]
+    pub required: bool,
+    /// 'choices' field.
+    #[serde(default)]
+    pub choices: Vec<serde_json::Value>,
+    /// 'default' field.
+    #[serde(default)]
+    pub default: Option<serde_json::Value>,
+    /// 'elements' field, the type of the elements of a list.
+    #[serde(default)]
+    pub elements: Option<String>,
+    /// 'suboptions' field, the options of a dict (or of the elements of a list).
+    #[serde(default)]
+    pub suboptions: IndexMap<String, Self>,
+}
+
+/// Deserialize a text field of ansible-doc, which is a string or a list of paragraphs.
+fn deserialize_paragraphs<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Option::<Text>::deserialize(deserializer)? {
+        Some(Text::One(text)) => vec![text],
+        Some(Text::Many(texts)) => texts,
+        None => Vec::new(),
+    })
+}
+
+/// Rustdoc of generated modules, converted from ansible-doc.
+struct DocFormatter {
+    /// Ansible markup such as `C(value)` and `L(text,url)`.
+    markup: Regex,
+}
+
+impl DocFormatter {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            markup: Regex::new(r"\b(RV|[BCEILMOPUV])\(([^)]*)\)")
+                .with_context(|| "failed to parse markup regex")?,
+        })
+    }
+
+    /// Convert Ansible markup to Markdown.
+    ///
+    /// See <https://docs.ansible.com/ansible/latest/dev_guide/ansible_markup.html>.
+    fn markdown(&self, text: &str) -> String {
+        self.markup
+            .replace_all(text, |caps: &regex::Captures<'_>| {
+                let (kind, arg) = (&caps[1], &caps[2]);
+                match kind {
+                    "I" => format!("*{arg}*"),
+                    "B" => format!("**{arg}**"),
+                    "U" => format!("<{arg}>"),
+                    "L" => arg.rsplit_once(',').map_or_else(
+                        || format!("<{arg}>"),
+                        |(label, url)| format!("[{}]({})", label.trim(), url.trim()),
+                    ),
+                    // C, E, M, O, P, RV, V
+                    _ => format!("`{arg}`"),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Append `text` as a paragraph.
+    fn push_paragraph(&self, lines: &mut Vec<String>, text: &str) {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(text.lines().map(|line| self.markdown(line.trim_end())));
+    }
+
+    /// Rustdoc of the `Module` struct.
+    fn module_doc(&self, module_name: &str, item: &AnsModuleItem) -> TokenStream {
+        let doc = &item.doc;
+        let mut lines = Vec::new();
+        self.push_paragraph(
+            &mut lines,
+            doc.short_description.as_deref().unwrap_or(module_name),
+        );
+        for paragraph in &doc.description {
+            self.push_paragraph(&mut lines, paragraph);
+        }
+        push_version_added(&mut lines, doc.version_added.as_ref());
+        if !doc.notes.is_empty() {
+            lines.extend([String::new(), "# Notes".to_owned(), String::new()]);
+            lines.extend(
+                doc.notes
+                    .iter()
+                    .map(|note| format!("- {}", self.markdown(note))),
+            );
+        }
+        if let Some(examples) = item
+            .examples
+            .as_deref()
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            lines.extend([
+                String::new(),
+                "# Examples".to_owned(),
+                String::new(),
+                "```text".to_owned(),
+            ]);
+            lines.extend(examples.lines().map(str::to_owned));
+            lines.push("```".to_owned());
+        }
+        doc_attrs(&lines)
+    }
+
+    /// Rustdoc of an option field.
+    fn option_doc(&self, option: &AnsModuleDocOption) -> TokenStream {
+        let mut lines = Vec::new();
+        for paragraph in &option.description {
+            self.push_paragraph(&mut lines, paragraph);
+        }
+        if let Some(default) = option.default.as_ref().filter(|v| !v.is_null()) {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("Default: `{default}`"));
+        }
+        if !option.aliases.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            let aliases: Vec<String> = option.aliases.iter().map(|a| format!("`{a}`")).collect();
+            lines.push(format!("Aliases: {}", aliases.join(", ")));
+        }
+        push_version_added(&mut lines, option.version_added.as_ref());
+        doc_attrs(&lines)
+    }
+
+    /// Rustdoc of a return value field.
+    fn return_doc(&self, value: &AnsReturnValue) -> TokenStream {
+        let mut lines = Vec::new();
+        for paragraph in &value.description {
+            self.push_paragraph(&mut lines, paragraph);
+        }
+        if let Some(returned) = value.returned.as_ref().filter(|v| !v.is_null()) {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!(
+                "Returned: {}",
+                self.markdown(&doc_scalar(returned))
+            ));
+        }
+        if let Some(sample) = value.sample.as_ref().filter(|v| !v.is_null()) {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(["Sample:".to_owned(), String::new(), "```json".to_owned()]);
+            lines.extend(
+                serde_json::to_string_pretty(sample)
+                    .unwrap_or_else(|_| sample.to_string())
+                    .lines()
+                    .map(str::to_owned),
+            );
+            lines.push("```".to_owned());
+        }
+        doc_attrs(&lines)
+    }
+
+    /// `#[deprecated]` attribute, if deprecated.
+    ///
+    /// `deprecated` is a bool, a string or a dict
+    /// (`why`, `alternative`, `removed_in` (or `version`) and `removed_at_date`).
+    fn deprecated_attr(
+        &self,
+        deprecated: Option<&serde_json::Value>,
+        removed_in_version: Option<&serde_json::Value>,
+        removed_at_date: Option<&serde_json::Value>,
+    ) -> Option<TokenStream> {
+        let deprecated = deprecated.filter(|v| !v.is_null() && v.as_bool() != Some(false));
+        if deprecated.is_none() && removed_in_version.is_none() && removed_at_date.is_none() {
+            return None;
+        }
+        let field = |key: &str| {
+            deprecated
+                .and_then(|v| v.get(key))
+                .filter(|v| !v.is_null())
+                .map(doc_scalar)
+        };
+        let mut note = Vec::new();
+        if let Some(why) =
+            field("why").or_else(|| deprecated.and_then(|v| v.as_str()).map(str::to_owned))
+        {
+            note.push(format!("{}.", why.trim_end_matches('.')));
+        }
+        if let Some(alternative) = field("alternative") {
+            note.push(format!(
+                "Alternative: {}.",
+                alternative.trim_end_matches('.')
+            ));
+        }
+        let removal = field("removed_in")
+            .or_else(|| field("version"))
+            .or_else(|| removed_in_version.map(doc_scalar))
+            .map(|version| format!("Will be removed in version {version}."))
+            .or_else(|| {
+                field("removed_at_date")
+                    .or_else(|| removed_at_date.map(doc_scalar))
+                    .map(|date| format!("Will be removed after {date}."))
+            });
+        note.extend(removal);
+        let note = if note.is_empty() {
+            "Deprecated".to_owned()
+        } else {
+            self.markdown(&note.join(" "))
+        };
+        Some(quote! { #[deprecated(note = #note)] })
+    }
+}
+
+/// Append 'Added in version X.' unless the version is unknown ('historical').
+fn push_version_added(lines: &mut Vec<String>, version_added: Option<&serde_json::Value>) {
+    let Some(version) = version_added
+        .map(doc_scalar)
+        .filter(|v| !v.is_empty() && v != "historical")
+    else {
+        return;
+    };
+    if !lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines.push(format!("Added in version {version}."));
+}
+
+/// `#[doc = "..."]` attributes of the lines.
+fn doc_attrs(lines: &[String]) -> TokenStream {
+    let attrs = lines.iter().map(|line| {
+        // Same as `/// line`
+        let line = if line.is_empty() {
+            String::new()
+        } else {
+            format!(" {line}")
+        };
+        quote! { #[doc = #line] }
+    });
+    quote! { #(#attrs)* }
+}
+
+/// A value of 'choices' (or '`required_if`') as written in [`cdk_ansible_core::core::OptionSpec::choices`].
+fn doc_scalar(value: &serde_json::Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), str::to_owned)
+}
+
+/// `OptionSpec`s of `options`, with the specs of their suboptions.
+fn generate_option_specs(options: &IndexMap<String, AnsModuleDocOption>) -> Vec<TokenStream> {
+    options
+        .iter()
+        .map(|(name, option)| {
+            let type_ = option.type_.clone().unwrap_or_else(|| "str".to_owned());
+            // 'free_form' is not a real argument; it can be given as 'cmd' or '_raw_params' instead.
+            let required = option.required && name != "free_form";
+            let choices = option.choices.iter().map(doc_scalar);
+            let aliases = &option.aliases;
+            let default = option
+                .default
+                .as_ref()
+                .filter(|v| !v.is_null())
+                .map_or_else(
+                    || quote! { None },
+                    |value| {
+                        let value = value.to_string();
+                        quote! { Some(#value) }
+                    },
+                );
+            let suboptions = if option.suboptions.is_empty() {
+                quote! { None }
+            } else {
+                let suboptions = generate_option_specs(&option.suboptions);
+                quote! {
+                    Some(&::cdk_ansible_core::core::ArgumentSpec {
+                        options: &[#(#suboptions),*],
+                        mutually_exclusive: &[],
+                        required_together: &[],
+                        required_one_of: &[],
+                        required_if: &[],
+                    })
+                }
+            };
+            quote! {
+                ::cdk_ansible_core::core::OptionSpec {
+                    name: #name,
+                    aliases: &[#(#aliases),*],
+                    type_: #type_,
+                    required: #required,
+                    choices: &[#(#choices),*],
+                    default: #default,
+                    suboptions: #suboptions,
+                }
+            }
+        })
+        .collect()
+}
+
+/// `ARGUMENT_SPEC` of the generated module.
+fn generate_argument_spec(doc: &AnsModuleDoc) -> Result<TokenStream> {
+    let options = doc
+        .options
+        .as_ref()
+        .map(generate_option_specs)
+        .unwrap_or_default();
+    let groups = |groups: &[Vec<String>]| {
+        let groups = groups.iter().map(|group| quote! { &[#(#group),*] });
+        quote! { &[#(#groups),*] }
+    };
+    let mutually_exclusive = groups(&doc.mutually_exclusive);
+    let required_together = groups(&doc.required_together);
+    let required_one_of = groups(&doc.required_one_of);
+    let required_if = doc
+        .required_if
+        .iter()
+        .map(|rule| {
+            let (Some(key), Some(value), Some(requirements)) = (
+                rule.first().and_then(|v| v.as_str()),
+                rule.get(1),
+                rule.get(2).and_then(|v| v.as_array()),
+            ) else {
+                bail!("invalid required_if: {rule:?}");
+            };
+            let value = doc_scalar(value);
+            let requirements = requirements
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .with_context(|| format!("invalid required_if: {rule:?}"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let any = rule
+                .get(3)
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            Ok(quote! {
+                ::cdk_ansible_core::core::RequiredIf {
+                    key: #key,
+                    value: #value,
+                    requirements: &[#(#requirements),*],
+                    any: #any,
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! {
+        /// Options of the module, checked by [`TaskModule::validate`]
+        pub const ARGUMENT_SPEC: ::cdk_ansible_core::core::ArgumentSpec =
+            ::cdk_ansible_core::core::ArgumentSpec {
+            options: &[#(#options),*],
+            mutually_exclusive: #mutually_exclusive,
+            required_together: #required_together,
+            required_one_of: #required_one_of,
+            required_if: &[#(#required_if),*],
+        };
+    })
 }
 
 /// generate module rs.
@@ -926,70 +1825,36 @@ async fn generate_module_rs(module_json: &AnsModuleJson) -> Result<String> {
         bail!("module_json does not have any key: {module_json:?}")
     };
 
+    let item = module_json
+        .get(module_name)
+        .with_context(|| format!("module name not found: {module_name}"))?;
+    let doc = &item.doc;
     let content = {
-        let struct_attributes = module_json
-            .get(module_name)
-            .with_context(|| format!("module name not found: {module_name}"))?
-            .doc
-            .options
-            .clone()
-            // If no options, return empty IndexMap
-            .unwrap_or_else(IndexMap::new)
-            .iter()
-            .map(|(key, value)| {
-                let key_ident = format_ident!("{}", {
-                    let mut boundary = Boundary::defaults().to_vec();
-                    boundary.push(ACRONYM_WITH_TWO_LOWER); // AAbb -> a_abb (allow 'IPv4' to be 'ipv4')
-                    escape_rust_reserved_keywords(
-                        &key
-                            // TODO: configure variable name's replacement rules from optional args
-                            .replace('-', "_xx_")
-                            .replace('+', "_xxx_")
-                            .as_str()
-                            .from_case(Case::Camel)
-                            .with_boundaries(&boundary)
-                            .without_boundaries(&[
-                                // Boundary::UPPER_DIGIT, // A1
-                                Boundary::LOWER_DIGIT, // a1 (allow 'IPv4' to be 'ipv4')
-                                // Boundary::DIGIT_LOWER, // 1a
-                                // Boundary::DIGIT_UPPER, // 1A
-                                Boundary::ACRONYM, // AAa (I don't expect 'IPv4' to 'i_pv4')
-                            ])
-                            .to_case(Case::Snake),
-                    )
-                });
-                let type_ident = syn::parse_str::<syn::Type>(
-                    match value
-                        .type_
-                        .clone()
-                        // If type is not set, implicitly set "str"
-                        .unwrap_or_else(|| "str".to_owned())
-                        .as_str()
-                    {
-                        // always include "string" because ansible can use template.
-                        // types are defined in `cdk-ansible-core/src/core/types.rs`
-                        "path" => "OptU<::cdk_ansible_core::core::StringOrPath>",
-                        "int" | "integer" => "OptU<::cdk_ansible_core::core::IntOrString>",
-                        "bool" | "boolean" => "OptU<::cdk_ansible_core::core::BoolOrString>",
-                        "list" => "OptU<::cdk_ansible_core::core::StringOrVec>",
-                        "dict" => "OptU<::cdk_ansible_core::core::StringOrMap>",
-                        "str" | "string" => "OptU<String>",
-                        // default should be [`OptU<String>`]
-                        _ => "OptU<::serde_json::Value>",
-                    },
-                )
-                .with_context(|| format!("failed to parse type: {:?}", value.type_))?;
-                let raw_key_ident = key.to_owned();
-                Ok(quote! {
-                    #[serde(
-                        default = "OptU::default",
-                        skip_serializing_if = "OptU::is_unset",
-                        rename = #raw_key_ident
-                    )]
-                    pub #key_ident: #type_ident,
-                })
-            })
-            .collect::<Result<Vec<_>>>()?;
+        // Not `Send`, so must be dropped before `.await`
+        let mut type_generator = TypeGenerator::new()?;
+        let module_doc = type_generator.doc_formatter.module_doc(module_name, item);
+        let module_deprecated =
+            type_generator
+                .doc_formatter
+                .deprecated_attr(doc.deprecated.as_ref(), None, None);
+        // The module itself uses the deprecated `Module`
+        let allow_deprecated = module_deprecated
+            .as_ref()
+            .map(|_| quote! { #[allow(deprecated)] });
+        let opt_doc = format!(" Options of [`Module`] (`{module_name}`)");
+        let argument_spec = generate_argument_spec(doc)?;
+        let struct_attributes = type_generator.fields(
+            "",
+            // If no options, generate an empty struct
+            &doc.options.clone().unwrap_or_default(),
+        )?;
+        let return_fields = type_generator.return_fields(
+            "Return",
+            // If no return values, generate an empty struct
+            &item.return_.clone().unwrap_or_default(),
+        )?;
+        let return_doc = format!(" Return values of [`Module`] (`{module_name}`)");
+        let type_defs = &type_generator.type_defs;
 
         let token_streams = vec![quote! {
             #[allow(unused_imports, reason = "Some modules may have empty `options` field")]
@@ -997,13 +1862,22 @@ async fn generate_module_rs(module_json: &AnsModuleJson) -> Result<String> {
             use cdk_ansible_core::core::TaskModule;
             use serde::Serialize;
 
+            #module_doc
+            #module_deprecated
             #[derive(Clone, Debug, Serialize)]
             pub struct Module {
                 #[serde(rename = #module_name)]
                 pub module: Args,
             }
 
-            impl TaskModule for Module {}
+            #allow_deprecated
+            impl TaskModule for Module {
+                fn validate(&self) -> Result<(), ::cdk_ansible_core::core::ArgumentError> {
+                    ARGUMENT_SPEC.validate_args(&self.module)
+                }
+            }
+
+            #argument_spec
 
             #[derive(Clone, Debug, Serialize)]
             pub struct Args {
@@ -1011,11 +1885,20 @@ async fn generate_module_rs(module_json: &AnsModuleJson) -> Result<String> {
                 pub options: Opt,
             }
 
+            #[doc = #opt_doc]
             #[derive(Clone, Debug, Default, Serialize)]
             #[serde(rename_all = "snake_case")]
             pub struct Opt {
                 #(#struct_attributes)*
             }
+
+            #[doc = #return_doc]
+            #[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize)]
+            pub struct Return {
+                #(#return_fields)*
+            }
+
+            #(#type_defs)*
         }];
 
         quote! {
@@ -1030,6 +1913,369 @@ async fn generate_module_rs(module_json: &AnsModuleJson) -> Result<String> {
     Ok(formatted_code)
 }
 
+/// Type names used in generated modules, which generated types must not take.
+const RESERVED_TYPE_NAMES: &[&str] = &[
+    "Module",
+    "Return",
+    "Args",
+    "Opt",
+    "OptU",
+    "TaskModule",
+    "Serialize",
+    "String",
+    "Option",
+    "Some",
+    "None",
+    "Result",
+    "Ok",
+    "Err",
+    "Self",
+    "Vec",
+    "Box",
+];
+
+/// Generates the fields of `Opt` and the types they use
+/// (enums for 'choices' and structs for 'suboptions').
+struct TypeGenerator {
+    doc_formatter: DocFormatter,
+    /// Names of generated types, which must not conflict with each other.
+    type_names: BTreeSet<String>,
+    /// Definitions of generated types.
+    type_defs: Vec<TokenStream>,
+}
+
+impl TypeGenerator {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            doc_formatter: DocFormatter::new()?,
+            type_names: RESERVED_TYPE_NAMES
+                .iter()
+                .map(|name| (*name).to_owned())
+                .collect(),
+            type_defs: Vec::new(),
+        })
+    }
+
+    /// Fields of a struct for `options`.
+    ///
+    /// Types generated for the options are named `<prefix><Option>`.
+    fn fields(
+        &mut self,
+        prefix: &str,
+        options: &IndexMap<String, AnsModuleDocOption>,
+    ) -> Result<Vec<TokenStream>> {
+        let mut fields = Vec::new();
+        // Aliases are not fields, but distinct options may still have the same field name
+        let mut field_names = BTreeSet::new();
+        for (key, option) in options {
+            let key_ident = format_ident!("{}", unique_name(&field_name(key), &mut field_names));
+            let type_ident = self.option_type(prefix, key, option)?;
+            let raw_key_ident = key.to_owned();
+            let field_doc = self.doc_formatter.option_doc(option);
+            let deprecated = self.doc_formatter.deprecated_attr(
+                option.deprecated.as_ref(),
+                option.removed_in_version.as_ref(),
+                option.removed_at_date.as_ref(),
+            );
+            fields.push(quote! {
+                #field_doc
+                #deprecated
+                #[serde(
+                    default = "OptU::default",
+                    skip_serializing_if = "OptU::is_unset",
+                    rename = #raw_key_ident
+                )]
+                pub #key_ident: OptU<#type_ident>,
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Type of an option (without [`cdk_ansible_core::core::OptU`]).
+    ///
+    /// Types always accept a string, because Ansible can use a template in place of any value.
+    fn option_type(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        option: &AnsModuleDocOption,
+    ) -> Result<syn::Type> {
+        // If type is not set, implicitly set "str"
+        let type_ = option.type_.as_deref().unwrap_or("str");
+        match type_ {
+            "list" => {
+                let Some(elements) = option.elements.as_deref() else {
+                    return Ok(syn::parse_quote! { ::cdk_ansible_core::core::StringOrVec });
+                };
+                let element_type = self.element_type(prefix, key, option, elements)?;
+                Ok(syn::parse_quote! { ::cdk_ansible_core::core::StringOr<Vec<#element_type>> })
+            }
+            "dict" if !option.suboptions.is_empty() => {
+                let struct_ident = self.suboptions_struct(prefix, key, option)?;
+                Ok(syn::parse_quote! { ::cdk_ansible_core::core::StringOr<#struct_ident> })
+            }
+            _ => self.scalar_type(prefix, key, option, type_),
+        }
+    }
+
+    /// Type of an element of a list option.
+    fn element_type(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        option: &AnsModuleDocOption,
+        elements: &str,
+    ) -> Result<syn::Type> {
+        match elements {
+            "dict" if !option.suboptions.is_empty() => {
+                let struct_ident = self.suboptions_struct(prefix, key, option)?;
+                Ok(syn::parse_quote! { #struct_ident })
+            }
+            _ => self.scalar_type(prefix, key, option, elements),
+        }
+    }
+
+    /// Type of a value of `type_`, which is not a list or a dict with suboptions.
+    fn scalar_type(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        option: &AnsModuleDocOption,
+        type_: &str,
+    ) -> Result<syn::Type> {
+        let type_path = match type_ {
+            "str" | "string" => {
+                if let Some(enum_ident) = self.choice_enum(prefix, key, option) {
+                    return Ok(syn::parse_quote! { #enum_ident });
+                }
+                "String"
+            }
+            // types are defined in `cdk-ansible-core/src/core/types.rs`
+            "path" => "::cdk_ansible_core::core::StringOrPath",
+            "int" | "integer" => "::cdk_ansible_core::core::IntOrString",
+            "bool" | "boolean" => "::cdk_ansible_core::core::BoolOrString",
+            "list" => "::cdk_ansible_core::core::StringOrVec",
+            "dict" => "::cdk_ansible_core::core::StringOrMap",
+            _ => "::serde_json::Value",
+        };
+        syn::parse_str::<syn::Type>(type_path)
+            .with_context(|| format!("failed to parse type: {type_path}"))
+    }
+
+    /// Enum for an option with string 'choices'.
+    ///
+    /// Each choice becomes a unit variant renamed to the exact Ansible string,
+    /// and `String(String)` is kept for templated values (`{{ ... }}`) as [`cdk_ansible_core::core::BoolOrString`] does.
+    /// Returns `None` if the option has no choices or its choices are not all strings.
+    fn choice_enum(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        option: &AnsModuleDocOption,
+    ) -> Option<syn::Ident> {
+        if option.choices.is_empty() {
+            return None;
+        }
+        let choices = option
+            .choices
+            .iter()
+            .map(serde_json::Value::as_str)
+            .collect::<Option<Vec<_>>>()?;
+
+        let enum_ident = self.type_ident(prefix, key);
+        let mut variant_names = BTreeSet::from(["String".to_owned()]);
+        let variants = choices.iter().map(|choice| {
+            let variant_ident = format_ident!(
+                "{}",
+                unique_name(&pascal_case_name(choice), &mut variant_names)
+            );
+            quote! {
+                #[serde(rename = #choice)]
+                #variant_ident,
+            }
+        });
+        let doc = format!(" Choices of `{key}`");
+        self.type_defs.push(quote! {
+            #[doc = #doc]
+            #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+            pub enum #enum_ident {
+                #(#variants)*
+                /// Templated value (`{{ ... }}`)
+                #[serde(untagged)]
+                String(String),
+            }
+        });
+        Some(enum_ident)
+    }
+
+    /// Struct for a dict option with 'suboptions', generated recursively.
+    fn suboptions_struct(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        option: &AnsModuleDocOption,
+    ) -> Result<syn::Ident> {
+        let struct_ident = self.type_ident(prefix, key);
+        let fields = self.fields(&struct_ident.to_string(), &option.suboptions)?;
+        let doc = format!(" Suboptions of `{key}`");
+        self.type_defs.push(quote! {
+            #[doc = #doc]
+            #[derive(Clone, Debug, Default, Serialize)]
+            #[serde(rename_all = "snake_case")]
+            pub struct #struct_ident {
+                #(#fields)*
+            }
+
+            impl From<#struct_ident> for ::cdk_ansible_core::core::StringOr<#struct_ident> {
+                fn from(value: #struct_ident) -> Self {
+                    Self::Value(value)
+                }
+            }
+        });
+        Ok(struct_ident)
+    }
+
+    /// Fields of a struct for return values.
+    ///
+    /// Values are not always returned, so every field is an [`Option`].
+    fn return_fields(
+        &mut self,
+        prefix: &str,
+        values: &IndexMap<String, AnsReturnValue>,
+    ) -> Result<Vec<TokenStream>> {
+        let mut fields = Vec::new();
+        let mut field_names = BTreeSet::new();
+        for (key, value) in values {
+            let key_ident = format_ident!("{}", unique_name(&field_name(key), &mut field_names));
+            let type_ident = self.return_type(prefix, key, value)?;
+            let field_doc = self.doc_formatter.return_doc(value);
+            fields.push(quote! {
+                #field_doc
+                #[serde(default, rename = #key)]
+                pub #key_ident: Option<#type_ident>,
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Type of a return value, which is a resolved value (not a template).
+    fn return_type(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        value: &AnsReturnValue,
+    ) -> Result<syn::Type> {
+        let type_ = value.type_.as_deref().unwrap_or("str");
+        Ok(match type_ {
+            "list" => {
+                let element_type = if value.contains.is_empty() {
+                    return_scalar_type(value.elements.as_deref().unwrap_or("raw"))
+                } else {
+                    let struct_ident = self.return_struct(prefix, key, value)?;
+                    syn::parse_quote! { #struct_ident }
+                };
+                syn::parse_quote! { Vec<#element_type> }
+            }
+            "dict" | "complex" if !value.contains.is_empty() => {
+                let struct_ident = self.return_struct(prefix, key, value)?;
+                syn::parse_quote! { #struct_ident }
+            }
+            _ => return_scalar_type(type_),
+        })
+    }
+
+    /// Struct for a return value with 'contains', generated recursively.
+    fn return_struct(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        value: &AnsReturnValue,
+    ) -> Result<syn::Ident> {
+        let struct_ident = self.type_ident(prefix, key);
+        let fields = self.return_fields(&struct_ident.to_string(), &value.contains)?;
+        let doc = format!(" Values in `{key}`");
+        self.type_defs.push(quote! {
+            #[doc = #doc]
+            #[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize)]
+            pub struct #struct_ident {
+                #(#fields)*
+            }
+        });
+        Ok(struct_ident)
+    }
+
+    /// Unique name of a type generated for the option `key`.
+    fn type_ident(&mut self, prefix: &str, key: &str) -> syn::Ident {
+        let name = format!("{prefix}{}", pascal_case_name(key));
+        format_ident!("{}", unique_name(&name, &mut self.type_names))
+    }
+}
+
+/// Type of a return value of `type_`, which is not a list or a dict with 'contains'.
+fn return_scalar_type(type_: &str) -> syn::Type {
+    match type_ {
+        "str" | "string" => syn::parse_quote! { String },
+        "path" => syn::parse_quote! { ::std::path::PathBuf },
+        "int" | "integer" => syn::parse_quote! { i64 },
+        "float" => syn::parse_quote! { f64 },
+        "bool" | "boolean" => syn::parse_quote! { bool },
+        "dict" => syn::parse_quote! { ::serde_json::Map<String, ::serde_json::Value> },
+        "list" => syn::parse_quote! { Vec<::serde_json::Value> },
+        _ => syn::parse_quote! { ::serde_json::Value },
+    }
+}
+
+/// Field name of an option.
+fn field_name(key: &str) -> String {
+    let mut boundary = Boundary::defaults().to_vec();
+    boundary.push(ACRONYM_WITH_TWO_LOWER); // AAbb -> a_abb (allow 'IPv4' to be 'ipv4')
+    escape_rust_reserved_keywords(
+        &key
+            // TODO: configure variable name's replacement rules from optional args
+            .replace('-', "_xx_")
+            .replace('+', "_xxx_")
+            .as_str()
+            .from_case(Case::Camel)
+            .with_boundaries(&boundary)
+            .without_boundaries(&[
+                // Boundary::UPPER_DIGIT, // A1
+                Boundary::LOWER_DIGIT, // a1 (allow 'IPv4' to be 'ipv4')
+                // Boundary::DIGIT_LOWER, // 1a
+                // Boundary::DIGIT_UPPER, // 1A
+                Boundary::ACRONYM, // AAa (I don't expect 'IPv4' to 'i_pv4')
+            ])
+            .to_case(Case::Snake),
+    )
+}
+
+/// Convert an option name or a choice to a `PascalCase` identifier.
+fn pascal_case_name(s: &str) -> String {
+    let name = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .to_case(Case::Pascal);
+    if name.is_empty() {
+        "Empty".to_owned()
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("V{name}")
+    } else {
+        name
+    }
+}
+
+/// Add a numeric suffix to `name` until it is not in `names`, and insert it.
+fn unique_name(name: &str, names: &mut BTreeSet<String>) -> String {
+    let mut unique = name.to_owned();
+    let mut n: u32 = 2;
+    while names.contains(&unique) {
+        unique = format!("{name}{n}");
+        n = n.saturating_add(1);
+    }
+    names.insert(unique.clone());
+    unique
+}
+
 /// Escape rust reserved keywords.
 ///
 /// <https://doc.rust-lang.org/reference/keywords.html>.
@@ -1079,3 +2325,454 @@ async fn format_code(code: &str) -> Result<String> {
         ))
     }
 }
+
+#[cfg(test)]
+#[expect(clippy::panic_in_result_fn, reason = "assertions in tests")]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn generate_argument_spec() -> Result<()> {
+        let module_json: AnsModuleJson = serde_json::from_str(
+            r#"{
+                "ns.coll.sample": {
+                    "doc": {
+                        "options": {
+                            "name": { "type": "str", "required": true },
+                            "state": { "type": "str", "choices": ["present", "absent"], "default": "present" },
+                            "mode": { "type": "int", "choices": [1, 2] },
+                            "free_form": { "type": "str", "required": true },
+                            "args": { "choices": ["a-b", "1", "string"] },
+                            "containers": {
+                                "type": "list",
+                                "elements": "dict",
+                                "suboptions": {
+                                    "name": { "type": "str", "required": true },
+                                    "pull": { "type": "str", "choices": ["always", "never"] }
+                                }
+                            }
+                        },
+                        "mutually_exclusive": [["name", "mode"]],
+                        "required_if": [["state", "absent", ["mode"]]]
+                    }
+                }
+            }"#,
+        )?;
+        let code = generate_module_rs(&module_json).await?;
+        let squashed: String = code.split_whitespace().collect();
+        for expected in [
+            "ARGUMENT_SPEC.validate_args(&self.module)",
+            "pub state: OptU<State>,",
+            "pub mode: OptU<::cdk_ansible_core::core::IntOrString>,",
+            "pub args: OptU<Args2>,",
+            "pub enum State { #[serde(rename = \"present\")] Present, #[serde(rename = \"absent\")] Absent,",
+            "#[serde(untagged)] String(String), }",
+            "#[serde(rename = \"a-b\")] AB, #[serde(rename = \"1\")] V1, #[serde(rename = \"string\")] String2,",
+            "name: \"name\", aliases: &[], type_: \"str\", required: true,",
+            "name: \"free_form\", aliases: &[], type_: \"str\", required: false,",
+            "choices: &[\"present\", \"absent\"], default: Some(\"\\\"present\\\"\"),",
+            "choices: &[\"1\", \"2\"],",
+            "mutually_exclusive: &[&[\"name\", \"mode\"]],",
+            "key: \"state\", value: \"absent\", requirements: &[\"mode\"], any: false,",
+            "name: \"containers\", aliases: &[], type_: \"list\", required: false, choices: &[], default: None,
+                suboptions: Some(&::cdk_ansible_core::core::ArgumentSpec { options: &[
+                    ::cdk_ansible_core::core::OptionSpec { name: \"name\", aliases: &[], type_: \"str\", required: true,
+                        choices: &[], default: None, suboptions: None, },
+                    ::cdk_ansible_core::core::OptionSpec { name: \"pull\", aliases: &[], type_: \"str\", required: false,
+                        choices: &[\"always\", \"never\"], default: None, suboptions: None, },
+                ],",
+        ] {
+            let expected: String = expected.split_whitespace().collect();
+            assert!(squashed.contains(&expected), "{expected}\n---\n{code}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_rustdoc() -> Result<()> {
+        let module_json: AnsModuleJson = serde_json::from_str(
+            r#"{
+                "ns.coll.sample": {
+                    "doc": {
+                        "short_description": "Sample module",
+                        "description": ["Manage a I(sample).", "See L(docs,https://example.com)."],
+                        "notes": "Use O(state=absent) to remove it.",
+                        "version_added": "2.9",
+                        "options": {
+                            "state": {
+                                "description": "State of C(sample).",
+                                "default": "present",
+                                "version_added": "historical"
+                            }
+                        }
+                    },
+                    "examples": "\n- name: Sample\n  ns.coll.sample:\n    state: absent\n"
+                }
+            }"#,
+        )?;
+        let code = generate_module_rs(&module_json).await?;
+        for expected in [
+            concat!(
+                "#[doc = \" Sample module\"]\n#[doc = \"\"]\n",
+                "#[doc = \" Manage a *sample*.\"]\n#[doc = \"\"]\n",
+                "#[doc = \" See [docs](https://example.com).\"]\n#[doc = \"\"]\n",
+                "#[doc = \" Added in version 2.9.\"]\n#[doc = \"\"]\n",
+                "#[doc = \" # Notes\"]\n#[doc = \"\"]\n",
+                "#[doc = \" - Use `state=absent` to remove it.\"]\n#[doc = \"\"]\n",
+                "#[doc = \" # Examples\"]\n#[doc = \"\"]\n",
+                "#[doc = \" ```text\"]\n#[doc = \" - name: Sample\"]\n",
+                "#[doc = \"   ns.coll.sample:\"]\n#[doc = \"     state: absent\"]\n",
+                "#[doc = \" ```\"]\n#[derive(Clone, Debug, Serialize)]\npub struct Module {",
+            ),
+            "#[doc = \" Options of [`Module`] (`ns.coll.sample`)\"]",
+            concat!(
+                "    #[doc = \" State of `sample`.\"]\n    #[doc = \"\"]\n",
+                "    #[doc = \" Default: `\\\"present\\\"`\"]\n    #[serde(",
+            ),
+        ] {
+            assert!(code.contains(expected), "{expected}\n---\n{code}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_suboptions() -> Result<()> {
+        let module_json: AnsModuleJson = serde_json::from_str(
+            r#"{
+                "ns.coll.sample": {
+                    "doc": {
+                        "options": {
+                            "headers": { "type": "dict" },
+                            "items": { "type": "list" },
+                            "tags": { "type": "list", "elements": "str" },
+                            "modes": { "type": "list", "elements": "str", "choices": ["a", "b"] },
+                            "containers": {
+                                "type": "list",
+                                "elements": "dict",
+                                "suboptions": {
+                                    "name": { "type": "str", "required": true },
+                                    "ports": { "type": "list", "elements": "int" },
+                                    "pull_policy": { "choices": ["Always", "IfNotPresent"] },
+                                    "resources": {
+                                        "type": "dict",
+                                        "suboptions": { "limits": { "type": "dict" } }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }"#,
+        )?;
+        let code = generate_module_rs(&module_json).await?;
+        let squashed: String = code.split_whitespace().collect();
+        for expected in [
+            "pub headers: OptU<::cdk_ansible_core::core::StringOrMap>,",
+            "pub items: OptU<::cdk_ansible_core::core::StringOrVec>,",
+            "pub tags: OptU<::cdk_ansible_core::core::StringOr<Vec<String>>>,",
+            "pub modes: OptU<::cdk_ansible_core::core::StringOr<Vec<Modes>>>,",
+            "pub containers: OptU<::cdk_ansible_core::core::StringOr<Vec<Containers>>>,",
+            "pub struct Containers {",
+            "pub ports: OptU<::cdk_ansible_core::core::StringOr<Vec<::cdk_ansible_core::core::IntOrString>>>,",
+            "pub pull_policy: OptU<ContainersPullPolicy>,",
+            "pub resources: OptU<::cdk_ansible_core::core::StringOr<ContainersResources>>,",
+            "pub struct ContainersResources { #[serde(",
+            "impl From<ContainersResources> for ::cdk_ansible_core::core::StringOr<ContainersResources> {",
+        ] {
+            let expected: String = expected.split_whitespace().collect();
+            assert!(squashed.contains(&expected), "{expected}\n---\n{code}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_deprecated() -> Result<()> {
+        let module_json: AnsModuleJson = serde_json::from_str(
+            r#"{
+                "ns.coll.sample": {
+                    "doc": {
+                        "deprecated": {
+                            "why": "Replaced by a new module.",
+                            "alternative": "Use M(ns.coll.other) instead",
+                            "removed_in": "3.0.0"
+                        },
+                        "options": {
+                            "dest": { "type": "path", "aliases": ["path", "name"] },
+                            "old": { "type": "str", "deprecated": true, "removed_in_version": "2.0.0" }
+                        }
+                    }
+                }
+            }"#,
+        )?;
+        let code = generate_module_rs(&module_json).await?;
+        let squashed: String = code.split_whitespace().collect();
+        for expected in [
+            r#"#[deprecated(note = "Replaced by a new module. Alternative: Use `ns.coll.other` instead. Will be removed in version 3.0.0.")]"#,
+            "#[allow(deprecated)] impl TaskModule for Module {",
+            r#"name: "dest", aliases: &["path", "name"],"#,
+            r#"#[doc = " Aliases: `path`, `name`"]"#,
+            r#"#[deprecated(note = "Will be removed in version 2.0.0.")]"#,
+        ] {
+            let expected: String = expected.split_whitespace().collect();
+            assert!(squashed.contains(&expected), "{expected}\n---\n{code}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_return() -> Result<()> {
+        let module_json: AnsModuleJson = serde_json::from_str(
+            r#"{
+                "ns.coll.sample": {
+                    "doc": {},
+                    "return": {
+                        "status": { "description": "HTTP status", "returned": "always", "type": "int", "sample": 200 },
+                        "content": { "returned": "success", "type": "str" },
+                        "stat": {
+                            "type": "complex",
+                            "contains": {
+                                "exists": { "type": "bool" },
+                                "mode": { "type": "str" }
+                            }
+                        },
+                        "items": {
+                            "type": "list",
+                            "elements": "dict",
+                            "contains": { "path": { "type": "path" } }
+                        },
+                        "lines": { "type": "list", "elements": "str" },
+                        "headers": { "type": "dict" }
+                    }
+                }
+            }"#,
+        )?;
+        let code = generate_module_rs(&module_json).await?;
+        let squashed: String = code.split_whitespace().collect();
+        for expected in [
+            "#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize)] pub struct Return {",
+            "#[doc = \" Returned: always\"]",
+            "#[serde(default, rename = \"status\")] pub status: Option<i64>,",
+            "pub content: Option<String>,",
+            "pub stat: Option<ReturnStat>,",
+            "pub items: Option<Vec<ReturnItems>>,",
+            "pub lines: Option<Vec<String>>,",
+            "pub headers: Option<::serde_json::Map<String, ::serde_json::Value>>,",
+            "pub struct ReturnStat { #[serde(default, rename = \"exists\")] pub exists: Option<bool>,",
+            "pub struct ReturnItems { #[serde(default, rename = \"path\")] pub path: Option<::std::path::PathBuf>,",
+        ] {
+            let expected: String = expected.split_whitespace().collect();
+            assert!(squashed.contains(&expected), "{expected}\n---\n{code}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_from_doc_json() -> Result<()> {
+        let doc_dir = tempfile::TempDir::new()?;
+        // `ansible-doc --json <module>` output
+        fs::write(
+            doc_dir.path().join("ns.coll.first.json"),
+            r#"{ "ns.coll.first": { "doc": { "options": { "msg": { "type": "str" } } } } }"#,
+        )
+        .await?;
+        // `ansible-doc --metadata-dump` output
+        fs::write(
+            doc_dir.path().join("dump.json"),
+            r#"{ "all": { "module": { "ns.coll.second": { "doc": {} }, "ns.other.third": { "doc": {} } } } }"#,
+        )
+        .await?;
+        fs::write(doc_dir.path().join("README.md"), "not json").await?;
+
+        let out_dir = tempfile::TempDir::new()?;
+        let cmd = ModuleCmd {
+            command: None,
+            pkg_prefix: "cdkam".to_owned(),
+            pkg_unit: PkgUnit::Namespace,
+            output_dir: out_dir.path().to_path_buf(),
+            no_cache: true,
+            cache_dir: out_dir.path().join("cache"),
+            doc_json: Some(doc_dir.path().to_path_buf()),
+            metadata_dump: false,
+            module_name: None,
+            module_name_regex: Some(r"ns\.coll\..*".to_owned()),
+            module_name_exclude: None,
+            max_procs: 1,
+        };
+        cmd.clone().run().await?;
+        let m_dir = out_dir.path().join("cdkam_ns/src/m/ns");
+        assert!(m_dir.join("coll/first.rs").exists());
+        assert!(m_dir.join("coll/second.rs").exists());
+        assert!(!m_dir.join("other").exists());
+        assert!(!out_dir.path().join("cache").exists());
+
+        // An explicit module must be in the JSON
+        let err = ModuleCmd {
+            module_name: Some("ns.coll.missing".to_owned()),
+            module_name_regex: None,
+            ..cmd.clone()
+        }
+        .run()
+        .await
+        .err()
+        .map(|e| format!("{e:#}"));
+        assert_eq!(
+            err.as_deref(),
+            Some("module 'ns.coll.missing' was not found")
+        );
+
+        // A duplicated module names the file having it again
+        let again = doc_dir.path().join("z.json");
+        fs::write(&again, r#"{ "ns.coll.first": { "doc": {} } }"#).await?;
+        let err = cmd.run().await.err().map(|e| format!("{e:#}"));
+        assert!(
+            err.as_deref().is_some_and(|err| err.contains(&format!(
+                "module 'ns.coll.first' is defined again in {}",
+                again.display()
+            ))),
+            "{err:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_metadata_dump() -> Result<()> {
+        let dump = r#"{
+            "all": {
+                "role": { "ns.coll.role": { "entry_points": {} } },
+                "module": {
+                    "ns.coll.first": { "doc": { "short_description": "first" } },
+                    "ns.coll.second": { "doc": {} }
+                }
+            },
+            "errors": [{ "plugin_type": "module", "error": "missing documentation" }]
+        }"#;
+        let mut modules = Vec::new();
+        for_each_module_doc(dump.as_bytes(), |name, doc| {
+            modules.push((name, doc));
+            Ok(())
+        })?;
+        assert_eq!(
+            modules,
+            [
+                (
+                    "ns.coll.first".to_owned(),
+                    serde_json::json!({ "doc": { "short_description": "first" } })
+                ),
+                (
+                    "ns.coll.second".to_owned(),
+                    serde_json::json!({ "doc": {} })
+                ),
+            ]
+        );
+
+        let res = for_each_module_doc(&br#"{ "ns.coll.first": {} } trailing"#[..], |_, _| Ok(()));
+        assert!(
+            res.as_ref()
+                .is_err_and(|e| e.to_string().contains("trailing characters")),
+            "{res:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn embed_collection_metadata() -> Result<()> {
+        let collections = Collections {
+            ansible_core: "2.17.1".to_owned(),
+            collections: BTreeMap::from([(
+                "community.general".to_owned(),
+                collection::CollectionMeta {
+                    version: "9.5.0".to_owned(),
+                    authors: vec!["Ansible".to_owned()],
+                    license: vec!["GPL-3.0-or-later".to_owned()],
+                    dependencies: Some(BTreeMap::new()),
+                },
+            )]),
+        };
+        let am_name = AnsibleModuleName::new("community.general.ufw")?;
+        let cargo_toml = r#"
+            [package]
+            name = "cdkam_community_general"
+            version = "0.1.0"
+        "#;
+
+        let mut doc: ::toml_edit::DocumentMut = cargo_toml.parse()?;
+        set_ansible_metadata(
+            &mut doc,
+            &am_name,
+            Some(&PkgUnitSetting::Collection),
+            &collections,
+        );
+        let squashed: String = doc.to_string().split_whitespace().collect();
+        let expected: String = r#"
+            [package]
+            name = "cdkam_community_general"
+            version = "9.5.0"
+
+            [package.metadata.ansible]
+            ansible-core = "2.17.1"
+
+            [package.metadata.ansible.collections."community.general"]
+            version = "9.5.0"
+            authors = ["Ansible"]
+            license = ["GPL-3.0-or-later"]
+            dependencies = {}
+        "#
+        .split_whitespace()
+        .collect();
+        assert_eq!(squashed, expected);
+
+        // A namespace package keeps its version
+        let mut doc: ::toml_edit::DocumentMut = cargo_toml.parse()?;
+        set_ansible_metadata(
+            &mut doc,
+            &am_name,
+            Some(&PkgUnitSetting::Namespace),
+            &collections,
+        );
+        assert!(doc.to_string().contains("version = \"0.1.0\""));
+
+        let dir = tempfile::TempDir::new()?;
+        let mod_rs_path = dir.path().join("mod.rs");
+        fs_err::write(&mod_rs_path, "#[cfg(feature = \"x\")]\npub mod ufw;\n")?;
+        let semaphore = Arc::new(Semaphore::new(1));
+        for version in ["9.4.0", "9.5.0"] {
+            set_collection_version(Arc::clone(&semaphore), &mod_rs_path, &am_name, version).await?;
+        }
+        let squashed: String = fs_err::read_to_string(&mod_rs_path)?
+            .split_whitespace()
+            .collect();
+        let expected: String = r#"
+            #[doc = " Version of the `community.general` collection the modules were generated from."]
+            pub const COLLECTION_VERSION: &str = "9.5.0";
+            #[cfg(feature = "x")]
+            pub mod ufw;
+        "#
+        .split_whitespace()
+        .collect();
+        assert_eq!(squashed, expected);
+
+        // A module package re-exports the version of its collection
+        let lib_rs_path = dir.path().join("lib.rs");
+        create_lib_rs(
+            Arc::clone(&semaphore),
+            &lib_rs_path,
+            &am_name,
+            Some(&PkgUnitSetting::Module),
+            true,
+        )
+        .await?;
+        let squashed: String = fs_err::read_to_string(&lib_rs_path)?
+            .split_whitespace()
+            .collect();
+        let expected: String = "
+            mod m;
+            pub use crate::m::community::general::ufw::*;
+            pub use crate::m::community::general::COLLECTION_VERSION;
+        "
+        .split_whitespace()
+        .collect();
+        assert_eq!(squashed, expected);
+        Ok(())
+    }
+}
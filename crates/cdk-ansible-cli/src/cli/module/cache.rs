@@ -0,0 +1,461 @@
+//! Cache of ansible-doc output.
+//!
+//! Each module is cached in `<cache_dir>/<module>.json` as the output of `ansible-doc --json <module>`.
+//! `<cache_dir>/manifest.json` records the versions of ansible-core and the collections
+//! the entries were generated with, and the hash of each entry.
+//! An entry is not used once ansible-core or its collection is upgraded, or if its file was modified.
+
+use super::collection::Collections;
+use anyhow::{Context as _, Result, anyhow, bail};
+use clap::{Args, Subcommand};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// File name of the manifest in the cache directory.
+const MANIFEST_FILE: &str = "manifest.json";
+/// Format of the manifest. A manifest of another format is discarded.
+const MANIFEST_FORMAT: u32 = 1;
+
+#[derive(Args, Debug, Clone)]
+pub struct CacheCmd {
+    #[command(subcommand)]
+    pub command: CacheSubcommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheSubcommand {
+    /// List the cached modules and the versions they were generated with.
+    List,
+    /// Remove the entries which are stale, modified or not in the manifest.
+    Prune,
+    /// Check the entries against their hashes and the installed ansible-core and collections.
+    Verify,
+}
+
+impl CacheCmd {
+    pub async fn run(self, cache_dir: &Path) -> Result<()> {
+        match self.command {
+            CacheSubcommand::List => {
+                for line in list(cache_dir)? {
+                    println!("{line}");
+                }
+            }
+            CacheSubcommand::Prune => {
+                for (name, problem) in prune(cache_dir, &AnsibleVersions::detect().await?)? {
+                    println!("removed '{name}': {problem}");
+                }
+            }
+            CacheSubcommand::Verify => {
+                let problems = problems(
+                    cache_dir,
+                    &read_manifest(cache_dir)?,
+                    Some(&AnsibleVersions::detect().await?),
+                )?;
+                if !problems.is_empty() {
+                    bail!(
+                        "invalid cache entries in {}:\n{}",
+                        cache_dir.display(),
+                        problems
+                            .iter()
+                            .map(|(name, problem)| format!("  {name}: {problem}"))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    );
+                }
+                println!("cache is valid: {}", cache_dir.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Versions of the installed ansible-core and collections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnsibleVersions {
+    pub ansible_core: String,
+    /// Version of each collection, by `<namespace>.<collection>`.
+    pub collections: BTreeMap<String, String>,
+}
+
+impl AnsibleVersions {
+    /// Detect by `ansible-doc --version` and `ansible-galaxy collection list`.
+    pub async fn detect() -> Result<Self> {
+        Ok(Self::from(&Collections::detect().await?))
+    }
+
+    /// Version of the collection of a module (ansible-core for `ansible.builtin`).
+    fn module_version(&self, name: &str) -> Option<String> {
+        let (collection, _) = name.rsplit_once('.')?;
+        if collection == "ansible.builtin" {
+            Some(self.ansible_core.clone())
+        } else {
+            self.collections.get(collection).cloned()
+        }
+    }
+}
+
+impl From<&Collections> for AnsibleVersions {
+    fn from(collections: &Collections) -> Self {
+        Self {
+            ansible_core: collections.ansible_core.clone(),
+            collections: collections
+                .collections
+                .iter()
+                .map(|(name, meta)| (name.clone(), meta.version.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    /// Version of ansible-core the entries were generated with.
+    ansible_core: String,
+    /// Versions of the collections installed when the entries were generated.
+    collections: BTreeMap<String, String>,
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Version of the collection of the module (ansible-core for `ansible.builtin`).
+    version: Option<String>,
+    /// SHA-256 of the cache file.
+    sha256: String,
+}
+
+/// Why a cache entry is not valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Missing,
+    Modified,
+    /// Generated with another version of ansible-core or the collection.
+    Stale {
+        cached: String,
+        installed: String,
+    },
+    /// A file which is not in the manifest.
+    Untracked,
+}
+
+#[expect(clippy::pattern_type_mismatch, reason = "fields are only formatted")]
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "file is missing"),
+            Self::Modified => write!(f, "file does not match its hash"),
+            Self::Stale { cached, installed } => {
+                write!(f, "generated with {cached}, but {installed} is installed")
+            }
+            Self::Untracked => write!(f, "not in the manifest"),
+        }
+    }
+}
+
+/// Cache used while generating modules.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    versions: AnsibleVersions,
+    manifest: Mutex<Manifest>,
+}
+
+impl Cache {
+    /// Open the cache for the installed versions.
+    ///
+    /// If ansible-core was upgraded, all entries are dropped.
+    pub fn open(dir: &Path, versions: AnsibleVersions) -> Result<Self> {
+        let mut manifest = read_manifest(dir)?;
+        if manifest.ansible_core != versions.ansible_core {
+            manifest.entries.clear();
+        }
+        manifest.ansible_core.clone_from(&versions.ansible_core);
+        manifest.collections.clone_from(&versions.collections);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            versions,
+            manifest: Mutex::new(manifest),
+        })
+    }
+
+    fn manifest(&self) -> Result<MutexGuard<'_, Manifest>> {
+        self.manifest
+            .lock()
+            .map_err(|e| anyhow!("failed to lock cache manifest: {e}"))
+    }
+
+    /// ansible-doc JSON of a module, if cached for the installed version and not modified.
+    pub fn get(&self, name: &str) -> Result<Option<serde_json::Value>> {
+        let Some(entry) = self.manifest()?.entries.get(name).cloned() else {
+            return Ok(None);
+        };
+        if entry.version != self.versions.module_version(name) {
+            return Ok(None);
+        }
+        let Ok(content) = fs_err::read(file_path(&self.dir, name)) else {
+            return Ok(None);
+        };
+        if sha256(&content) != entry.sha256 {
+            return Ok(None);
+        }
+        let mut value: serde_json::Value = serde_json::from_slice(&content)
+            .with_context(|| format!("failed to parse cache of '{name}'"))?;
+        Ok(value.get_mut(name).map(serde_json::Value::take))
+    }
+
+    /// Cache the ansible-doc JSON of a module.
+    pub fn put(&self, name: &str, doc: &serde_json::Value) -> Result<()> {
+        fs_err::create_dir_all(&self.dir)?;
+        let content = serde_json::json!({ name: doc }).to_string();
+        fs_err::write(file_path(&self.dir, name), &content)?;
+        self.manifest()?.entries.insert(
+            name.to_owned(),
+            CacheEntry {
+                version: self.versions.module_version(name),
+                sha256: sha256(content.as_bytes()),
+            },
+        );
+        Ok(())
+    }
+
+    /// Write the manifest.
+    pub fn save(&self) -> Result<()> {
+        let manifest = self.manifest()?;
+        write_manifest(&self.dir, &manifest)
+    }
+}
+
+/// Cache file of a module.
+fn file_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.json"))
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Read the manifest, or an empty one if it does not exist or is of another format.
+fn read_manifest(dir: &Path) -> Result<Manifest> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(Manifest::default());
+    }
+    let manifest: Manifest =
+        serde_json::from_str(&fs_err::read_to_string(&path)?).unwrap_or_default();
+    if manifest.format != MANIFEST_FORMAT {
+        return Ok(Manifest::default());
+    }
+    Ok(manifest)
+}
+
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    fs_err::create_dir_all(dir)?;
+    let manifest = Manifest {
+        format: MANIFEST_FORMAT,
+        ..manifest.clone()
+    };
+    // Write to a temporary file first not to leave a broken manifest
+    let tmp_path = dir.join(format!("{MANIFEST_FILE}.tmp"));
+    fs_err::write(&tmp_path, serde_json::to_string_pretty(&manifest)?)?;
+    fs_err::rename(&tmp_path, dir.join(MANIFEST_FILE))?;
+    Ok(())
+}
+
+/// Invalid entries and untracked files, by module.
+///
+/// Versions are checked only if `versions` is given.
+fn problems(
+    dir: &Path,
+    manifest: &Manifest,
+    versions: Option<&AnsibleVersions>,
+) -> Result<BTreeMap<String, Problem>> {
+    let mut problems = BTreeMap::new();
+    for (name, entry) in &manifest.entries {
+        let problem = match fs_err::read(file_path(dir, name)) {
+            Err(_) => Some(Problem::Missing),
+            Ok(content) if sha256(&content) != entry.sha256 => Some(Problem::Modified),
+            Ok(_) => versions.and_then(|versions| stale(manifest, entry, versions, name)),
+        };
+        if let Some(problem) = problem {
+            problems.insert(name.clone(), problem);
+        }
+    }
+    if dir.exists() {
+        for dir_entry in fs_err::read_dir(dir)? {
+            let file_name = dir_entry?.file_name();
+            let Some(name) = file_name
+                .to_str()
+                .filter(|file_name| *file_name != MANIFEST_FILE)
+                .and_then(|file_name| file_name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            if !manifest.entries.contains_key(name) {
+                problems.insert(name.to_owned(), Problem::Untracked);
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// [`Problem::Stale`] if an entry was generated with other versions.
+fn stale(
+    manifest: &Manifest,
+    entry: &CacheEntry,
+    versions: &AnsibleVersions,
+    name: &str,
+) -> Option<Problem> {
+    if manifest.ansible_core != versions.ansible_core {
+        return Some(Problem::Stale {
+            cached: format!("ansible-core {}", manifest.ansible_core),
+            installed: format!("ansible-core {}", versions.ansible_core),
+        });
+    }
+    let installed = versions.module_version(name);
+    (entry.version != installed).then(|| {
+        let describe = |version: Option<&String>| {
+            version.map_or_else(|| "no version".to_owned(), |v| format!("version {v}"))
+        };
+        Problem::Stale {
+            cached: describe(entry.version.as_ref()),
+            installed: describe(installed.as_ref()),
+        }
+    })
+}
+
+/// Lines of `cache list`.
+fn list(dir: &Path) -> Result<Vec<String>> {
+    let manifest = read_manifest(dir)?;
+    let problems = problems(dir, &manifest, None)?;
+    let mut lines = vec![format!("ansible-core {}", manifest.ansible_core)];
+    for (name, entry) in &manifest.entries {
+        lines.push(format!(
+            "{name}\t{}\t{}",
+            entry.version.as_deref().unwrap_or("-"),
+            problems
+                .get(name)
+                .map_or_else(|| "ok".to_owned(), ToString::to_string)
+        ));
+    }
+    for (name, problem) in &problems {
+        if *problem == Problem::Untracked {
+            lines.push(format!("{name}\t-\t{problem}"));
+        }
+    }
+    Ok(lines)
+}
+
+/// Remove invalid entries and untracked files, and return them.
+fn prune(dir: &Path, versions: &AnsibleVersions) -> Result<BTreeMap<String, Problem>> {
+    let mut manifest = read_manifest(dir)?;
+    let problems = problems(dir, &manifest, Some(versions))?;
+    for (name, problem) in &problems {
+        let path = file_path(dir, name);
+        if *problem != Problem::Missing {
+            fs_err::remove_file(&path)?;
+        }
+        manifest.entries.remove(name);
+    }
+    if dir.exists() {
+        write_manifest(dir, &manifest)?;
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+#[expect(clippy::panic_in_result_fn, reason = "assertions in tests")]
+mod tests {
+    use super::*;
+
+    fn versions(core: &str, general: &str) -> AnsibleVersions {
+        AnsibleVersions {
+            ansible_core: core.to_owned(),
+            collections: BTreeMap::from([("community.general".to_owned(), general.to_owned())]),
+        }
+    }
+
+    #[test]
+    fn cache_invalidation() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let doc = serde_json::json!({ "doc": {} });
+        let cache = Cache::open(dir.path(), versions("2.17.0", "9.0.0"))?;
+        cache.put("ansible.builtin.debug", &doc)?;
+        cache.put("community.general.ufw", &doc)?;
+        cache.save()?;
+        // The cache file is named after the FQCN
+        assert!(dir.path().join("community.general.ufw.json").exists());
+
+        let cache = Cache::open(dir.path(), versions("2.17.0", "9.0.0"))?;
+        assert_eq!(cache.get("ansible.builtin.debug")?, Some(doc.clone()));
+        assert_eq!(cache.get("community.general.ufw")?, Some(doc.clone()));
+        assert_eq!(cache.get("community.general.ini_file")?, None);
+
+        // Collection upgraded
+        let cache = Cache::open(dir.path(), versions("2.17.0", "10.0.0"))?;
+        assert_eq!(cache.get("ansible.builtin.debug")?, Some(doc));
+        assert_eq!(cache.get("community.general.ufw")?, None);
+
+        // ansible-core upgraded
+        let cache = Cache::open(dir.path(), versions("2.18.0", "9.0.0"))?;
+        assert_eq!(cache.get("ansible.builtin.debug")?, None);
+
+        // Modified
+        fs_err::write(dir.path().join("ansible.builtin.debug.json"), "{}")?;
+        let cache = Cache::open(dir.path(), versions("2.17.0", "9.0.0"))?;
+        assert_eq!(cache.get("ansible.builtin.debug")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn cache_prune() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let doc = serde_json::json!({ "doc": {} });
+        let cache = Cache::open(dir.path(), versions("2.17.0", "9.0.0"))?;
+        for name in [
+            "ansible.builtin.debug",
+            "ansible.builtin.shell",
+            "community.general.ufw",
+        ] {
+            cache.put(name, &doc)?;
+        }
+        cache.save()?;
+        fs_err::write(dir.path().join("ansible.builtin.debug.json"), "{}")?;
+        fs_err::remove_file(dir.path().join("ansible.builtin.shell.json"))?;
+        fs_err::write(dir.path().join("ansible.builtin.json"), "{}")?;
+
+        assert_eq!(
+            list(dir.path())?,
+            [
+                "ansible-core 2.17.0",
+                "ansible.builtin.debug\t2.17.0\tfile does not match its hash",
+                "ansible.builtin.shell\t2.17.0\tfile is missing",
+                "community.general.ufw\t9.0.0\tok",
+                "ansible.builtin\t-\tnot in the manifest",
+            ]
+        );
+        let removed = prune(dir.path(), &versions("2.17.0", "10.0.0"))?;
+        assert_eq!(
+            removed,
+            BTreeMap::from([
+                ("ansible.builtin.debug".to_owned(), Problem::Modified),
+                ("ansible.builtin.shell".to_owned(), Problem::Missing),
+                (
+                    "community.general.ufw".to_owned(),
+                    Problem::Stale {
+                        cached: "version 9.0.0".to_owned(),
+                        installed: "version 10.0.0".to_owned()
+                    }
+                ),
+                ("ansible.builtin".to_owned(), Problem::Untracked),
+            ])
+        );
+        assert_eq!(list(dir.path())?, ["ansible-core 2.17.0"]);
+        assert!(!dir.path().join("ansible.builtin.json").exists());
+        Ok(())
+    }
+}
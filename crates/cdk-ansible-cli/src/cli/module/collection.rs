@@ -0,0 +1,198 @@
+//! Versions and galaxy metadata of the installed ansible-core and collections.
+//!
+//! Embedded in the generated crates as `[package.metadata.ansible]` in `Cargo.toml`
+//! and as `COLLECTION_VERSION` in the module of each collection.
+
+use anyhow::{Context as _, Result};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Collection of the modules bundled with ansible-core.
+const ANSIBLE_BUILTIN: &str = "ansible.builtin";
+
+/// Installed ansible-core and collections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Collections {
+    pub ansible_core: String,
+    /// Metadata of each collection, by `<namespace>.<collection>`.
+    pub collections: BTreeMap<String, CollectionMeta>,
+}
+
+/// Metadata of a collection, from its `MANIFEST.json` or `galaxy.yml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CollectionMeta {
+    pub version: String,
+    #[serde(default, deserialize_with = "super::deserialize_paragraphs")]
+    pub authors: Vec<String>,
+    /// SPDX license identifiers.
+    #[serde(default, deserialize_with = "super::deserialize_paragraphs")]
+    pub license: Vec<String>,
+    /// Version range of each required collection, by `<namespace>.<collection>`.
+    #[serde(default)]
+    pub dependencies: Option<BTreeMap<String, String>>,
+}
+
+/// `MANIFEST.json` of an installed collection.
+#[derive(Debug, Deserialize)]
+struct CollectionManifest {
+    collection_info: CollectionMeta,
+}
+
+/// A collection in `ansible-galaxy collection list --format json`.
+#[derive(Debug, Deserialize)]
+struct GalaxyCollection {
+    version: String,
+}
+
+impl Collections {
+    /// Detect by `ansible-doc --version` and `ansible-galaxy collection list`.
+    pub async fn detect() -> Result<Self> {
+        let (core, collections) = tokio::join!(
+            Command::new("ansible-doc").arg("--version").output(),
+            Command::new("ansible-galaxy")
+                .args(["collection", "list", "--format", "json"])
+                .output(),
+        );
+        let core = core.with_context(|| "running 'ansible-doc --version'")?;
+        let core = String::from_utf8_lossy(&core.stdout);
+        // e.g. 'ansible-doc [core 2.17.1]'
+        let ansible_core = Regex::new(r"\[core ([^\]]+)\]")?
+            .captures(&core)
+            .and_then(|caps| caps.get(1))
+            .with_context(|| format!("failed to get ansible-core version: {core}"))?
+            .as_str()
+            .to_owned();
+
+        // `{"<collections path>": {"<namespace>.<collection>": {"version": "1.0.0"}}}`,
+        // where a collection in an earlier path takes precedence.
+        // Without any collection, ansible-galaxy fails and the output is empty.
+        let collections =
+            collections.with_context(|| "running 'ansible-galaxy collection list'")?;
+        let paths: IndexMap<String, IndexMap<String, GalaxyCollection>> =
+            if collections.status.success() {
+                serde_json::from_slice(&collections.stdout)
+                    .with_context(|| "failed to parse 'ansible-galaxy collection list' output")?
+            } else {
+                IndexMap::new()
+            };
+        let mut metas = BTreeMap::new();
+        for (path, collections) in paths {
+            for (name, collection) in collections {
+                if metas.contains_key(&name) {
+                    continue;
+                }
+                let meta = match name.split_once('.') {
+                    Some((namespace, collection)) => {
+                        CollectionMeta::read(&Path::new(&path).join(namespace).join(collection))?
+                    }
+                    None => None,
+                };
+                metas.insert(
+                    name,
+                    meta.unwrap_or_else(|| CollectionMeta {
+                        version: collection.version,
+                        ..Default::default()
+                    }),
+                );
+            }
+        }
+        Ok(Self {
+            ansible_core,
+            collections: metas,
+        })
+    }
+
+    /// Metadata of a collection. `ansible.builtin` has the version of ansible-core.
+    pub fn get(&self, collection: &str) -> Option<CollectionMeta> {
+        if collection == ANSIBLE_BUILTIN {
+            return Some(CollectionMeta {
+                version: self.ansible_core.clone(),
+                ..Default::default()
+            });
+        }
+        self.collections.get(collection).cloned()
+    }
+}
+
+impl CollectionMeta {
+    /// Read `MANIFEST.json` (installed from a tarball or galaxy), or `galaxy.yml` (a source checkout).
+    fn read(dir: &Path) -> Result<Option<Self>> {
+        let manifest_path = dir.join("MANIFEST.json");
+        if manifest_path.exists() {
+            let manifest: CollectionManifest =
+                serde_json::from_str(&fs_err::read_to_string(&manifest_path)?)
+                    .with_context(|| format!("failed to parse {}", manifest_path.display()))?;
+            return Ok(Some(manifest.collection_info));
+        }
+        let galaxy_path = dir.join("galaxy.yml");
+        if galaxy_path.exists() {
+            let meta = serde_yaml_ng::from_str(&fs_err::read_to_string(&galaxy_path)?)
+                .with_context(|| format!("failed to parse {}", galaxy_path.display()))?;
+            return Ok(Some(meta));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::panic_in_result_fn, reason = "assertions in tests")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_collection_meta() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let manifest = dir.path().join("community").join("general");
+        fs_err::create_dir_all(&manifest)?;
+        fs_err::write(
+            manifest.join("MANIFEST.json"),
+            r#"{
+                "collection_info": {
+                    "namespace": "community",
+                    "name": "general",
+                    "version": "9.5.0",
+                    "authors": ["Ansible (https://github.com/ansible)"],
+                    "license": ["GPL-3.0-or-later"],
+                    "license_file": null,
+                    "dependencies": {},
+                    "repository": "https://github.com/ansible-collections/community.general"
+                },
+                "format": 1
+            }"#,
+        )?;
+        let galaxy = dir.path().join("my").join("coll");
+        fs_err::create_dir_all(&galaxy)?;
+        fs_err::write(
+            galaxy.join("galaxy.yml"),
+            "namespace: my\nname: coll\nversion: 1.2.0-rc.1\nauthors:\n  - me\nlicense: MIT\ndependencies:\n  community.general: '>=9.0.0'\n",
+        )?;
+
+        assert_eq!(
+            CollectionMeta::read(&manifest)?,
+            Some(CollectionMeta {
+                version: "9.5.0".to_owned(),
+                authors: vec!["Ansible (https://github.com/ansible)".to_owned()],
+                license: vec!["GPL-3.0-or-later".to_owned()],
+                dependencies: Some(BTreeMap::new()),
+            })
+        );
+        assert_eq!(
+            CollectionMeta::read(&galaxy)?,
+            Some(CollectionMeta {
+                version: "1.2.0-rc.1".to_owned(),
+                authors: vec!["me".to_owned()],
+                license: vec!["MIT".to_owned()],
+                dependencies: Some(BTreeMap::from([(
+                    "community.general".to_owned(),
+                    ">=9.0.0".to_owned()
+                )])),
+            })
+        );
+        assert_eq!(CollectionMeta::read(dir.path())?, None);
+        Ok(())
+    }
+}
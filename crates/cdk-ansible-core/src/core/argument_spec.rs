@@ -0,0 +1,444 @@
+//! Metadata of module options, generated from `ansible-doc` by `cdk-ansible module`
+//!
+//! Generated modules check their arguments against it in [`crate::core::TaskModule::validate`],
+//! so that a missing required argument or a bad choice fails at synth, not on the target.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Option of a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionSpec {
+    /// Name in the module arguments
+    pub name: &'static str,
+    /// Other names accepted for the option
+    pub aliases: &'static [&'static str],
+    /// `type` in ansible-doc (`str`, `bool`, `list`, ...)
+    pub type_: &'static str,
+    pub required: bool,
+    /// Allowed values. Non-string values are written as JSON (`1`, `true`).
+    pub choices: &'static [&'static str],
+    /// Default value as JSON
+    pub default: Option<&'static str>,
+    /// Options of a dict, or of each dict of a list
+    pub suboptions: Option<&'static ArgumentSpec>,
+}
+
+/// `required_if`: if `key` is `value`, `requirements` are required (any of them if `any`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequiredIf {
+    pub key: &'static str,
+    /// Written as [`OptionSpec::choices`]
+    pub value: &'static str,
+    pub requirements: &'static [&'static str],
+    pub any: bool,
+}
+
+/// Options of a module and the constraints between them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArgumentSpec {
+    pub options: &'static [OptionSpec],
+    pub mutually_exclusive: &'static [&'static [&'static str]],
+    pub required_together: &'static [&'static [&'static str]],
+    pub required_one_of: &'static [&'static [&'static str]],
+    pub required_if: &'static [RequiredIf],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentError {
+    /// Arguments could not be serialized into a map
+    Serialize(String),
+    MissingRequired(String),
+    InvalidChoice {
+        option: String,
+        value: String,
+        choices: Vec<String>,
+    },
+    MutuallyExclusive(Vec<String>),
+    RequiredTogether(Vec<String>),
+    RequiredOneOf(Vec<String>),
+    RequiredIf {
+        key: String,
+        value: String,
+        missing: Vec<String>,
+        any: bool,
+    },
+    /// Error in the suboptions of `option` (`<name>` or `<name>[<index>]`)
+    Suboption {
+        option: String,
+        error: Box<ArgumentError>,
+    },
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgumentError::Serialize(e) => write!(f, "failed to serialize arguments: {e}"),
+            ArgumentError::MissingRequired(option) => {
+                write!(f, "missing required argument: {option}")
+            }
+            ArgumentError::InvalidChoice {
+                option,
+                value,
+                choices,
+            } => write!(
+                f,
+                "value of {option} must be one of: {}, got: {value}",
+                choices.join(", ")
+            ),
+            ArgumentError::MutuallyExclusive(options) => {
+                write!(
+                    f,
+                    "parameters are mutually exclusive: {}",
+                    options.join("|")
+                )
+            }
+            ArgumentError::RequiredTogether(options) => {
+                write!(
+                    f,
+                    "parameters are required together: {}",
+                    options.join(", ")
+                )
+            }
+            ArgumentError::RequiredOneOf(options) => {
+                write!(
+                    f,
+                    "one of the following is required: {}",
+                    options.join(", ")
+                )
+            }
+            ArgumentError::RequiredIf {
+                key,
+                value,
+                missing,
+                any,
+            } => write!(
+                f,
+                "{key} is {value} but {} of the following are missing: {}",
+                if *any { "any" } else { "all" },
+                missing.join(", ")
+            ),
+            ArgumentError::Suboption { option, error } => write!(f, "{option}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError {}
+
+impl ArgumentSpec {
+    /// Validate the serialized arguments of a module
+    pub fn validate_args<T: Serialize + ?Sized>(&self, args: &T) -> Result<(), ArgumentError> {
+        let value =
+            serde_json::to_value(args).map_err(|e| ArgumentError::Serialize(e.to_string()))?;
+        self.validate(&value)
+    }
+
+    /// Validate the arguments of a module, given as a JSON object
+    pub fn validate(&self, args: &Value) -> Result<(), ArgumentError> {
+        let Value::Object(args) = args else {
+            return Err(ArgumentError::Serialize(format!(
+                "arguments must be a map: {args}"
+            )));
+        };
+        // Value of an option, given by its name or one of its aliases
+        let get = |name: &str| {
+            let aliases = self
+                .options
+                .iter()
+                .find(|option| option.name == name)
+                .map_or(&[][..], |option| option.aliases);
+            std::iter::once(name)
+                .chain(aliases.iter().copied())
+                .find_map(|name| args.get(name).filter(|v| !v.is_null()))
+        };
+        let is_set = |name: &str| get(name).is_some();
+        let names = |names: &[&str]| names.iter().map(|n| (*n).to_owned()).collect();
+
+        for option in self.options.iter() {
+            let Some(value) = get(option.name) else {
+                if option.required {
+                    return Err(ArgumentError::MissingRequired(option.name.to_owned()));
+                }
+                continue;
+            };
+            if let Some(suboptions) = option.suboptions {
+                suboptions.validate_suboptions(option.name, value)?;
+            }
+            if option.choices.is_empty() {
+                continue;
+            }
+            let values = match value {
+                Value::Array(items) => items.iter().collect(),
+                _ => vec![value],
+            };
+            for value in values {
+                // Templates and tagged values are resolved by Ansible
+                let Some(scalar) = scalar(value) else {
+                    continue;
+                };
+                if is_template(&scalar) {
+                    continue;
+                }
+                if !option.choices.contains(&scalar.as_str()) {
+                    return Err(ArgumentError::InvalidChoice {
+                        option: option.name.to_owned(),
+                        value: scalar,
+                        choices: names(option.choices),
+                    });
+                }
+            }
+        }
+        for group in self.mutually_exclusive.iter() {
+            if group.iter().filter(|name| is_set(name)).count() > 1 {
+                return Err(ArgumentError::MutuallyExclusive(names(group)));
+            }
+        }
+        for group in self.required_together.iter() {
+            let count = group.iter().filter(|name| is_set(name)).count();
+            if count > 0 && count < group.len() {
+                return Err(ArgumentError::RequiredTogether(names(group)));
+            }
+        }
+        for group in self.required_one_of.iter() {
+            if !group.iter().any(|name| is_set(name)) {
+                return Err(ArgumentError::RequiredOneOf(names(group)));
+            }
+        }
+        for rule in self.required_if.iter() {
+            if get(rule.key).and_then(scalar).as_deref() != Some(rule.value) {
+                continue;
+            }
+            let missing: Vec<String> = rule
+                .requirements
+                .iter()
+                .filter(|name| !is_set(name))
+                .map(|name| (*name).to_owned())
+                .collect();
+            let failed = if rule.any {
+                missing.len() == rule.requirements.len()
+            } else {
+                !missing.is_empty()
+            };
+            if failed {
+                return Err(ArgumentError::RequiredIf {
+                    key: rule.key.to_owned(),
+                    value: rule.value.to_owned(),
+                    missing,
+                    any: rule.any,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the dict, or each dict of the list, given as the option `name`
+    ///
+    /// Other values (templates) are resolved by Ansible.
+    fn validate_suboptions(&self, name: &str, value: &Value) -> Result<(), ArgumentError> {
+        let wrap = |option: String| {
+            move |error| ArgumentError::Suboption {
+                option,
+                error: Box::new(error),
+            }
+        };
+        match value {
+            Value::Object(_) => self.validate(value).map_err(wrap(name.to_owned())),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.is_object())
+                .try_for_each(|(i, item)| {
+                    self.validate(item).map_err(wrap(format!("{name}[{i}]")))
+                }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A scalar written as [`OptionSpec::choices`]
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn is_template(s: &str) -> bool {
+    s.contains("{{") || s.contains("{%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SPEC: ArgumentSpec = ArgumentSpec {
+        options: &[
+            OptionSpec {
+                name: "name",
+                aliases: &["dest"],
+                type_: "str",
+                required: true,
+                choices: &[],
+                default: None,
+                suboptions: None,
+            },
+            OptionSpec {
+                name: "state",
+                aliases: &[],
+                type_: "str",
+                required: false,
+                choices: &["present", "absent"],
+                default: Some("\"present\""),
+                suboptions: None,
+            },
+            OptionSpec {
+                name: "mode",
+                aliases: &[],
+                type_: "int",
+                required: false,
+                choices: &["1", "2"],
+                default: None,
+                suboptions: None,
+            },
+        ],
+        mutually_exclusive: &[&["src", "content"]],
+        required_together: &[&["owner", "group"]],
+        required_one_of: &[],
+        required_if: &[RequiredIf {
+            key: "state",
+            value: "absent",
+            requirements: &["force", "purge"],
+            any: true,
+        }],
+    };
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(SPEC.validate(&json!({ "name": "x", "mode": 2 })), Ok(()));
+        assert_eq!(SPEC.validate(&json!({ "dest": "x" })), Ok(()));
+        assert_eq!(
+            SPEC.validate(&json!({ "name": "x", "state": "{{ state }}" })),
+            Ok(())
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "state": "present" })),
+            Err(ArgumentError::MissingRequired("name".into()))
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "name": "x", "state": "presnet" }))
+                .unwrap_err()
+                .to_string(),
+            "value of state must be one of: present, absent, got: presnet"
+        );
+        assert!(SPEC.validate(&json!({ "name": "x", "mode": 3 })).is_err());
+        assert_eq!(
+            SPEC.validate(&json!({ "name": "x", "src": "a", "content": "b" })),
+            Err(ArgumentError::MutuallyExclusive(vec![
+                "src".into(),
+                "content".into()
+            ]))
+        );
+        assert!(
+            SPEC.validate(&json!({ "name": "x", "owner": "a" }))
+                .is_err()
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "name": "x", "state": "absent" }))
+                .unwrap_err()
+                .to_string(),
+            "state is absent but any of the following are missing: force, purge"
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "name": "x", "state": "absent", "purge": true })),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_suboptions() {
+        const CONTAINER: ArgumentSpec = ArgumentSpec {
+            options: &[
+                OptionSpec {
+                    name: "name",
+                    aliases: &[],
+                    type_: "str",
+                    required: true,
+                    choices: &[],
+                    default: None,
+                    suboptions: None,
+                },
+                OptionSpec {
+                    name: "pull",
+                    aliases: &[],
+                    type_: "str",
+                    required: false,
+                    choices: &["always", "never"],
+                    default: None,
+                    suboptions: None,
+                },
+            ],
+            mutually_exclusive: &[],
+            required_together: &[],
+            required_one_of: &[],
+            required_if: &[],
+        };
+        const SPEC: ArgumentSpec = ArgumentSpec {
+            options: &[
+                OptionSpec {
+                    name: "containers",
+                    aliases: &[],
+                    type_: "list",
+                    required: false,
+                    choices: &[],
+                    default: None,
+                    suboptions: Some(&CONTAINER),
+                },
+                OptionSpec {
+                    name: "sidecar",
+                    aliases: &[],
+                    type_: "dict",
+                    required: false,
+                    choices: &[],
+                    default: None,
+                    suboptions: Some(&CONTAINER),
+                },
+            ],
+            mutually_exclusive: &[],
+            required_together: &[],
+            required_one_of: &[],
+            required_if: &[],
+        };
+
+        assert_eq!(
+            SPEC.validate(&json!({
+                "containers": [{ "name": "a", "pull": "always" }, "{{ container }}"],
+                "sidecar": { "name": "b" },
+            })),
+            Ok(())
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "containers": "{{ containers }}" })),
+            Ok(())
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "containers": [{ "name": "a" }, { "pull": "never" }] }))
+                .unwrap_err()
+                .to_string(),
+            "containers[1]: missing required argument: name"
+        );
+        assert_eq!(
+            SPEC.validate(&json!({ "sidecar": { "name": "b", "pull": "sometimes" } })),
+            Err(ArgumentError::Suboption {
+                option: "sidecar".into(),
+                error: Box::new(ArgumentError::InvalidChoice {
+                    option: "pull".into(),
+                    value: "sometimes".into(),
+                    choices: vec!["always".into(), "never".into()],
+                }),
+            })
+        );
+    }
+}
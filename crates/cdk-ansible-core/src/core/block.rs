@@ -0,0 +1,259 @@
+use crate::core::{BoolOrString, BoolOrStringOrVecString, Handler, IntOrString, OptU, Task};
+use cdk_ansible_macro::OptionsSetters;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// An item of a task list (`tasks`, `pre_tasks`, `post_tasks`, `block`, `rescue` and `always`)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum TaskOrBlock {
+    // `Block` is tried first in deserializing, otherwise `block` is taken as a module name.
+    Block(Block),
+    Task(Task),
+}
+
+impl From<Task> for TaskOrBlock {
+    fn from(value: Task) -> Self {
+        Self::Task(value)
+    }
+}
+
+impl From<Block> for TaskOrBlock {
+    fn from(value: Block) -> Self {
+        Self::Block(value)
+    }
+}
+
+/// [Blocks](https://docs.ansible.com/ansible/latest/playbook_guide/playbooks_blocks.html)
+///
+/// Blocks create logical groups of tasks and handle task errors with `rescue` and `always`.
+/// Blocks can be nested.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{Block, BlockOptions, OptU, Task, TaskModule, TaskOptions};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize, Clone, Debug)]
+/// struct SampleTaskModule {
+///     x1: String,
+/// }
+/// impl TaskModule for SampleTaskModule {}
+///
+/// let task = |name: &str| Task {
+///     name: name.to_string(),
+///     options: TaskOptions::default(),
+///     command: Box::new(SampleTaskModule { x1: "x1".to_string() }),
+/// };
+///
+/// let _block = Block {
+///     name: "handle errors".to_string(),
+///     options: BlockOptions {
+///         become_: OptU::Some(true.into()),
+///         ..Default::default()
+///     },
+///     block: vec![task("main").into()],
+///     rescue: OptU::Some(vec![task("on error").into()]),
+///     always: OptU::Some(vec![task("finally").into()]),
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    /// Identifier. Can be used for documentation, or in tasks/handlers.
+    /// An empty name is not serialized.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(flatten)]
+    pub options: BlockOptions,
+    /// List of tasks in a block.
+    pub block: Vec<TaskOrBlock>,
+    /// List of tasks in a block that run if there is a task error in the main block list.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub rescue: OptU<Vec<TaskOrBlock>>,
+    /// List of tasks, in a block, that execute no matter if there is an error in the block or not.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub always: OptU<Vec<TaskOrBlock>>,
+}
+
+/// [playbook keywords (block)](https://docs.ansible.com/ansible/latest/reference_appendices/playbooks_keywords.html#block)
+///
+/// The subset of [`crate::core::TaskOptions`] which is applied to a block.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, OptionsSetters, PartialEq)]
+pub struct BlockOptions {
+    /// Force any un-handled task errors on any host to propagate to all hosts and end the play.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub any_errors_fatal: OptU<BoolOrString>,
+    /// Boolean that controls if privilege escalation is used or not on Task execution.
+    /// Implemented by the become plugin. See Become plugins.
+    #[serde(
+        rename = "become",
+        default = "OptU::default",
+        skip_serializing_if = "OptU::is_unset"
+    )]
+    pub become_: OptU<BoolOrString>,
+    /// Path to the executable used to elevate privileges. Implemented by the become plugin. See Become plugins.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub become_exe: OptU<String>,
+    /// A string of flag(s) to pass to the privilege escalation program when become is True.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub become_flags: OptU<String>,
+    /// Which method of privilege escalation to use (such as sudo or su).
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub become_method: OptU<String>,
+    /// User that you 'become' after using privilege escalation. The remote/login user must have permissions to become this user.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub become_user: OptU<String>,
+    /// A boolean that controls if a task is executed in 'check' mode. See Validating tasks: check mode and diff mode.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub check_mode: OptU<BoolOrString>,
+    /// List of collection namespaces to search for modules, plugins, and roles. See Using collections in a playbook
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub collections: OptU<Vec<String>>,
+    /// Allows you to change the connection plugin used for tasks to execute on the target. See Using connection plugins.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub connection: OptU<String>,
+    /// Enable debugging tasks based on the state of the task result. See Debugging tasks.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub debugger: OptU<BoolOrString>,
+    /// Boolean that allows you to apply facts to a delegated host instead of inventory_hostname.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub delegate_facts: OptU<BoolOrString>,
+    /// Host to execute task instead of the target (inventory_hostname).
+    /// Connection vars from the delegated host will also be used for the task.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub delegate_to: OptU<String>,
+    /// Toggle to make tasks return 'diff' information or not.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub diff: OptU<BoolOrString>,
+    /// A dictionary that gets converted into environment vars to be provided for the task upon execution.
+    /// This can ONLY be used with modules. This is not supported for any other type of plugins nor Ansible itself nor its configuration,
+    /// it just sets the variables for the code responsible for executing the task.
+    /// This is not a recommended way to pass in confidential data.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub environment: OptU<IndexMap<String, String>>,
+    /// Boolean that allows you to ignore task failures and continue with play. It does not affect connection errors.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub ignore_errors: OptU<BoolOrString>,
+    /// Boolean that allows you to ignore task failures due to an unreachable host and continue with the play.
+    /// This does not affect other task errors (see ignore_errors) but is useful for groups of volatile/ephemeral hosts.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub ignore_unreachable: OptU<BoolOrString>,
+    /// Specifies default parameter values for modules.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub module_defaults: OptU<IndexMap<String, serde_json::Value>>,
+    /// Boolean that controls information disclosure.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub no_log: OptU<BoolOrString>,
+    /// List of handlers to notify when the task returns a 'changed=True' status.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub notify: OptU<Vec<Handler>>,
+    /// Used to override the default port used in a connection.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub port: OptU<IntOrString>,
+    /// User used to log into the target via the connection plugin.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub remote_user: OptU<String>,
+    /// Boolean that will bypass the host loop, forcing the task to attempt to execute on the first host available
+    /// and afterward apply any results and facts to all active hosts in the same batch.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub run_once: OptU<BoolOrString>,
+    /// Tags applied to the task or included tasks, this allows selecting subsets of tasks from the command line.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub tags: OptU<Vec<String>>,
+    /// Limit the number of concurrent task runs on task, block and playbook level.
+    /// This is independent of the forks and serial settings, but cannot be set higher than those limits.
+    /// For example, if forks is set to 10 and the throttle is set to 15, at most 10 hosts will be operated on in parallel.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub throttle: OptU<IntOrString>,
+    /// Time limit for the task action to execute in, if exceeded, Ansible will interrupt the process.
+    /// Timeout does not include templating or looping.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub timeout: OptU<IntOrString>,
+    /// Dictionary/map of variables
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub vars: OptU<IndexMap<String, serde_json::Value>>,
+    /// Conditional expression, determines if an iteration of a task is run or not.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub when: OptU<BoolOrStringOrVecString>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{TaskModule, TaskOptions};
+
+    #[derive(Serialize, Clone, Debug, PartialEq)]
+    struct SampleTaskModule {
+        x1: String,
+    }
+
+    impl TaskModule for SampleTaskModule {}
+
+    fn task_helper(name: &str) -> TaskOrBlock {
+        Task {
+            name: name.to_string(),
+            options: TaskOptions::default(),
+            command: Box::new(SampleTaskModule {
+                x1: "x1".to_string(),
+            }),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_block_minimum() {
+        assert_eq!(
+            serde_json::to_string(&TaskOrBlock::from(Block {
+                name: "block1".to_string(),
+                options: BlockOptions::default(),
+                block: vec![task_helper("task1")],
+                rescue: OptU::Unset,
+                always: OptU::Unset,
+            }))
+            .expect("failed to serialize"),
+            r#"{"name":"block1","block":[{"name":"task1","x1":"x1"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_block_nested_with_rescue_and_always() {
+        assert_eq!(
+            serde_json::to_string(&Block {
+                name: "block1".to_string(),
+                options: BlockOptions {
+                    become_: OptU::Some(true.into()),
+                    tags: OptU::Some(vec!["tag1".to_string()]),
+                    when: OptU::Some("x is defined".into()),
+                    ..Default::default()
+                },
+                block: vec![
+                    task_helper("task1"),
+                    Block {
+                        name: "block2".to_string(),
+                        options: BlockOptions::default(),
+                        block: vec![task_helper("task2")],
+                        rescue: OptU::Unset,
+                        always: OptU::Unset,
+                    }
+                    .into(),
+                ],
+                rescue: OptU::Some(vec![task_helper("rescue1")]),
+                always: OptU::Some(vec![task_helper("always1")]),
+            })
+            .expect("failed to serialize"),
+            String::new()
+                + "{"
+                + r#""name":"block1","#
+                + r#""become":true,"#
+                + r#""tags":["tag1"],"#
+                + r#""when":"x is defined","#
+                + r#""block":["#
+                + r#"{"name":"task1","x1":"x1"},"#
+                + r#"{"name":"block2","block":[{"name":"task2","x1":"x1"}]}"#
+                + r#"],"#
+                + r#""rescue":[{"name":"rescue1","x1":"x1"}],"#
+                + r#""always":[{"name":"always1","x1":"x1"}]"#
+                + "}"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Constructors taking the required fields, and the setters of the options
+//!
+//! The setters of [`PlayOptions`], [`TaskOptions`] and [`BlockOptions`] are defined in
+//! [`WithPlayOptions`], [`WithTaskOptions`] and [`WithBlockOptions`],
+//! which are also implemented for [`Play`], [`Task`] and [`Block`].
+//!
+//! ```rust
+//! use cdk_ansible_core::core::{
+//!     Expr, Play, RawModule, Register, Task, WithPlayOptions as _, WithTaskOptions as _,
+//! };
+//!
+//! let result = Register::new("result");
+//! let play = Play::new("sample", "localhost")
+//!     .become_(true)
+//!     .tags(["sample"])
+//!     .task(
+//!         Task::new("uptime", RawModule::new("ansible.builtin.command", "uptime".into()))
+//!             .register(result.clone())
+//!             .changed_when(false),
+//!     )
+//!     .task(
+//!         Task::new("show", RawModule::new("ansible.builtin.debug", "msg={{ result.stdout }}".into()))
+//!             .when(result.rc().equals(0)),
+//!     );
+//! assert_eq!(play.tasks.len(), 2);
+//! ```
+
+use crate::core::{
+    Block, BlockOptions, OptU, Play, PlayOptions, StringOrVecString, Task, TaskModule, TaskOptions,
+    TaskOrBlock, WithBlockOptions, WithPlayOptions, WithTaskOptions,
+};
+
+impl Play {
+    pub fn new(name: impl Into<String>, hosts: impl Into<StringOrVecString>) -> Self {
+        Self {
+            name: name.into(),
+            hosts: hosts.into(),
+            options: PlayOptions::default(),
+            tasks: vec![],
+        }
+    }
+
+    /// Append a task (or a block)
+    pub fn task(mut self, task: impl Into<TaskOrBlock>) -> Self {
+        self.tasks.push(task.into());
+        self
+    }
+
+    /// Append tasks (or blocks)
+    pub fn tasks<I>(mut self, tasks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TaskOrBlock>,
+    {
+        self.tasks.extend(tasks.into_iter().map(Into::into));
+        self
+    }
+}
+
+impl WithPlayOptions for Play {
+    fn play_options_mut(&mut self) -> &mut PlayOptions {
+        &mut self.options
+    }
+}
+
+impl Task {
+    pub fn new(name: impl Into<String>, module: impl TaskModule + 'static) -> Self {
+        Self {
+            name: name.into(),
+            options: TaskOptions::default(),
+            command: Box::new(module),
+        }
+    }
+}
+
+impl WithTaskOptions for Task {
+    fn task_options_mut(&mut self) -> &mut TaskOptions {
+        &mut self.options
+    }
+}
+
+impl Block {
+    pub fn new<I>(name: impl Into<String>, block: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TaskOrBlock>,
+    {
+        Self {
+            name: name.into(),
+            options: BlockOptions::default(),
+            block: block.into_iter().map(Into::into).collect(),
+            rescue: OptU::Unset,
+            always: OptU::Unset,
+        }
+    }
+
+    /// Set the tasks run on an error in `block`
+    pub fn rescue<I>(mut self, rescue: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TaskOrBlock>,
+    {
+        self.rescue = OptU::Some(rescue.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the tasks always run after `block`
+    pub fn always<I>(mut self, always: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TaskOrBlock>,
+    {
+        self.always = OptU::Some(always.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+impl WithBlockOptions for Block {
+    fn block_options_mut(&mut self) -> &mut BlockOptions {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Handler, RawModule, Register};
+    use indexmap::IndexMap;
+
+    fn module() -> RawModule {
+        RawModule::new("ansible.builtin.ping", serde_json::json!({}))
+    }
+
+    #[test]
+    fn test_task_builder() {
+        let result = Register::new("result");
+        let task = Task::new("task1", module())
+            .become_(true)
+            .become_user("root")
+            .when(result.changed())
+            .register(result.clone())
+            .notify(["handler1"])
+            .tags(["a", "b"])
+            .vars(IndexMap::from([("x".to_string(), 1.into())]))
+            .loop_(vec![serde_json::json!("x")])
+            .retries(3);
+        assert_eq!(
+            serde_json::to_string(&task).expect("failed to serialize"),
+            String::new()
+                + "{"
+                + r#""name":"task1","#
+                + r#""become":true,"#
+                + r#""become_user":"root","#
+                + r#""loop":["x"],"#
+                + r#""notify":["handler1"],"#
+                + r#""register":"result","#
+                + r#""retries":3,"#
+                + r#""tags":["a","b"],"#
+                + r#""vars":{"x":1},"#
+                + r#""when":"result.changed","#
+                + r#""ansible.builtin.ping":{}"#
+                + "}"
+        );
+    }
+
+    #[test]
+    fn test_play_builder() {
+        let handler = Task::new("handler1", module());
+        let play = Play::new("play1", vec!["host1".to_string()])
+            .gather_facts(false)
+            .handlers([handler.clone()])
+            .roles(["role1"])
+            .task(Task::new("task1", module()).notify([Handler::of(&handler)]))
+            .task(
+                Block::new("block1", [Task::new("task2", module())])
+                    .rescue([Task::new("task3", module())])
+                    .ignore_errors(true),
+            );
+        assert_eq!(
+            serde_json::to_string(&play).expect("failed to serialize"),
+            String::new()
+                + "{"
+                + r#""name":"play1","#
+                + r#""hosts":["host1"],"#
+                + r#""gather_facts":false,"#
+                + r#""handlers":[{"name":"handler1","ansible.builtin.ping":{}}],"#
+                + r#""roles":["role1"],"#
+                + r#""tasks":["#
+                + r#"{"name":"task1","notify":["handler1"],"ansible.builtin.ping":{}},"#
+                + r#"{"name":"block1","ignore_errors":true,"#
+                + r#""block":[{"name":"task2","ansible.builtin.ping":{}}],"#
+                + r#""rescue":[{"name":"task3","ansible.builtin.ping":{}}]}"#
+                + "]"
+                + "}"
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Control-flow actions: `import_*`, `include_*` and `meta`
+//!
+//! `import_*` are processed when the playbook is parsed, so they don't accept loops nor `apply`.
+//! Synth rejects such combinations.
+
+use crate::core::{BoolOrStringOrVecString, OptU, Play, Task, TaskModule, TaskOptions};
+use indexmap::IndexMap;
+use serde::ser::SerializeMap as _;
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Serialize `<module>: <args>`
+fn serialize_module<S: Serializer, T: Serialize + ?Sized>(
+    serializer: S,
+    module: &str,
+    args: &T,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(module, args)?;
+    map.end()
+}
+
+/// [ansible.builtin.import_tasks](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/import_tasks_module.html)
+///
+/// Statically imports a task file. Loops are not allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportTasks {
+    pub file: String,
+}
+
+impl ImportTasks {
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_owned(),
+        }
+    }
+}
+
+impl Serialize for ImportTasks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Args<'a> {
+            file: &'a str,
+        }
+        serialize_module(
+            serializer,
+            "ansible.builtin.import_tasks",
+            &Args { file: &self.file },
+        )
+    }
+}
+
+impl TaskModule for ImportTasks {}
+
+/// [ansible.builtin.include_tasks](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/include_tasks_module.html)
+///
+/// Dynamically includes a task file.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{IncludeTasks, OptU, Task, TaskOptions};
+///
+/// let _task = Task::new(
+///     "install",
+///     IncludeTasks::new("install.yaml").apply(TaskOptions {
+///         become_: OptU::Some(true.into()),
+///         ..Default::default()
+///     }),
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncludeTasks {
+    pub file: String,
+    /// Keywords applied to the included tasks
+    pub apply: OptU<TaskOptions>,
+}
+
+impl IncludeTasks {
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_owned(),
+            apply: OptU::Unset,
+        }
+    }
+
+    /// Keywords applied to the included tasks
+    pub fn apply(mut self, apply: TaskOptions) -> Self {
+        self.apply = OptU::Some(apply);
+        self
+    }
+}
+
+impl Serialize for IncludeTasks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Args<'a> {
+            file: &'a str,
+            #[serde(skip_serializing_if = "OptU::is_unset")]
+            apply: &'a OptU<TaskOptions>,
+        }
+        serialize_module(
+            serializer,
+            "ansible.builtin.include_tasks",
+            &Args {
+                file: &self.file,
+                apply: &self.apply,
+            },
+        )
+    }
+}
+
+impl TaskModule for IncludeTasks {}
+
+/// Options shared by [`ImportRole`] and [`IncludeRole`]
+#[derive(Serialize, Default, Clone, Debug, PartialEq)]
+pub struct RoleOptions {
+    /// File to load from a role's `tasks/` directory
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub tasks_from: OptU<String>,
+    /// File to load from a role's `vars/` directory
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub vars_from: OptU<String>,
+    /// File to load from a role's `defaults/` directory
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub defaults_from: OptU<String>,
+    /// File to load from a role's `handlers/` directory
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub handlers_from: OptU<String>,
+    /// Overrides the role's metadata setting to allow using a role more than once with the same parameters
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub allow_duplicates: OptU<bool>,
+    /// Whether the role's variables and defaults are exposed to the play
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub public: OptU<bool>,
+    /// Perform role argument spec validation if an argument spec is defined
+    #[serde(skip_serializing_if = "OptU::is_unset")]
+    pub rolespec_validate: OptU<bool>,
+}
+
+/// [ansible.builtin.import_role](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/import_role_module.html)
+///
+/// Statically imports a role. Loops are not allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportRole {
+    pub name: String,
+    pub options: RoleOptions,
+}
+
+/// [ansible.builtin.include_role](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/include_role_module.html)
+///
+/// Dynamically includes a role.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{IncludeRole, Task};
+///
+/// let _task = Task::new(
+///     "configure nginx",
+///     IncludeRole::new("nginx").tasks_from("configure").public(true),
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncludeRole {
+    pub name: String,
+    pub options: RoleOptions,
+    /// Keywords applied to the tasks of the role
+    pub apply: OptU<TaskOptions>,
+}
+
+macro_rules! role_option_setters {
+    ($ty:ty) => {
+        impl $ty {
+            pub fn tasks_from(mut self, file: &str) -> Self {
+                self.options.tasks_from = OptU::Some(file.to_owned());
+                self
+            }
+
+            pub fn vars_from(mut self, file: &str) -> Self {
+                self.options.vars_from = OptU::Some(file.to_owned());
+                self
+            }
+
+            pub fn defaults_from(mut self, file: &str) -> Self {
+                self.options.defaults_from = OptU::Some(file.to_owned());
+                self
+            }
+
+            pub fn handlers_from(mut self, file: &str) -> Self {
+                self.options.handlers_from = OptU::Some(file.to_owned());
+                self
+            }
+
+            pub fn allow_duplicates(mut self, value: bool) -> Self {
+                self.options.allow_duplicates = OptU::Some(value);
+                self
+            }
+
+            pub fn public(mut self, value: bool) -> Self {
+                self.options.public = OptU::Some(value);
+                self
+            }
+
+            pub fn rolespec_validate(mut self, value: bool) -> Self {
+                self.options.rolespec_validate = OptU::Some(value);
+                self
+            }
+        }
+    };
+}
+
+role_option_setters!(ImportRole);
+role_option_setters!(IncludeRole);
+
+impl ImportRole {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            options: RoleOptions::default(),
+        }
+    }
+}
+
+impl IncludeRole {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            options: RoleOptions::default(),
+            apply: OptU::Unset,
+        }
+    }
+
+    /// Keywords applied to the tasks of the role
+    pub fn apply(mut self, apply: TaskOptions) -> Self {
+        self.apply = OptU::Some(apply);
+        self
+    }
+}
+
+impl Serialize for ImportRole {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Args<'a> {
+            name: &'a str,
+            #[serde(flatten)]
+            options: &'a RoleOptions,
+        }
+        serialize_module(
+            serializer,
+            "ansible.builtin.import_role",
+            &Args {
+                name: &self.name,
+                options: &self.options,
+            },
+        )
+    }
+}
+
+impl Serialize for IncludeRole {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Args<'a> {
+            name: &'a str,
+            #[serde(flatten)]
+            options: &'a RoleOptions,
+            #[serde(skip_serializing_if = "OptU::is_unset")]
+            apply: &'a OptU<TaskOptions>,
+        }
+        serialize_module(
+            serializer,
+            "ansible.builtin.include_role",
+            &Args {
+                name: &self.name,
+                options: &self.options,
+                apply: &self.apply,
+            },
+        )
+    }
+}
+
+impl TaskModule for ImportRole {}
+impl TaskModule for IncludeRole {}
+
+/// [ansible.builtin.meta](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/meta_module.html)
+///
+/// ```rust
+/// use cdk_ansible_core::core::Meta;
+///
+/// let task = Meta::FlushHandlers.task();
+/// assert_eq!(
+///     serde_json::to_string(&task).unwrap(),
+///     r#"{"name":"meta: flush_handlers","ansible.builtin.meta":"flush_handlers"}"#
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Meta {
+    /// Run the notified handlers now
+    FlushHandlers,
+    RefreshInventory,
+    Noop,
+    ClearFacts,
+    ClearHostErrors,
+    EndPlay,
+    EndHost,
+    EndBatch,
+    EndRole,
+    ResetConnection,
+}
+
+impl Meta {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FlushHandlers => "flush_handlers",
+            Self::RefreshInventory => "refresh_inventory",
+            Self::Noop => "noop",
+            Self::ClearFacts => "clear_facts",
+            Self::ClearHostErrors => "clear_host_errors",
+            Self::EndPlay => "end_play",
+            Self::EndHost => "end_host",
+            Self::EndBatch => "end_batch",
+            Self::EndRole => "end_role",
+            Self::ResetConnection => "reset_connection",
+        }
+    }
+
+    /// A task of the action, named `meta: <action>`
+    pub fn task(self) -> Task {
+        Task::new(format!("meta: {}", self.as_str()), self)
+    }
+}
+
+impl Serialize for Meta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_module(serializer, "ansible.builtin.meta", self.as_str())
+    }
+}
+
+impl TaskModule for Meta {}
+
+/// [ansible.builtin.import_playbook](https://docs.ansible.com/ansible/latest/collections/ansible/builtin/import_playbook_module.html)
+///
+/// This is a playbook entry, not a task. See [`PlayOrImport`].
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct ImportPlaybook {
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub name: OptU<String>,
+    /// The playbook file to import
+    #[serde(rename = "ansible.builtin.import_playbook", alias = "import_playbook")]
+    pub file: String,
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub tags: OptU<Vec<String>>,
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub vars: OptU<IndexMap<String, serde_json::Value>>,
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub when: OptU<BoolOrStringOrVecString>,
+}
+
+impl ImportPlaybook {
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+/// An entry of a playbook
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum PlayOrImport {
+    Play(Play),
+    ImportPlaybook(ImportPlaybook),
+}
+
+impl From<Play> for PlayOrImport {
+    fn from(value: Play) -> Self {
+        Self::Play(value)
+    }
+}
+
+impl From<ImportPlaybook> for PlayOrImport {
+    fn from(value: ImportPlaybook) -> Self {
+        Self::ImportPlaybook(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::WithTaskOptions as _;
+
+    #[test]
+    fn test_include_role() {
+        let task = Task::new(
+            "include",
+            IncludeRole::new("nginx")
+                .tasks_from("install")
+                .public(true)
+                .apply(TaskOptions::default().tags(["nginx"])),
+        )
+        .when("install_nginx");
+        assert_eq!(
+            serde_json::to_string(&task).expect("failed to serialize"),
+            String::new()
+                + "{"
+                + r#""name":"include","#
+                + r#""when":"install_nginx","#
+                + r#""ansible.builtin.include_role":{"#
+                + r#""name":"nginx","tasks_from":"install","public":true,"#
+                + r#""apply":{"tags":["nginx"]}"#
+                + "}}"
+        );
+    }
+
+    #[test]
+    fn test_import_tasks() {
+        assert_eq!(
+            serde_json::to_string(&Task::new("import", ImportTasks::new("setup.yaml")))
+                .expect("failed to serialize"),
+            r#"{"name":"import","ansible.builtin.import_tasks":{"file":"setup.yaml"}}"#
+        );
+    }
+
+    #[test]
+    fn test_play_or_import() {
+        let entries: Vec<PlayOrImport> = serde_json::from_str(
+            r#"[{"import_playbook":"base.yaml","when":"x"},{"name":"play1","hosts":"all","tasks":[]}]"#,
+        )
+        .expect("failed to deserialize");
+        assert!(matches!(
+            entries.as_slice(),
+            [PlayOrImport::ImportPlaybook(_), PlayOrImport::Play(_)]
+        ));
+        assert_eq!(
+            serde_json::to_string(&entries[0]).expect("failed to serialize"),
+            r#"{"ansible.builtin.import_playbook":"base.yaml","when":"x"}"#
+        );
+    }
+}
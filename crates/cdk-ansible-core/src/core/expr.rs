@@ -0,0 +1,595 @@
+//! Jinja2 expressions for conditional keywords (`when`, `changed_when`, `failed_when`, `until`)
+
+use crate::core::{BoolOrStringOrVecString, StringOrVec};
+use std::fmt;
+use std::ops::Not;
+
+/// A Jinja2 expression
+///
+/// [`fmt::Display`] renders the expression without `{{ }}`, which is the form of `when`, `changed_when`, `failed_when` and `until`.
+/// Use [`Expr::template`] for a templated value (e.g. module arguments).
+///
+/// Parentheses are inserted according to the Jinja2 operator precedence.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{BoolOrStringOrVecString, Expr};
+///
+/// let cond = Expr::var("result")
+///     .attr("rc")
+///     .equals(0)
+///     .and(Expr::var("mode").default("dev").not_equals("prod"));
+/// assert_eq!(
+///     cond.to_string(),
+///     "result.rc == 0 and mode | default('dev') != 'prod'"
+/// );
+///
+/// let when: BoolOrStringOrVecString = (!Expr::var("skip").is_defined()).into();
+/// assert_eq!(when, "not skip is defined".into());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A variable reference
+    Var(String),
+    /// A literal value
+    Literal(serde_json::Value),
+    /// `<expr>.<name>` (or `<expr>['<name>']` if `name` is not an identifier)
+    Attr(Box<Expr>, String),
+    /// `<expr>[<key>]`
+    Item(Box<Expr>, Box<Expr>),
+    /// `not <expr>`
+    Not(Box<Expr>),
+    /// `<lhs> and <rhs>`
+    And(Box<Expr>, Box<Expr>),
+    /// `<lhs> or <rhs>`
+    Or(Box<Expr>, Box<Expr>),
+    /// `<lhs> <op> <rhs>`
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    /// `<expr> | <name>(<args>)`
+    Filter(Box<Expr>, String, Vec<Expr>),
+    /// `<expr> is [not] <name>(<args>)`
+    Test {
+        expr: Box<Expr>,
+        name: String,
+        args: Vec<Expr>,
+        negated: bool,
+    },
+    /// `<name>(<args>, <kwarg>=<value>)`, a global function call (e.g. `lookup`, `query`)
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        kwargs: Vec<(String, Expr)>,
+    },
+    /// A raw Jinja2 expression, which is always parenthesized when it is an operand
+    Raw(String),
+}
+
+/// Comparison operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    NotIn,
+}
+
+impl CompareOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::In => "in",
+            Self::NotIn => "not in",
+        }
+    }
+}
+
+/// Binding strength in Jinja2, from the loosest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Raw,
+    Or,
+    And,
+    Not,
+    Compare,
+    Test,
+    Filter,
+    Primary,
+}
+
+impl Expr {
+    /// A variable reference. A name which is not an identifier is rendered as `vars['<name>']`.
+    pub fn var(name: &str) -> Self {
+        Self::Var(name.to_owned())
+    }
+
+    /// A literal value (`'string'`, `1`, `true`, `none`, `[...]`, `{...}`)
+    pub fn lit(value: impl Into<serde_json::Value>) -> Self {
+        Self::Literal(value.into())
+    }
+
+    /// A raw Jinja2 expression
+    pub fn raw(expr: &str) -> Self {
+        Self::Raw(expr.to_owned())
+    }
+
+    /// `self.<name>`
+    pub fn attr(self, name: &str) -> Self {
+        Self::Attr(Box::new(self), name.to_owned())
+    }
+
+    /// `self[<key>]`
+    pub fn item(self, key: impl Into<Expr>) -> Self {
+        Self::Item(Box::new(self), Box::new(key.into()))
+    }
+
+    pub fn and(self, rhs: impl Into<Expr>) -> Self {
+        Self::And(Box::new(self), Box::new(rhs.into()))
+    }
+
+    pub fn or(self, rhs: impl Into<Expr>) -> Self {
+        Self::Or(Box::new(self), Box::new(rhs.into()))
+    }
+
+    pub fn compare(self, op: CompareOp, rhs: impl Into<Expr>) -> Self {
+        Self::Compare(Box::new(self), op, Box::new(rhs.into()))
+    }
+
+    /// `self == rhs`
+    pub fn equals(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Eq, rhs)
+    }
+
+    /// `self != rhs`
+    pub fn not_equals(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Ne, rhs)
+    }
+
+    pub fn lt(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Lt, rhs)
+    }
+
+    pub fn le(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Le, rhs)
+    }
+
+    pub fn gt(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Gt, rhs)
+    }
+
+    pub fn ge(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Ge, rhs)
+    }
+
+    /// `self in rhs`
+    pub fn in_(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::In, rhs)
+    }
+
+    /// `self not in rhs`
+    pub fn not_in(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::NotIn, rhs)
+    }
+
+    /// `self | <name>(<args>)`
+    pub fn filter(self, name: &str, args: Vec<Expr>) -> Self {
+        Self::Filter(Box::new(self), name.to_owned(), args)
+    }
+
+    /// `self | default(<value>)`
+    pub fn default(self, value: impl Into<Expr>) -> Self {
+        self.filter("default", vec![value.into()])
+    }
+
+    /// `self | bool`
+    pub fn bool(self) -> Self {
+        self.filter("bool", vec![])
+    }
+
+    /// `self | int`
+    pub fn int(self) -> Self {
+        self.filter("int", vec![])
+    }
+
+    /// `self | length`
+    pub fn length(self) -> Self {
+        self.filter("length", vec![])
+    }
+
+    /// `self is <name>(<args>)`
+    pub fn test(self, name: &str, args: Vec<Expr>) -> Self {
+        Self::Test {
+            expr: Box::new(self),
+            name: name.to_owned(),
+            args,
+            negated: false,
+        }
+    }
+
+    /// `self is not <name>(<args>)`
+    pub fn test_not(self, name: &str, args: Vec<Expr>) -> Self {
+        Self::Test {
+            expr: Box::new(self),
+            name: name.to_owned(),
+            args,
+            negated: true,
+        }
+    }
+
+    /// `self is defined`
+    pub fn is_defined(self) -> Self {
+        self.test("defined", vec![])
+    }
+
+    /// `self is undefined`
+    pub fn is_undefined(self) -> Self {
+        self.test("undefined", vec![])
+    }
+
+    /// `{{ self }}`
+    pub fn template(&self) -> String {
+        format!("{{{{ {self} }}}}")
+    }
+
+    fn prec(&self) -> Prec {
+        match self {
+            Self::Var(_)
+            | Self::Literal(_)
+            | Self::Attr(..)
+            | Self::Item(..)
+            | Self::Call { .. } => Prec::Primary,
+            Self::Filter(..) => Prec::Filter,
+            Self::Test { .. } => Prec::Test,
+            Self::Compare(..) => Prec::Compare,
+            Self::Not(_) => Prec::Not,
+            Self::And(..) => Prec::And,
+            Self::Or(..) => Prec::Or,
+            Self::Raw(_) => Prec::Raw,
+        }
+    }
+
+    /// Write `self`, parenthesized if it binds looser than `min`
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min: Prec) -> fmt::Result {
+        if self.prec() < min {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Var(name) => {
+                if is_identifier(name) {
+                    f.write_str(name)
+                } else {
+                    write!(f, "vars[{}]", quote(name))
+                }
+            }
+            Self::Literal(value) => fmt_literal(f, value),
+            Self::Attr(expr, name) => {
+                expr.fmt_operand(f, Prec::Primary)?;
+                if is_identifier(name) {
+                    write!(f, ".{name}")
+                } else {
+                    write!(f, "[{}]", quote(name))
+                }
+            }
+            Self::Item(expr, key) => {
+                expr.fmt_operand(f, Prec::Primary)?;
+                write!(f, "[{key}]")
+            }
+            Self::Not(expr) => {
+                f.write_str("not ")?;
+                expr.fmt_operand(f, Prec::Not)
+            }
+            Self::And(lhs, rhs) => {
+                lhs.fmt_operand(f, Prec::And)?;
+                f.write_str(" and ")?;
+                rhs.fmt_operand(f, Prec::Not)
+            }
+            Self::Or(lhs, rhs) => {
+                lhs.fmt_operand(f, Prec::Or)?;
+                f.write_str(" or ")?;
+                rhs.fmt_operand(f, Prec::And)
+            }
+            Self::Compare(lhs, op, rhs) => {
+                lhs.fmt_operand(f, Prec::Test)?;
+                write!(f, " {} ", op.as_str())?;
+                rhs.fmt_operand(f, Prec::Test)
+            }
+            Self::Filter(expr, name, args) => {
+                expr.fmt_operand(f, Prec::Filter)?;
+                write!(f, " | {name}")?;
+                fmt_args(f, args)
+            }
+            Self::Test {
+                expr,
+                name,
+                args,
+                negated,
+            } => {
+                expr.fmt_operand(f, Prec::Filter)?;
+                f.write_str(if *negated { " is not " } else { " is " })?;
+                f.write_str(name)?;
+                fmt_args(f, args)
+            }
+            Self::Call { name, args, kwargs } => {
+                write!(f, "{name}(")?;
+                let kwargs = kwargs.iter().map(|(k, v)| (Some(k), v));
+                for (i, (key, value)) in args.iter().map(|v| (None, v)).chain(kwargs).enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    if let Some(key) = key {
+                        write!(f, "{key}=")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str(")")
+            }
+            Self::Raw(expr) => f.write_str(expr),
+        }
+    }
+}
+
+/// `not <expr>`
+impl Not for Expr {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
+impl From<&str> for Expr {
+    fn from(value: &str) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<String> for Expr {
+    fn from(value: String) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<bool> for Expr {
+    fn from(value: bool) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<i32> for Expr {
+    fn from(value: i32) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<f64> for Expr {
+    fn from(value: f64) -> Self {
+        Self::lit(value)
+    }
+}
+
+impl From<serde_json::Value> for Expr {
+    fn from(value: serde_json::Value) -> Self {
+        Self::Literal(value)
+    }
+}
+
+impl From<Expr> for BoolOrStringOrVecString {
+    fn from(value: Expr) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// A list of conditions, which are combined with `and` by Ansible
+impl From<Vec<Expr>> for BoolOrStringOrVecString {
+    fn from(value: Vec<Expr>) -> Self {
+        Self::VecString(value.iter().map(ToString::to_string).collect())
+    }
+}
+
+/// A templated list for `loop`
+impl From<Expr> for StringOrVec {
+    fn from(value: Expr) -> Self {
+        Self::String(value.template())
+    }
+}
+
+fn fmt_args(f: &mut fmt::Formatter<'_>, args: &[Expr]) -> fmt::Result {
+    if args.is_empty() {
+        return Ok(());
+    }
+    f.write_str("(")?;
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{arg}")?;
+    }
+    f.write_str(")")
+}
+
+fn fmt_literal(f: &mut fmt::Formatter<'_>, value: &serde_json::Value) -> fmt::Result {
+    match value {
+        serde_json::Value::Null => f.write_str("none"),
+        serde_json::Value::Bool(b) => write!(f, "{b}"),
+        serde_json::Value::Number(n) => write!(f, "{n}"),
+        serde_json::Value::String(s) => f.write_str(&quote(s)),
+        serde_json::Value::Array(items) => {
+            f.write_str("[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                fmt_literal(f, item)?;
+            }
+            f.write_str("]")
+        }
+        serde_json::Value::Object(map) => {
+            f.write_str("{")?;
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: ", quote(k))?;
+                fmt_literal(f, v)?;
+            }
+            f.write_str("}")
+        }
+    }
+}
+
+/// A single-quoted Jinja2 string literal
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// `[A-Za-z_][A-Za-z0-9_]*`, except the keywords of Jinja2
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(
+            s,
+            "and"
+                | "or"
+                | "not"
+                | "in"
+                | "is"
+                | "if"
+                | "else"
+                | "true"
+                | "false"
+                | "none"
+                | "True"
+                | "False"
+                | "None"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal() {
+        for (expr, expected) in [
+            (Expr::lit("it's"), r"'it\'s'"),
+            (Expr::lit("a\\b\n"), r"'a\\b\n'"),
+            (Expr::lit(1), "1"),
+            (Expr::lit(1.5), "1.5"),
+            (Expr::lit(true), "true"),
+            (Expr::lit(serde_json::Value::Null), "none"),
+            (
+                Expr::lit(serde_json::json!(["a", 1, {"k": false}])),
+                "['a', 1, {'k': false}]",
+            ),
+        ] {
+            assert_eq!(expr.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_var() {
+        assert_eq!(Expr::var("x").to_string(), "x");
+        assert_eq!(Expr::var("my-var").to_string(), "vars['my-var']");
+        assert_eq!(
+            Expr::var("result").attr("stdout").to_string(),
+            "result.stdout"
+        );
+        assert_eq!(
+            Expr::var("hostvars").attr("web-1").attr("ip").to_string(),
+            "hostvars['web-1'].ip"
+        );
+        assert_eq!(
+            Expr::var("result").attr("results").item(0).to_string(),
+            "result.results[0]"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let a = || Expr::var("a");
+        let b = || Expr::var("b");
+        let c = || Expr::var("c");
+        for (expr, expected) in [
+            (a().and(b()).or(c()), "a and b or c"),
+            (a().and(b().or(c())), "a and (b or c)"),
+            (a().or(b()).and(c()), "(a or b) and c"),
+            (a().or(b().or(c())), "a or (b or c)"),
+            (!a().and(b()), "not (a and b)"),
+            (!a().equals(1), "not a == 1"),
+            (a().and(!b()), "a and not b"),
+            (!a().is_defined(), "not a is defined"),
+            (a().test_not("defined", vec![]), "a is not defined"),
+            (a().default(0).int().gt(1), "a | default(0) | int > 1"),
+            (a().equals(1).bool(), "(a == 1) | bool"),
+            (a().is_defined().bool(), "(a is defined) | bool"),
+            (
+                a().length().test("divisibleby", vec![2.into()]),
+                "a | length is divisibleby(2)",
+            ),
+            ("x".into(), "'x'"),
+            (Expr::lit("x").in_(a()), "'x' in a"),
+            (
+                a().not_in(Expr::lit(serde_json::json!([1, 2]))),
+                "a not in [1, 2]",
+            ),
+            (Expr::raw("a + 1").gt(b()), "(a + 1) > b"),
+            (Expr::raw("a + 1").attr("x"), "(a + 1).x"),
+            (a().or(b()).attr("x"), "(a or b).x"),
+        ] {
+            assert_eq!(expr.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_into_conditions() {
+        let cond = Expr::var("x").is_defined();
+        assert_eq!(cond.template(), "{{ x is defined }}");
+        assert_eq!(
+            BoolOrStringOrVecString::from(cond.clone()),
+            BoolOrStringOrVecString::String("x is defined".to_string())
+        );
+        assert_eq!(
+            BoolOrStringOrVecString::from(vec![cond, Expr::var("y").bool()]),
+            BoolOrStringOrVecString::VecString(vec![
+                "x is defined".to_string(),
+                "y | bool".to_string()
+            ])
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::core::Task;
+use serde::{Deserialize, Serialize};
+
+/// A handle for a [handler](https://docs.ansible.com/ansible/latest/playbook_guide/playbooks_handlers.html), used in `notify` and `listen`
+///
+/// A handle refers to a handler by its name, or to a `listen` topic.
+/// Synth fails when a task notifies a handler (or a topic) which doesn't exist in its play.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{Handler, OptU, RawModule, Task, TaskOptions};
+///
+/// let restart = Task {
+///     name: "restart nginx".to_string(),
+///     options: TaskOptions::default(),
+///     command: Box::new(RawModule::new(
+///         "ansible.builtin.service",
+///         serde_json::json!({ "name": "nginx", "state": "restarted" }),
+///     )),
+/// };
+/// let _options = TaskOptions {
+///     notify: OptU::Some(vec![Handler::of(&restart)]),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Handler(String);
+
+impl Handler {
+    /// The handler task
+    pub fn of(handler: &Task) -> Self {
+        Self(handler.name.clone())
+    }
+
+    /// A `listen` topic
+    pub fn topic(topic: &str) -> Self {
+        Self(topic.to_owned())
+    }
+
+    /// Name of the handler or the topic
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Handler {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl From<String> for Handler {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&Task> for Handler {
+    fn from(value: &Task) -> Self {
+        Self::of(value)
+    }
+}
@@ -0,0 +1,466 @@
+//! [Host patterns](https://docs.ansible.com/ansible/latest/inventory_guide/intro_patterns.html) for `hosts`
+
+use crate::core::{InventoryChild, InventoryRoot, StringOrVecString};
+use indexmap::{IndexMap, IndexSet};
+use regex::Regex;
+use std::fmt;
+
+/// A term of a [`HostPattern`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostTerm {
+    /// A host or a group. `*` and `?` are wildcards (e.g. `web*.example.com`).
+    Name(String),
+    /// `~<regex>`, matched against the start of host and group names
+    Regex(String),
+    /// `<term>[<index>]`, the host at the index of the hosts matching the term.
+    /// A negative index counts from the end.
+    Index(Box<HostTerm>, i64),
+    /// `<term>[<start>:<end>]`, the hosts in the range of the hosts matching the term,
+    /// where both ends are inclusive.
+    /// `None` or `-1` is the end of the hosts, written as `<term>[<start>:]`.
+    ///
+    /// As in Ansible, `<end>` of `0` takes only the host at `<start>`,
+    /// and any other negative `<end>` makes the term a literal name.
+    Slice(Box<HostTerm>, u64, Option<i64>),
+}
+
+impl HostTerm {
+    /// Hosts matching the term, in the inventory order
+    fn resolve(&self, groups: &Groups) -> Result<Vec<String>, regex::Error> {
+        Ok(match self {
+            Self::Name(name) if name.contains(['*', '?']) => {
+                let mut hosts = IndexSet::new();
+                for (group, members) in groups.groups.iter() {
+                    if wildcard_match(name, group) {
+                        hosts.extend(members.iter().cloned());
+                    }
+                }
+                hosts.extend(
+                    groups
+                        .all()
+                        .iter()
+                        .filter(|host| wildcard_match(name, host))
+                        .cloned(),
+                );
+                hosts.into_iter().collect()
+            }
+            Self::Name(name) => match groups.groups.get(name) {
+                Some(members) => members.iter().cloned().collect(),
+                None => groups
+                    .all()
+                    .iter()
+                    .filter(|h| *h == name)
+                    .cloned()
+                    .collect(),
+            },
+            Self::Regex(regex) => {
+                // Ansible matches with `re.match`, from the start of the name
+                let regex = Regex::new(&format!("^(?:{regex})"))?;
+                let mut hosts = IndexSet::new();
+                for (group, members) in groups.groups.iter() {
+                    if regex.is_match(group) {
+                        hosts.extend(members.iter().cloned());
+                    }
+                }
+                hosts.extend(
+                    groups
+                        .all()
+                        .iter()
+                        .filter(|host| regex.is_match(host))
+                        .cloned(),
+                );
+                hosts.into_iter().collect()
+            }
+            Self::Index(term, index) => {
+                let hosts = term.resolve(groups)?;
+                position(*index, hosts.len())
+                    .and_then(|i| hosts.get(i))
+                    .cloned()
+                    .into_iter()
+                    .collect()
+            }
+            Self::Slice(_, _, Some(end)) if *end < -1 => {
+                // Not a subscript for Ansible
+                Self::Name(self.to_string()).resolve(groups)?
+            }
+            Self::Slice(term, start, end) => {
+                let hosts = term.resolve(groups)?;
+                let len = hosts.len();
+                let start = usize::try_from(*start).unwrap_or(usize::MAX).min(len);
+                let end = match end {
+                    // `hosts[start]` for a falsy end
+                    Some(0) => start.saturating_add(1).min(len),
+                    Some(end) if *end > 0 => usize::try_from(*end)
+                        .unwrap_or(usize::MAX)
+                        .saturating_add(1)
+                        .min(len),
+                    Some(_) | None => len,
+                };
+                hosts.get(start..end).unwrap_or_default().to_vec()
+            }
+        })
+    }
+}
+
+impl fmt::Display for HostTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => f.write_str(name),
+            Self::Regex(regex) => write!(f, "~{regex}"),
+            Self::Index(term, index) => write!(f, "{term}[{index}]"),
+            Self::Slice(term, start, None | Some(-1)) => write!(f, "{term}[{start}:]"),
+            Self::Slice(term, start, Some(end)) => write!(f, "{term}[{start}:{end}]"),
+        }
+    }
+}
+
+impl From<&str> for HostTerm {
+    fn from(value: &str) -> Self {
+        Self::Name(value.to_owned())
+    }
+}
+
+impl From<String> for HostTerm {
+    fn from(value: String) -> Self {
+        Self::Name(value)
+    }
+}
+
+/// A host pattern, which is `<union>:&<intersection>:!<exclusion>`
+///
+/// As in Ansible, the result is the union of the terms, intersected with each of `intersect`,
+/// excluding each of `exclude`, regardless of the order in which they are added.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{HostPattern, StringOrVecString};
+///
+/// let pattern = HostPattern::name("webservers")
+///     .union("dbservers")
+///     .intersect("staging")
+///     .exclude(HostPattern::regex(r"^db0\d"));
+/// assert_eq!(pattern.to_string(), "webservers:dbservers:&staging:!~^db0\\d");
+/// let _hosts: StringOrVecString = pattern.into();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostPattern {
+    pub union: Vec<HostTerm>,
+    pub intersect: Vec<HostTerm>,
+    pub exclude: Vec<HostTerm>,
+}
+
+impl HostPattern {
+    pub fn new(term: impl Into<HostTerm>) -> Self {
+        Self {
+            union: vec![term.into()],
+            intersect: vec![],
+            exclude: vec![],
+        }
+    }
+
+    /// All hosts
+    pub fn all() -> Self {
+        Self::name("all")
+    }
+
+    /// A host or a group, which may have wildcards
+    pub fn name(name: &str) -> Self {
+        Self::new(HostTerm::Name(name.to_owned()))
+    }
+
+    /// Hosts and groups whose names start with a match of the regex
+    pub fn regex(regex: &str) -> HostTerm {
+        HostTerm::Regex(regex.to_owned())
+    }
+
+    /// `<term>[<index>]`, where the term is a group, a host, a wildcard or a regex
+    pub fn index(term: impl Into<HostTerm>, index: i64) -> HostTerm {
+        HostTerm::Index(Box::new(term.into()), index)
+    }
+
+    /// `<term>[<start>:<end>]`, where both ends are inclusive. `None` or `-1` is the end of the hosts.
+    pub fn slice(term: impl Into<HostTerm>, start: u64, end: Option<i64>) -> HostTerm {
+        HostTerm::Slice(Box::new(term.into()), start, end)
+    }
+
+    /// `self:<term>`
+    pub fn union(mut self, term: impl Into<HostTerm>) -> Self {
+        self.union.push(term.into());
+        self
+    }
+
+    /// `self:&<term>`
+    pub fn intersect(mut self, term: impl Into<HostTerm>) -> Self {
+        self.intersect.push(term.into());
+        self
+    }
+
+    /// `self:!<term>`
+    pub fn exclude(mut self, term: impl Into<HostTerm>) -> Self {
+        self.exclude.push(term.into());
+        self
+    }
+
+    /// Hosts of the inventory matching the pattern, in the inventory order
+    pub fn resolve(&self, inventory: &InventoryRoot) -> Result<Vec<String>, regex::Error> {
+        let groups = Groups::new(inventory);
+        let mut hosts = IndexSet::new();
+        for term in self.union.iter() {
+            hosts.extend(term.resolve(&groups)?);
+        }
+        for term in self.intersect.iter() {
+            let other = term.resolve(&groups)?.into_iter().collect::<IndexSet<_>>();
+            hosts.retain(|host| other.contains(host));
+        }
+        for term in self.exclude.iter() {
+            for host in term.resolve(&groups)? {
+                hosts.shift_remove(&host);
+            }
+        }
+        // in the inventory order
+        Ok(groups
+            .all()
+            .iter()
+            .filter(|host| hosts.contains(*host))
+            .cloned()
+            .collect())
+    }
+}
+
+impl fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.union.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{term}")?;
+        }
+        for term in self.intersect.iter() {
+            write!(f, ":&{term}")?;
+        }
+        for term in self.exclude.iter() {
+            write!(f, ":!{term}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<HostTerm> for HostPattern {
+    fn from(value: HostTerm) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<HostPattern> for StringOrVecString {
+    fn from(value: HostPattern) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// Hosts of each group, including `all` and `ungrouped`
+struct Groups {
+    groups: IndexMap<String, IndexSet<String>>,
+}
+
+impl Groups {
+    fn new(inventory: &InventoryRoot) -> Self {
+        let mut groups = IndexMap::new();
+        let all = collect_group(&inventory.all, &mut groups);
+        let ungrouped = match inventory.all.hosts.as_option() {
+            Some(hosts) => hosts
+                .keys()
+                .filter(|host| {
+                    !groups
+                        .values()
+                        .any(|g: &IndexSet<String>| g.contains(*host))
+                })
+                .cloned()
+                .collect(),
+            None => IndexSet::new(),
+        };
+        groups.insert("ungrouped".to_owned(), ungrouped);
+        groups.insert("all".to_owned(), all);
+        Self { groups }
+    }
+
+    fn all(&self) -> &IndexSet<String> {
+        &self.groups["all"]
+    }
+}
+
+/// Hosts of the group and its descendants, registering each descendant in `groups`
+fn collect_group(
+    group: &InventoryChild,
+    groups: &mut IndexMap<String, IndexSet<String>>,
+) -> IndexSet<String> {
+    let mut hosts: IndexSet<String> = match group.hosts.as_option() {
+        Some(hosts) => hosts.keys().cloned().collect(),
+        None => IndexSet::new(),
+    };
+    if let Some(children) = group.children.as_option() {
+        for (name, child) in children.iter() {
+            let child_hosts = collect_group(child, groups);
+            hosts.extend(child_hosts.iter().cloned());
+            groups.entry(name.clone()).or_default().extend(child_hosts);
+        }
+    }
+    hosts
+}
+
+/// Index from the start, for a Python-style index
+fn position(index: i64, len: usize) -> Option<usize> {
+    let len = i64::try_from(len).ok()?;
+    let index = if index < 0 { len + index } else { index };
+    (0..len)
+        .contains(&index)
+        .then(|| usize::try_from(index).ok())?
+}
+
+/// `*` matches any string, `?` matches any character
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // (pattern index, name index) to retry from the last `*`
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || Some(c) == name.get(n) => {
+                p += 1;
+                n += 1;
+            }
+            Some(_) | None => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern
+        .get(p..)
+        .unwrap_or_default()
+        .iter()
+        .all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::OptU;
+
+    fn inventory() -> InventoryRoot {
+        let group = |hosts: &[&str]| InventoryChild {
+            hosts: OptU::Some(hosts.iter().map(|h| (h.to_string(), None)).collect()),
+            ..Default::default()
+        };
+        InventoryRoot {
+            all: InventoryChild {
+                hosts: OptU::Some(IndexMap::from([("bastion".to_string(), None)])),
+                children: OptU::Some(IndexMap::from([
+                    ("web".to_string(), group(&["web1", "web2", "web3"])),
+                    ("db".to_string(), group(&["db1", "db2"])),
+                    ("staging".to_string(), group(&["web1", "db1"])),
+                ])),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let inv = inventory();
+        let resolve = |p: HostPattern| p.resolve(&inv).expect("failed to resolve");
+        assert_eq!(
+            resolve(HostPattern::name("web").union("db").intersect("staging")),
+            vec!["web1", "db1"]
+        );
+        assert_eq!(
+            resolve(HostPattern::all().exclude("web")),
+            vec!["bastion", "db1", "db2"]
+        );
+        assert_eq!(resolve(HostPattern::name("ungrouped")), vec!["bastion"]);
+        assert_eq!(
+            resolve(HostPattern::name("web?")),
+            vec!["web1", "web2", "web3"]
+        );
+        assert_eq!(
+            resolve(HostPattern::regex("^(db|bastion)").into()),
+            vec!["bastion", "db1", "db2"]
+        );
+        assert_eq!(resolve(HostPattern::index("web", -1).into()), vec!["web3"]);
+        assert_eq!(
+            resolve(HostPattern::slice("web", 1, None).into()),
+            vec!["web2", "web3"]
+        );
+        assert_eq!(
+            resolve(HostPattern::slice("web", 0, Some(1)).into()),
+            vec!["web1", "web2"]
+        );
+        // As in Ansible
+        assert_eq!(
+            resolve(HostPattern::slice("web", 0, Some(-1)).into()),
+            vec!["web1", "web2", "web3"]
+        );
+        assert_eq!(
+            resolve(HostPattern::slice("web", 1, Some(0)).into()),
+            vec!["web2"]
+        );
+        assert!(resolve(HostPattern::slice("web", 0, Some(-2)).into()).is_empty());
+        assert_eq!(
+            resolve(HostPattern::slice("web", 2, Some(10)).into()),
+            vec!["web3"]
+        );
+        // Regexes match from the start
+        assert!(resolve(HostPattern::regex("eb").into()).is_empty());
+        assert_eq!(
+            resolve(HostPattern::regex("db|bastion").into()),
+            vec!["bastion", "db1", "db2"]
+        );
+        // Subscripts apply to the hosts matching any term
+        assert_eq!(resolve(HostPattern::index("web*", 0).into()), vec!["web1"]);
+        // Hosts of the matching groups come first, as in Ansible
+        assert_eq!(
+            resolve(HostPattern::slice(HostPattern::regex("db|bastion"), 1, None).into()),
+            vec!["bastion", "db2"]
+        );
+        assert_eq!(resolve(HostPattern::index("web2", 0).into()), vec!["web2"]);
+        assert!(resolve(HostPattern::index("nothing", 0).into()).is_empty());
+        assert!(resolve(HostPattern::name("nothing")).is_empty());
+        assert!(
+            HostPattern::new(HostPattern::regex("("))
+                .resolve(&inv)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            HostPattern::new(HostPattern::slice("web", 0, Some(2)))
+                .union(HostPattern::index("db", 0))
+                .exclude("web*")
+                .to_string(),
+            "web[0:2]:db[0]:!web*"
+        );
+        assert_eq!(
+            HostPattern::slice("web", 1, Some(-1)).to_string(),
+            "web[1:]"
+        );
+        assert_eq!(HostPattern::slice("web", 1, None).to_string(), "web[1:]");
+        assert_eq!(HostPattern::index("web*", 0).to_string(), "web*[0]");
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.example.com", "a.b.example.com"));
+        assert!(wildcard_match("w?b*", "web1"));
+        assert!(!wildcard_match("web", "web1"));
+        assert!(!wildcard_match("*.com", "example.org"));
+    }
+}
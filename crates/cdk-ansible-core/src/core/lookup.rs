@@ -0,0 +1,210 @@
+//! Lookup plugins, in `with_<lookup>` loops and in `lookup()` / `query()` calls
+
+use crate::core::Expr;
+use indexmap::IndexMap;
+use serde::ser::SerializeMap as _;
+use serde::{Serialize, Serializer};
+
+/// `with_<lookup>` loop of a task, [`crate::core::TaskOptions::with_lookup`]
+///
+/// This is serialized as a `with_<lookup>` keyword of the task.
+/// `terms` is a list or a templated string (e.g. `"{{ users }}"`).
+///
+/// ```rust
+/// use cdk_ansible_core::core::{OptU, TaskOptions, WithLookup};
+///
+/// let options = TaskOptions {
+///     with_lookup: OptU::Some(WithLookup::fileglob(serde_json::json!(["/etc/*.conf"]))),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     serde_json::to_string(&options).unwrap(),
+///     r#"{"with_fileglob":["/etc/*.conf"]}"#
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithLookup {
+    /// Name of the lookup plugin
+    pub lookup: String,
+    pub terms: serde_json::Value,
+}
+
+impl WithLookup {
+    pub fn new(lookup: &str, terms: impl Into<serde_json::Value>) -> Self {
+        Self {
+            lookup: lookup.to_owned(),
+            terms: terms.into(),
+        }
+    }
+
+    /// `with_items`
+    pub fn items(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("items", terms)
+    }
+
+    /// `with_list`
+    pub fn list(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("list", terms)
+    }
+
+    /// `with_dict`
+    pub fn dict(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("dict", terms)
+    }
+
+    /// `with_fileglob`
+    pub fn fileglob(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("fileglob", terms)
+    }
+
+    /// `with_file`
+    pub fn file(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("file", terms)
+    }
+
+    /// `with_sequence`
+    pub fn sequence(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("sequence", terms)
+    }
+
+    /// `with_subelements`
+    pub fn subelements(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("subelements", terms)
+    }
+
+    /// `with_nested`
+    pub fn nested(terms: impl Into<serde_json::Value>) -> Self {
+        Self::new("nested", terms)
+    }
+
+    /// The keyword, `with_<lookup>`
+    pub fn keyword(&self) -> String {
+        format!("with_{}", self.lookup)
+    }
+}
+
+impl Serialize for WithLookup {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&self.keyword(), &self.terms)?;
+        map.end()
+    }
+}
+
+/// A call of a lookup plugin, rendered as `lookup(...)` or `query(...)`
+///
+/// ```rust
+/// use cdk_ansible_core::core::{Lookup, StringOrVec};
+///
+/// let files = Lookup::new("fileglob").term("/etc/*.conf");
+/// assert_eq!(
+///     files.query().to_string(),
+///     "query('fileglob', '/etc/*.conf')"
+/// );
+/// let _loop: StringOrVec = files.query().into();
+///
+/// let home = Lookup::new("env").term("HOME").kwarg("default", "/root");
+/// assert_eq!(
+///     home.lookup().template(),
+///     "{{ lookup('env', 'HOME', default='/root') }}"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lookup {
+    /// Name of the lookup plugin
+    pub plugin: String,
+    pub terms: Vec<Expr>,
+    /// Keyword arguments (e.g. `errors`, `wantlist`)
+    pub kwargs: IndexMap<String, Expr>,
+}
+
+impl Lookup {
+    pub fn new(plugin: &str) -> Self {
+        Self {
+            plugin: plugin.to_owned(),
+            terms: vec![],
+            kwargs: IndexMap::new(),
+        }
+    }
+
+    /// Append a term
+    pub fn term(mut self, term: impl Into<Expr>) -> Self {
+        self.terms.push(term.into());
+        self
+    }
+
+    /// Append terms
+    pub fn terms<I>(mut self, terms: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Expr>,
+    {
+        self.terms.extend(terms.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set a keyword argument
+    pub fn kwarg(mut self, name: &str, value: impl Into<Expr>) -> Self {
+        self.kwargs.insert(name.to_owned(), value.into());
+        self
+    }
+
+    /// `lookup('<plugin>', <terms>, <kwargs>)`, which returns a comma-separated string by default
+    pub fn lookup(&self) -> Expr {
+        self.call("lookup")
+    }
+
+    /// `query('<plugin>', <terms>, <kwargs>)`, which always returns a list
+    pub fn query(&self) -> Expr {
+        self.call("query")
+    }
+
+    fn call(&self, function: &str) -> Expr {
+        let mut args = vec![Expr::lit(self.plugin.as_str())];
+        args.extend(self.terms.iter().cloned());
+        Expr::Call {
+            name: function.to_owned(),
+            args,
+            kwargs: self
+                .kwargs
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{OptU, Register, TaskOptions};
+
+    #[test]
+    fn test_with_lookup_in_task_options() {
+        let users = Register::new("users");
+        let options = TaskOptions {
+            become_: OptU::Some(true.into()),
+            with_lookup: OptU::Some(WithLookup::dict(users.expr().template())),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&options).expect("failed to serialize"),
+            r#"{"become":true,"with_dict":"{{ users }}"}"#
+        );
+    }
+
+    #[test]
+    fn test_lookup_call() {
+        let lookup = Lookup::new("file")
+            .terms(["a.txt", "b.txt"])
+            .kwarg("errors", "ignore");
+        assert_eq!(
+            lookup.lookup().to_string(),
+            "lookup('file', 'a.txt', 'b.txt', errors='ignore')"
+        );
+        assert_eq!(
+            lookup.query().length().gt(0).to_string(),
+            "query('file', 'a.txt', 'b.txt', errors='ignore') | length > 0"
+        );
+    }
+}
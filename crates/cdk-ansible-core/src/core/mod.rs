@@ -1,10 +1,36 @@
+use cdk_ansible_macro::OptionsSetters;
 use dyn_clone::{DynClone, clone_trait_object};
 use erased_serde::serialize_trait_object;
 use indexmap::IndexMap;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, de};
 
+mod argument_spec;
+mod block;
+mod builder;
+mod control;
+mod expr;
+mod handler;
+mod host_pattern;
+mod lookup;
+mod raw_module;
+mod register;
+mod role;
+mod tag;
 mod types;
+mod vars_prompt;
+pub use argument_spec::*;
+pub use block::*;
+pub use control::*;
+pub use expr::*;
+pub use handler::*;
+pub use host_pattern::*;
+pub use lookup::*;
+pub use raw_module::*;
+pub use register::*;
+pub use role::*;
+pub use tag::*;
 pub use types::*;
+pub use vars_prompt::*;
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Inventory {
@@ -14,23 +40,46 @@ pub struct Inventory {
 
 impl Inventory {
     /// @deprecated
+    ///
+    /// Tagged values ([`Secret`], [`Unsafe`]) are an error, as JSON has no tags.
     pub fn dump_json(&self) -> serde_json::Result<String> {
-        serde_json::to_string(&self.root)
+        let value = serde_json::to_value(&self.root)?;
+        if let Some(key) = find_tag_key(&value) {
+            return Err(serde::ser::Error::custom(format!(
+                "'{key}' value can't be written as JSON"
+            )));
+        }
+        serde_json::to_string(&value)
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+/// The key of the first tagged value in `value`
+fn find_tag_key(value: &serde_json::Value) -> Option<&str> {
+    match value {
+        serde_json::Value::Array(items) => items.iter().find_map(find_tag_key),
+        serde_json::Value::Object(map) => map.iter().find_map(|(k, v)| {
+            if k.starts_with(TAG_KEY_PREFIX) {
+                Some(k.as_str())
+            } else {
+                find_tag_key(v)
+            }
+        }),
+        _ => None,
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InventoryRoot {
     pub all: InventoryChild,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InventoryChild {
-    #[serde(skip_serializing_if = "OptU::is_unset")]
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub hosts: OptU<InventoryHosts>,
-    #[serde(skip_serializing_if = "OptU::is_unset")]
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub children: OptU<IndexMap<String, InventoryChild>>,
-    #[serde(skip_serializing_if = "OptU::is_unset")]
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub vars: OptU<InventoryVars>,
 }
 
@@ -42,7 +91,17 @@ pub struct Playbook {
     /// Name of the playbook
     /// The output file name will be `<name>.yaml`
     pub name: String,
-    pub plays: Vec<Play>,
+    pub plays: Vec<PlayOrImport>,
+}
+
+impl Playbook {
+    /// Plays of the playbook, skipping `import_playbook` entries
+    pub fn iter_plays(&self) -> impl Iterator<Item = &Play> {
+        self.plays.iter().filter_map(|entry| match entry {
+            PlayOrImport::Play(play) => Some(play),
+            PlayOrImport::ImportPlaybook(_) => None,
+        })
+    }
 }
 
 /// Option for an unset value
@@ -55,7 +114,7 @@ pub struct Playbook {
 ///
 /// let x: OptU<i32> = OptU::Unset;
 /// ```
-#[derive(Clone, Debug, PartialEq, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum OptU<T: Serialize> {
     Some(T),
@@ -68,24 +127,35 @@ impl<T: Serialize> OptU<T> {
     pub fn is_unset(&self) -> bool {
         matches!(self, OptU::Unset)
     }
+
+    /// The value, if it is set
+    pub fn as_option(&self) -> Option<&T> {
+        match self {
+            OptU::Some(value) => Some(value),
+            OptU::Unset => None,
+        }
+    }
 }
 
 /// Play
 /// Optional Values are defined in [`PlayOptions`]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Play {
     /// Identifier. Can be used for documentation, or in tasks/handlers.
+    /// An empty name is not serialized.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
     /// A list of groups, hosts or host pattern that translates into a list of hosts that are the play's target.
     pub hosts: StringOrVecString,
     #[serde(flatten)]
     pub options: PlayOptions,
     /// Main list of tasks to execute in the play, they run after roles and before post_tasks.
-    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub tasks: Vec<TaskOrBlock>,
 }
 
 /// [playbook keywords (play)](https://docs.ansible.com/ansible/latest/reference_appendices/playbooks_keywords.html#play)
-#[derive(Serialize, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, OptionsSetters)]
 pub struct PlayOptions {
     /// Force any un-handled task errors on any host to propagate to all hosts and end the play.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
@@ -178,16 +248,16 @@ pub struct PlayOptions {
     pub port: OptU<IntOrString>,
     /// A list of tasks to execute after the tasks section.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub post_tasks: OptU<Vec<Task>>,
+    pub post_tasks: OptU<Vec<TaskOrBlock>>,
     /// A list of tasks to execute before roles.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub pre_tasks: OptU<Vec<Task>>,
+    pub pre_tasks: OptU<Vec<TaskOrBlock>>,
     /// User used to log into the target via the connection plugin.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub remote_user: OptU<String>,
     /// List of roles to be imported into the play
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub roles: OptU<Vec<String>>,
+    pub roles: OptU<Vec<StringOrRole>>,
     /// Boolean that will bypass the host loop, forcing the task to attempt to execute on the first host available
     /// and afterward apply any results and facts to all active hosts in the same batch.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
@@ -215,12 +285,17 @@ pub struct PlayOptions {
     pub vars_files: OptU<Vec<String>>,
     /// List of variables to prompt for.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub vars_prompt: OptU<Vec<String>>,
+    pub vars_prompt: OptU<Vec<VarsPrompt>>,
 }
 
+/// Task
+///
+/// In deserializing, the keys other than `name` and [`TaskOptions`] are kept in a [`RawModule`].
+/// A task without `name` gets an empty name, which is not serialized.
 #[derive(Serialize, Clone, Debug)]
 pub struct Task {
     /// Identifier. Can be used for documentation, or in tasks/handlers.
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub name: String,
     #[serde(flatten)]
     pub options: TaskOptions,
@@ -228,6 +303,61 @@ pub struct Task {
     pub command: Box<dyn TaskModule>,
 }
 
+impl<'de> Deserialize<'de> for Task {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct TaskRepr {
+            #[serde(default)]
+            name: String,
+            #[serde(flatten)]
+            options: TaskOptions,
+            #[serde(flatten)]
+            command: RawModule,
+        }
+
+        let mut repr = TaskRepr::deserialize(deserializer)?;
+        // `with_<lookup>` is collected by the module, as it is not a fixed key
+        let with_keys: Vec<String> = repr
+            .command
+            .0
+            .keys()
+            .filter(|key| key.starts_with("with_"))
+            .cloned()
+            .collect();
+        match with_keys.as_slice() {
+            [] => {}
+            [key] => {
+                let terms = repr.command.0.shift_remove(key).unwrap_or_default();
+                repr.options.with_lookup = OptU::Some(WithLookup::new(
+                    key.strip_prefix("with_").unwrap_or(key),
+                    terms,
+                ));
+            }
+            [..] => {
+                return Err(de::Error::custom(format!(
+                    "task '{}' has multiple with_<lookup> keys: {}",
+                    repr.name,
+                    with_keys.join(", ")
+                )));
+            }
+        }
+        if repr.command.0.is_empty()
+            && repr.options.action.is_unset()
+            && repr.options.local_action.is_unset()
+        {
+            return Err(de::Error::custom(format!(
+                "task '{}' has no module",
+                repr.name
+            )));
+        }
+        Ok(Self {
+            name: repr.name,
+            options: repr.options,
+            command: Box::new(repr.command),
+        })
+    }
+}
+
 /// Task module trait
 ///
 /// If you want to add a new task module, you need to implement this trait
@@ -243,13 +373,18 @@ pub struct Task {
 /// }
 /// impl TaskModule for SampleTaskModule {}
 /// ```
-pub trait TaskModule: erased_serde::Serialize + DynClone + std::fmt::Debug + Send + Sync {}
+pub trait TaskModule: erased_serde::Serialize + DynClone + std::fmt::Debug + Send + Sync {
+    /// Check the arguments at synth. Generated modules check them against their [`ArgumentSpec`].
+    fn validate(&self) -> Result<(), ArgumentError> {
+        Ok(())
+    }
+}
 
 serialize_trait_object!(TaskModule);
 clone_trait_object!(TaskModule);
 
 /// [playbook keyword (task)](https://docs.ansible.com/ansible/latest/reference_appendices/playbooks_keywords.html#task)
-#[derive(Serialize, Default, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, OptionsSetters, PartialEq)]
 pub struct TaskOptions {
     /// The 'action' to execute for a task, it normally translates into a C(module) or action plugin.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
@@ -343,6 +478,10 @@ pub struct TaskOptions {
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub ignore_unreachable: OptU<BoolOrString>,
 
+    /// Topics of a handler. The handler runs when one of the topics is notified.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub listen: OptU<Vec<Handler>>,
+
     /// Same as action but also implies delegate_to: localhost
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub local_action: OptU<String>,
@@ -367,7 +506,7 @@ pub struct TaskOptions {
     pub no_log: OptU<BoolOrString>,
     /// List of handlers to notify when the task returns a 'changed=True' status.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub notify: OptU<Vec<String>>,
+    pub notify: OptU<Vec<Handler>>,
 
     /// Sets the polling interval in seconds for async tasks (default 10s).
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
@@ -379,7 +518,7 @@ pub struct TaskOptions {
 
     /// Name of variable that will contain task status and module return data.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
-    pub register: OptU<String>,
+    pub register: OptU<Register>,
 
     /// User used to log into the target via the connection plugin.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
@@ -419,15 +558,44 @@ pub struct TaskOptions {
     /// Conditional expression, determines if an iteration of a task is run or not.
     #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
     pub when: OptU<BoolOrStringOrVecString>,
-    // FIXME: not supported yet!
-    // with_<lookup_plugin>
-    // The same as loop but magically adds the output of any lookup plugin to generate the item list.
+    /// with_<lookup_plugin>
+    /// The same as loop but magically adds the output of any lookup plugin to generate the item list.
+    ///
+    /// This is read from the `with_*` key of a task by [`Task`]'s deserializer.
+    #[serde(flatten, skip_deserializing, skip_serializing_if = "OptU::is_unset")]
+    pub with_lookup: OptU<WithLookup>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_inventory_dump_json_rejects_tags() {
+        let mut inventory = Inventory {
+            name: "inv".to_string(),
+            root: InventoryRoot {
+                all: InventoryChild {
+                    vars: OptU::Some(IndexMap::from([(
+                        "user".to_string(),
+                        serde_json::json!("admin"),
+                    )])),
+                    ..Default::default()
+                },
+            },
+        };
+        assert_eq!(
+            inventory.dump_json().unwrap(),
+            r#"{"all":{"vars":{"user":"admin"}}}"#
+        );
+
+        if let OptU::Some(vars) = &mut inventory.root.all.vars {
+            vars.insert("password".to_string(), Secret::new("p@ssw0rd").into());
+        }
+        let err = inventory.dump_json().unwrap_err().to_string();
+        assert!(!err.contains("p@ssw0rd"));
+    }
+
     #[derive(Serialize, Clone, Debug, PartialEq)]
     struct SampleTaskModule {
         x1: String,
@@ -441,13 +609,16 @@ mod tests {
             serde_json::to_string(&Play {
                 name: "play1".to_string(),
                 hosts: vec!["host1".to_string()].into(),
-                tasks: vec![Task {
-                    name: "task1".to_string(),
-                    options: TaskOptions::default(),
-                    command: Box::new(SampleTaskModule {
-                        x1: "x1".to_string(),
-                    }),
-                }],
+                tasks: vec![
+                    Task {
+                        name: "task1".to_string(),
+                        options: TaskOptions::default(),
+                        command: Box::new(SampleTaskModule {
+                            x1: "x1".to_string(),
+                        }),
+                    }
+                    .into()
+                ],
                 options: PlayOptions::default(),
             })
             .expect("failed to serialize"),
@@ -461,13 +632,16 @@ mod tests {
             serde_json::to_string(&Play {
                 name: "play1".to_string(),
                 hosts: vec!["host1".to_string()].into(),
-                tasks: vec![Task {
-                    name: "task1".to_string(),
-                    options: TaskOptions::default(),
-                    command: Box::new(SampleTaskModule {
-                        x1: "x1".to_string(),
-                    }),
-                }],
+                tasks: vec![
+                    Task {
+                        name: "task1".to_string(),
+                        options: TaskOptions::default(),
+                        command: Box::new(SampleTaskModule {
+                            x1: "x1".to_string(),
+                        }),
+                    }
+                    .into()
+                ],
                 options: PlayOptions {
                     any_errors_fatal: OptU::Some(true.into()),
                     become_: OptU::Some(true.into()),
@@ -506,22 +680,28 @@ mod tests {
                     no_log: OptU::Some(true.into()),
                     order: OptU::Some("order".to_string()),
                     port: OptU::Some(10.into()),
-                    post_tasks: OptU::Some(vec![Task {
-                        name: "post_task1".to_string(),
-                        options: TaskOptions::default(),
-                        command: Box::new(SampleTaskModule {
-                            x1: "x1".to_string(),
-                        }),
-                    }]),
-                    pre_tasks: OptU::Some(vec![Task {
-                        name: "pre_task1".to_string(),
-                        options: TaskOptions::default(),
-                        command: Box::new(SampleTaskModule {
-                            x1: "x1".to_string(),
-                        }),
-                    }]),
+                    post_tasks: OptU::Some(vec![
+                        Task {
+                            name: "post_task1".to_string(),
+                            options: TaskOptions::default(),
+                            command: Box::new(SampleTaskModule {
+                                x1: "x1".to_string(),
+                            }),
+                        }
+                        .into()
+                    ]),
+                    pre_tasks: OptU::Some(vec![
+                        Task {
+                            name: "pre_task1".to_string(),
+                            options: TaskOptions::default(),
+                            command: Box::new(SampleTaskModule {
+                                x1: "x1".to_string(),
+                            }),
+                        }
+                        .into()
+                    ]),
                     remote_user: OptU::Some("remote_user".to_string()),
-                    roles: OptU::Some(vec!["role1".to_string()]),
+                    roles: OptU::Some(vec!["role1".into()]),
                     run_once: OptU::Some(true.into()),
                     serial: OptU::Some(10.into()),
                     strategy: OptU::Some("strategy".to_string()),
@@ -533,7 +713,7 @@ mod tests {
                         serde_json::Value::String("value1".to_string())
                     )])),
                     vars_files: OptU::Some(vec!["vars_file1".to_string()]),
-                    vars_prompt: OptU::Some(vec!["vars_prompt1".to_string()]),
+                    vars_prompt: OptU::Some(vec![VarsPrompt::new("vars_prompt1", "prompt1")]),
                 }
             })
             .expect("failed to serialize"),
@@ -578,7 +758,7 @@ mod tests {
                 + r#""timeout":10,"#
                 + r#""vars":{"var1":"value1"},"#
                 + r#""vars_files":["vars_file1"],"#
-                + r#""vars_prompt":["vars_prompt1"],"#
+                + r#""vars_prompt":[{"name":"vars_prompt1","prompt":"prompt1"}],"#
                 + r#""tasks":[{"name":"task1","x1":"x1"}]"#
                 + r#"}"#
         );
@@ -621,6 +801,7 @@ mod tests {
                 failed_when: OptU::Some("failed_when".to_string().into()),
                 ignore_errors: OptU::Some(true.into()),
                 ignore_unreachable: OptU::Some(true.into()),
+                listen: OptU::Some(vec!["listen1".into()]),
                 local_action: OptU::Some("local_action".to_string()),
                 loop_: OptU::Some(vec!["loop1".into()].into()),
                 loop_control: OptU::Some(IndexMap::from([(
@@ -632,10 +813,10 @@ mod tests {
                     serde_json::Value::String("value1".to_string())
                 )])),
                 no_log: OptU::Some(true.into()),
-                notify: OptU::Some(vec!["notify1".to_string()]),
+                notify: OptU::Some(vec!["notify1".into()]),
                 poll: OptU::Some(10.into()),
                 port: OptU::Some(10.into()),
-                register: OptU::Some("register".to_string()),
+                register: OptU::Some("register".into()),
                 remote_user: OptU::Some("remote_user".to_string()),
                 retries: OptU::Some(10.into()),
                 run_once: OptU::Some(true.into()),
@@ -648,6 +829,7 @@ mod tests {
                     serde_json::Value::String("value1".to_string())
                 )])),
                 when: OptU::Some("when".to_string().into()),
+                with_lookup: OptU::Some(WithLookup::items(vec!["item1"])),
             })
             .expect("failed to serialize"),
             String::new()
@@ -674,6 +856,7 @@ mod tests {
                 + r#""failed_when":"failed_when","#
                 + r#""ignore_errors":true,"#
                 + r#""ignore_unreachable":true,"#
+                + r#""listen":["listen1"],"#
                 + r#""local_action":"local_action","#
                 + r#""loop":["loop1"],"#
                 + r#""loop_control":{"loop_control1":"value1"},"#
@@ -691,7 +874,8 @@ mod tests {
                 + r#""timeout":10,"#
                 + r#""until":"until","#
                 + r#""vars":{"var1":"value1"},"#
-                + r#""when":"when""#
+                + r#""when":"when","#
+                + r#""with_items":["item1"]"#
                 + r#"}"#
         );
     }
@@ -806,4 +990,73 @@ mod tests {
             String::new() + r#"{"when":["1 == 1","2 == 2"]}"#
         );
     }
+
+    #[test]
+    fn test_deserialize_play() {
+        let src = String::new()
+            + "{"
+            + r#""name":"play1","#
+            + r#""hosts":"all","#
+            + r#""become":true,"#
+            + r#""roles":["role1"],"#
+            + r#""tasks":["#
+            + r#"{"name":"task1","ansible.builtin.debug":{"msg":"hi"},"when":"x is defined"},"#
+            + r#"{"name":"block1","block":[{"name":"task2","command":"echo 1"}],"rescue":[]}"#
+            + r#"]"#
+            + "}";
+        let play: Play = serde_json::from_str(&src).expect("failed to deserialize");
+        assert_eq!(play.options.become_, OptU::Some(true.into()));
+        assert_eq!(
+            play.tasks.first().map(|t| match t {
+                TaskOrBlock::Task(task) => task.options.when.clone(),
+                TaskOrBlock::Block(_) => OptU::Unset,
+            }),
+            Some(OptU::Some("x is defined".into()))
+        );
+        assert!(matches!(play.tasks.get(1), Some(TaskOrBlock::Block(_))));
+        // Round-trip, while the keys of the module are moved after the options
+        assert_eq!(
+            serde_json::to_string(&play).expect("failed to serialize"),
+            String::new()
+                + "{"
+                + r#""name":"play1","#
+                + r#""hosts":"all","#
+                + r#""become":true,"#
+                + r#""roles":["role1"],"#
+                + r#""tasks":["#
+                + r#"{"name":"task1","when":"x is defined","ansible.builtin.debug":{"msg":"hi"}},"#
+                + r#"{"name":"block1","block":[{"name":"task2","command":"echo 1"}],"rescue":[]}"#
+                + r#"]"#
+                + "}"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_task_without_module() {
+        assert!(serde_json::from_str::<Task>(r#"{"name":"task1","when":true}"#).is_err());
+        assert!(
+            serde_json::from_str::<Task>(r#"{"name":"task1","action":"ansible.builtin.ping"}"#)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_task_with_lookup() {
+        let json = r#"{"name":"task1","with_fileglob":["*.conf"],"ansible.builtin.debug":{}}"#;
+        let task: Task = serde_json::from_str(json).expect("failed to deserialize");
+        assert_eq!(
+            task.options.with_lookup,
+            OptU::Some(WithLookup::fileglob(vec!["*.conf"]))
+        );
+        assert_eq!(
+            serde_json::to_string(&task).expect("failed to serialize"),
+            r#"{"name":"task1","with_fileglob":["*.conf"],"ansible.builtin.debug":{}}"#
+        );
+        assert!(
+            serde_json::from_str::<Task>(
+                r#"{"name":"task1","with_items":[1],"with_dict":{},"ansible.builtin.debug":{}}"#
+            )
+            .is_err()
+        );
+    }
 }
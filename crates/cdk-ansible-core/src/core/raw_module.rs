@@ -0,0 +1,32 @@
+use crate::core::TaskModule;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// A module which keeps its name and arguments as [`serde_json::Value`]
+///
+/// This is used for tasks loaded from existing playbooks,
+/// and for modules which don't have a generated Rust type.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{RawModule, Task, TaskOptions};
+///
+/// let _task = Task {
+///     name: "ping".to_string(),
+///     options: TaskOptions::default(),
+///     command: Box::new(RawModule::new(
+///         "ansible.builtin.ping",
+///         serde_json::json!({ "data": "pong" }),
+///     )),
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct RawModule(pub IndexMap<String, serde_json::Value>);
+
+impl RawModule {
+    pub fn new(name: &str, args: serde_json::Value) -> Self {
+        Self(IndexMap::from([(name.to_owned(), args)]))
+    }
+}
+
+impl TaskModule for RawModule {}
@@ -0,0 +1,107 @@
+use crate::core::Expr;
+use serde::{Deserialize, Serialize};
+
+/// A handle for a variable set by [`crate::core::TaskOptions::register`]
+///
+/// The handle gives [`Expr`]s for the
+/// [common return values](https://docs.ansible.com/ansible/latest/reference_appendices/common_return_values.html).
+/// Synth warns if the variable is used before the task which registers it, or in another play.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{OptU, Register, TaskOptions};
+///
+/// let result = Register::new("result");
+/// let _register_opts = TaskOptions {
+///     register: OptU::Some(result.clone()),
+///     ..Default::default()
+/// };
+/// let _use_opts = TaskOptions {
+///     when: OptU::Some(result.rc().not_equals(0).into()),
+///     ..Default::default()
+/// };
+/// assert_eq!(result.stdout().template(), "{{ result.stdout }}");
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Register(String);
+
+impl Register {
+    pub fn new(name: &str) -> Self {
+        Self(name.to_owned())
+    }
+
+    /// Name of the variable
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The whole registered variable
+    pub fn expr(&self) -> Expr {
+        Expr::var(&self.0)
+    }
+
+    /// `<name>.<attr>`, for module specific return values
+    pub fn attr(&self, name: &str) -> Expr {
+        self.expr().attr(name)
+    }
+
+    /// Return code of a command
+    pub fn rc(&self) -> Expr {
+        self.attr("rc")
+    }
+
+    pub fn stdout(&self) -> Expr {
+        self.attr("stdout")
+    }
+
+    pub fn stdout_lines(&self) -> Expr {
+        self.attr("stdout_lines")
+    }
+
+    pub fn stderr(&self) -> Expr {
+        self.attr("stderr")
+    }
+
+    pub fn stderr_lines(&self) -> Expr {
+        self.attr("stderr_lines")
+    }
+
+    pub fn changed(&self) -> Expr {
+        self.attr("changed")
+    }
+
+    pub fn failed(&self) -> Expr {
+        self.attr("failed")
+    }
+
+    pub fn skipped(&self) -> Expr {
+        self.attr("skipped")
+    }
+
+    pub fn msg(&self) -> Expr {
+        self.attr("msg")
+    }
+
+    /// Results of each iteration of a loop
+    pub fn results(&self) -> Expr {
+        self.attr("results")
+    }
+}
+
+impl From<&str> for Register {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Register {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Register> for Expr {
+    fn from(value: Register) -> Self {
+        value.expr()
+    }
+}
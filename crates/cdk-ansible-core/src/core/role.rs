@@ -0,0 +1,167 @@
+use crate::core::{BoolOrStringOrVecString, OptU, Task, TaskOrBlock};
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// [Roles](https://docs.ansible.com/ansible/latest/playbook_guide/playbooks_reuse_roles.html)
+///
+/// A role is synthesized into the standard directory structure `roles/<name>/`.
+///
+/// | field          | path                          |
+/// |----------------|-------------------------------|
+/// | `tasks`        | `tasks/main.yaml`             |
+/// | `handlers`     | `handlers/main.yaml`          |
+/// | `defaults`     | `defaults/main.yaml`          |
+/// | `vars`         | `vars/main.yaml`              |
+/// | `files`        | `files/<path>`                |
+/// | `templates`    | `templates/<path>`            |
+/// | `dependencies` | `meta/main.yaml`              |
+///
+/// In a play or in `meta/main.yaml` of another role, a role is serialized as its name.
+///
+/// ```rust
+/// use cdk_ansible_core::core::{OptU, PlayOptions, Role};
+/// use indexmap::IndexMap;
+///
+/// let common = Role {
+///     name: "common".to_string(),
+///     defaults: IndexMap::from([("ntp_server".to_string(), "ntp.example.com".into())]),
+///     ..Default::default()
+/// };
+/// let web = Role {
+///     name: "web".to_string(),
+///     templates: IndexMap::from([(
+///         "index.html.j2".to_string(),
+///         "<p>{{ inventory_hostname }}</p>\n".to_string(),
+///     )]),
+///     dependencies: vec![common],
+///     ..Default::default()
+/// };
+///
+/// let _options = PlayOptions {
+///     roles: OptU::Some(vec![web.into()]),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct Role {
+    /// Name of the role. The role is synthesized into `roles/<name>/`.
+    pub name: String,
+    /// The main list of tasks that the role executes.
+    pub tasks: Vec<TaskOrBlock>,
+    /// Handlers, which may be used within or outside this role.
+    pub handlers: Vec<Task>,
+    /// Default variables for the role. These variables have the lowest priority of any variables available.
+    pub defaults: IndexMap<String, serde_json::Value>,
+    /// Other variables for the role.
+    pub vars: IndexMap<String, serde_json::Value>,
+    /// Files that the role deploys. The key is a relative path under `files/`.
+    pub files: IndexMap<String, String>,
+    /// Templates that the role deploys. The key is a relative path under `templates/`.
+    pub templates: IndexMap<String, String>,
+    /// Role dependencies, written to `meta/main.yaml`.
+    pub dependencies: Vec<Role>,
+}
+
+/// Refer to the role by its name
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+/// Only the name is restored in deserializing
+impl<'de> Deserialize<'de> for Role {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            name: String::deserialize(deserializer)?,
+            ..Default::default()
+        })
+    }
+}
+
+/// A role name, a [`Role`], or a [`RoleEntry`]
+///
+/// A name (or a [`RoleEntry`]) refers to a role which already exists in `roles_path`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum StringOrRole {
+    String(String),
+    Role(Role),
+    Entry(RoleEntry),
+}
+
+/// A role in `roles` written as a mapping, with keywords and role parameters
+///
+/// ```yaml
+/// roles:
+///   - role: common
+///     vars:
+///       ntp_server: ntp.example.com
+///     when: ansible_os_family == 'Debian'
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RoleEntry {
+    /// Name (or path) of the role. `name:` is also accepted in deserializing.
+    #[serde(alias = "name")]
+    pub role: String,
+    /// Variables for the role
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub vars: OptU<IndexMap<String, serde_json::Value>>,
+    /// Conditional expression, determines if the role is applied
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub when: OptU<BoolOrStringOrVecString>,
+    /// Other keywords (`tags`, `become`, ...) and role parameters
+    #[serde(flatten)]
+    pub params: IndexMap<String, serde_json::Value>,
+}
+
+impl From<String> for StringOrRole {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for StringOrRole {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<Role> for StringOrRole {
+    fn from(value: Role) -> Self {
+        Self::Role(value)
+    }
+}
+
+impl From<RoleEntry> for StringOrRole {
+    fn from(value: RoleEntry) -> Self {
+        Self::Entry(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_as_reference() {
+        let common = Role {
+            name: "common".to_string(),
+            defaults: IndexMap::from([("x".to_string(), 1.into())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&vec![
+                StringOrRole::from("existing"),
+                StringOrRole::from(Role {
+                    name: "web".to_string(),
+                    dependencies: vec![common],
+                    ..Default::default()
+                }),
+            ])
+            .expect("failed to serialize"),
+            r#"["existing","web"]"#
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Values written with a YAML tag by the YAML writer of cdk-ansible
+//!
+//! Vars are kept as [`serde_json::Value`], which has no tags.
+//! A tagged value is represented as a map with the single key `$cdk_ansible::tag::<tag>`,
+//! which the YAML writer turns into `!<tag>`.
+
+use serde::ser::SerializeMap as _;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Prefix of the key of a tagged value
+pub const TAG_KEY_PREFIX: &str = "$cdk_ansible::tag::";
+
+/// Key of a [`Secret`]
+pub const VAULT_TAG_KEY: &str = "$cdk_ansible::tag::vault";
+
+/// Key of an [`Unsafe`] value
+pub const UNSAFE_TAG_KEY: &str = "$cdk_ansible::tag::unsafe";
+
+/// A secret string, which synth encrypts with Ansible Vault and writes as `!vault`
+///
+/// The vault password is given to the synth (or deploy) command by `--vault-password-file`
+/// or the `CDK_ANSIBLE_VAULT_PASSWORD` environment variable. Synth fails without a password.
+///
+/// ```rust
+/// use cdk_ansible_core::core::Secret;
+///
+/// let password = Secret::new("p@ssw0rd");
+/// assert_eq!(format!("{password:?}"), "Secret(***)");
+/// let _var: serde_json::Value = password.into();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(plaintext: impl Into<String>) -> Self {
+        Self(plaintext.into())
+    }
+
+    /// The plaintext
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(VAULT_TAG_KEY, &self.0)?;
+        map.end()
+    }
+}
+
+impl From<Secret> for serde_json::Value {
+    fn from(value: Secret) -> Self {
+        serde_json::json!({ VAULT_TAG_KEY: value.0 })
+    }
+}
+
+/// A value which Ansible must not template, written with the `!unsafe` tag
+///
+/// Strings in a list or a dict are tagged one by one; other scalars are written as is.
+///
+/// It is a value like [`Secret`], so it can be set wherever a [`serde_json::Value`] is accepted:
+/// vars, inventory vars, [`super::RawModule`] arguments, and module options typed as [`serde_json::Value`].
+///
+/// ```rust
+/// use cdk_ansible_core::core::{OptU, Unsafe};
+///
+/// let _cmd: OptU<serde_json::Value> = OptU::Some(Unsafe("echo '{{ not a template }}'").into());
+/// let _var: serde_json::Value = Unsafe("{% raw %}").into();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unsafe<T>(pub T);
+
+impl<T: Serialize> Serialize for Unsafe<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(UNSAFE_TAG_KEY, &self.0)?;
+        map.end()
+    }
+}
+
+impl<T: Into<serde_json::Value>> From<Unsafe<T>> for serde_json::Value {
+    fn from(value: Unsafe<T>) -> Self {
+        serde_json::json!({ UNSAFE_TAG_KEY: value.0.into() })
+    }
+}
@@ -1,11 +1,11 @@
 //! combined types for Json Schema
 
 use indexmap::IndexMap;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// A boolean or a string
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum BoolOrString {
     Bool(bool),
@@ -31,7 +31,7 @@ impl From<&str> for BoolOrString {
 }
 
 /// i64 or string
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum IntOrString {
     Int(i64),
@@ -57,7 +57,7 @@ impl From<&str> for IntOrString {
 }
 
 /// A string or a vector of strings
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum StringOrVecString {
     String(String),
@@ -83,7 +83,7 @@ impl From<&str> for StringOrVecString {
 }
 
 /// A boolean or a string or a vector of strings
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum BoolOrStringOrVecString {
     Bool(bool),
@@ -116,7 +116,7 @@ impl From<Vec<String>> for BoolOrStringOrVecString {
 }
 
 /// String or Path
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum StringOrPath {
     String(String),
@@ -142,7 +142,7 @@ impl From<&str> for StringOrPath {
 }
 
 // String or Vec<Value>
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum StringOrVec {
     String(String),
@@ -168,7 +168,7 @@ impl From<&str> for StringOrVec {
 }
 
 // String or Map<String, Value>
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum StringOrMap {
     String(String),
@@ -192,3 +192,31 @@ impl From<&str> for StringOrMap {
         Self::String(value.to_owned())
     }
 }
+
+/// A value of `T` or a string, such as a template which Ansible resolves to `T`
+///
+/// Generated modules use it for typed lists (`StringOr<Vec<T>>`) and dicts with suboptions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum StringOr<T> {
+    String(String),
+    Value(T),
+}
+
+impl<T> From<Vec<T>> for StringOr<Vec<T>> {
+    fn from(value: Vec<T>) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl<T> From<String> for StringOr<T> {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T> From<&str> for StringOr<T> {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
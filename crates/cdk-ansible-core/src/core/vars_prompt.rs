@@ -0,0 +1,99 @@
+use crate::core::OptU;
+use serde::{Deserialize, Serialize};
+
+/// An entry of [`crate::core::PlayOptions::vars_prompt`]
+///
+/// See [Interactive input: prompts](https://docs.ansible.com/ansible/latest/playbook_guide/playbooks_prompts.html).
+/// A prompt is skipped if the variable is given as an extra var (e.g. by `deploy --prompt`).
+///
+/// ```rust
+/// use cdk_ansible_core::core::VarsPrompt;
+///
+/// let prompt = VarsPrompt::new("password", "Enter the password")
+///     .private(true)
+///     .confirm(true)
+///     .encrypt("sha512_crypt");
+/// assert_eq!(
+///     serde_json::to_string(&prompt).unwrap(),
+///     r#"{"name":"password","prompt":"Enter the password","private":true,"encrypt":"sha512_crypt","confirm":true}"#
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct VarsPrompt {
+    /// Name of the variable
+    pub name: String,
+    /// The message shown to the user
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub prompt: OptU<String>,
+    /// Hide the input. Ansible's default is `true`.
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub private: OptU<bool>,
+    /// Value used when the input is empty
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub default: OptU<String>,
+    /// Hash algorithm of the input (e.g. `sha512_crypt`)
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub encrypt: OptU<String>,
+    /// Ask the input twice
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub confirm: OptU<bool>,
+    /// Salt size for `encrypt`
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub salt_size: OptU<i64>,
+    /// Salt for `encrypt`
+    #[serde(default = "OptU::default", skip_serializing_if = "OptU::is_unset")]
+    pub salt: OptU<String>,
+    /// Allow the input to contain template characters (`{{`, `{%`)
+    #[serde(
+        rename = "unsafe",
+        default = "OptU::default",
+        skip_serializing_if = "OptU::is_unset"
+    )]
+    pub unsafe_: OptU<bool>,
+}
+
+impl VarsPrompt {
+    pub fn new(name: &str, prompt: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            prompt: OptU::Some(prompt.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    pub fn private(mut self, value: bool) -> Self {
+        self.private = OptU::Some(value);
+        self
+    }
+
+    /// Set [`VarsPrompt::default`]
+    pub fn default_value(mut self, value: &str) -> Self {
+        self.default = OptU::Some(value.to_owned());
+        self
+    }
+
+    pub fn encrypt(mut self, algorithm: &str) -> Self {
+        self.encrypt = OptU::Some(algorithm.to_owned());
+        self
+    }
+
+    pub fn confirm(mut self, value: bool) -> Self {
+        self.confirm = OptU::Some(value);
+        self
+    }
+
+    pub fn salt_size(mut self, value: i64) -> Self {
+        self.salt_size = OptU::Some(value);
+        self
+    }
+
+    pub fn salt(mut self, value: &str) -> Self {
+        self.salt = OptU::Some(value.to_owned());
+        self
+    }
+
+    pub fn unsafe_(mut self, value: bool) -> Self {
+        self.unsafe_ = OptU::Some(value);
+        self
+    }
+}
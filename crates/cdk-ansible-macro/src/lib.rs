@@ -3,12 +3,18 @@ use quote::quote;
 use syn::{Attribute, ImplItem, ItemImpl, ItemStruct, LitStr, parse_macro_input};
 
 mod inventory_derive;
+mod options_setters;
 
 #[proc_macro_derive(AllInventoryVarsGen)]
 pub fn all_inventory_vars_gen_derive(input: TokenStream) -> TokenStream {
     inventory_derive::vars_gen_derive(input)
 }
 
+#[proc_macro_derive(OptionsSetters)]
+pub fn options_setters_derive(input: TokenStream) -> TokenStream {
+    options_setters::options_setters_derive(input)
+}
+
 // MIT License
 // Copyright (c) 2021-2023 Astral Sh
 // https://github.com/astral-sh/uv/blob/cfd1e670ddb803f4e67d4abd069fad271e1d1c7f/crates/uv-macros/src/lib.rs
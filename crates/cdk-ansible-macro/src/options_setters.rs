@@ -0,0 +1,145 @@
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DataStruct, DeriveInput, Error, Fields, FieldsNamed, GenericArgument, PathArguments,
+    Type, TypePath, parse_macro_input,
+};
+
+/// This derive macro generates a `With<Struct>` trait, which has a setter for each `OptU<T>` field.
+///
+/// The trait is implemented for the struct itself, and is supposed to be implemented for the structs
+/// which own it (e.g. `Task` for `TaskOptions`) by providing `<struct>_mut`.
+///
+/// * `OptU<Vec<T>>` fields take `impl IntoIterator<Item = impl Into<T>>`
+/// * other `OptU<T>` fields take `impl Into<T>`
+pub fn options_setters_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = match input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(FieldsNamed { named, .. }),
+            ..
+        }) => named,
+        Data::Struct(_) | Data::Enum(_) | Data::Union(_) => {
+            return Error::new(input.ident.span(), "Only structs are supported")
+                .into_compile_error()
+                .into();
+        }
+    };
+
+    let trait_name = format_ident!("With{}", name);
+    let getter = format_ident!("{}_mut", to_snake_case(&name.to_string()));
+    let trait_doc =
+        format!("Setters of [`{name}`]\n\nImplemented for the structs which own [`{name}`].");
+    let getter_doc = format!("Mutable reference to the [`{name}`]");
+
+    let setters: Vec<_> = fields
+        .iter()
+        .filter_map(|field| {
+            let field_name = field.ident.as_ref()?;
+            let Type::Path(TypePath { path, .. }) = &field.ty else {
+                return None;
+            };
+            let last = path.segments.last()?;
+            if last.ident != "OptU" {
+                return None;
+            }
+            let inner = single_generic_arg(&last.arguments)?;
+            // `OptU::Some`, with the same path as the type
+            let mut some_path = path.clone();
+            if let Some(seg) = some_path.segments.last_mut() {
+                seg.arguments = PathArguments::None;
+            }
+            let docs: Vec<_> = field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("doc"))
+                .collect();
+
+            let setter = match vec_element(inner) {
+                Some(elem) => quote! {
+                    #(#docs)*
+                    fn #field_name<I>(mut self, value: I) -> Self
+                    where
+                        Self: Sized,
+                        I: IntoIterator,
+                        I::Item: Into<#elem>,
+                    {
+                        self.#getter().#field_name =
+                            #some_path::Some(value.into_iter().map(Into::into).collect());
+                        self
+                    }
+                },
+                None => quote! {
+                    #(#docs)*
+                    fn #field_name(mut self, value: impl Into<#inner>) -> Self
+                    where
+                        Self: Sized,
+                    {
+                        self.#getter().#field_name = #some_path::Some(value.into());
+                        self
+                    }
+                },
+            };
+            Some(setter)
+        })
+        .collect();
+
+    let token = quote! {
+        #[doc = #trait_doc]
+        pub trait #trait_name {
+            #[doc = #getter_doc]
+            fn #getter(&mut self) -> &mut #name;
+
+            #(#setters)*
+        }
+
+        #[automatically_derived]
+        impl #trait_name for #name {
+            fn #getter(&mut self) -> &mut #name {
+                self
+            }
+        }
+    };
+
+    token.into()
+}
+
+/// `T` of `X<T>`
+fn single_generic_arg(arguments: &PathArguments) -> Option<&Type> {
+    let PathArguments::AngleBracketed(args) = arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+        Some(_) | None => None,
+    }
+}
+
+/// `T` of `Vec<T>`
+fn vec_element(ty: &Type) -> Option<&Type> {
+    let Type::Path(TypePath { path, .. }) = ty else {
+        return None;
+    };
+    let last = path.segments.last()?;
+    if last.ident != "Vec" {
+        return None;
+    }
+    single_generic_arg(&last.arguments)
+}
+
+/// `TaskOptions` -> `task_options`
+fn to_snake_case(s: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
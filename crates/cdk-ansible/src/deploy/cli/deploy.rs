@@ -4,10 +4,15 @@ use crate::{
         cli::{GlobalConfig, synth::synth},
     },
     types::{ExePlaybook, StackName},
+    utils::{
+        extra_vars_args, parse_prompt_answer, prompt_extra_vars, roles_path_env,
+        vault_playbook_args,
+    },
 };
 use anyhow::{Context as _, Result};
 use clap::Args;
 use futures::future::{BoxFuture, FutureExt as _};
+use indexmap::IndexMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::process::Command;
@@ -38,6 +43,11 @@ pub struct Deploy {
     /// The maximum number of playbook processes.
     #[arg(short = 'P', long, required = false, default_value = "2")]
     pub max_procs: usize,
+    /// Answer a `vars_prompt` as `NAME=VALUE`. Can be repeated.
+    ///
+    /// Prompts which are not answered here are looked up in `CDK_ANSIBLE_PROMPT_<NAME>` environment variables.
+    #[arg(long = "prompt", value_name = "NAME=VALUE", value_parser = parse_prompt_answer)]
+    pub prompts: Vec<(String, String)>,
     // The stack name to deploy.
     #[arg(required = true)]
     pub stack_name: String,
@@ -45,7 +55,7 @@ pub struct Deploy {
 
 impl Deploy {
     pub async fn run(self, app: &App, global_config: Arc<GlobalConfig>) -> Result<()> {
-        let deploy_config = Arc::new(DeployConfig::new(self)?);
+        let deploy_config = Arc::new(DeployConfig::new(self, &global_config)?);
         synth(app, &global_config).await?;
 
         deploy(app, &global_config, &deploy_config).await?;
@@ -58,16 +68,23 @@ struct DeployConfig {
     playbook_command: Vec<String>,
     inventory: String,
     max_procs: usize,
+    prompt_answers: IndexMap<String, String>,
+    vault_args: Vec<String>,
     stack_name: StackName,
 }
 
 impl DeployConfig {
-    pub fn new(args: Deploy) -> Result<Self> {
+    pub fn new(args: Deploy, global_config: &GlobalConfig) -> Result<Self> {
         Ok(Self {
             playbook_command: ::shlex::split(&args.playbook_command)
                 .with_context(|| "parsing playbook command")?,
             inventory: args.inventory,
             max_procs: args.max_procs,
+            prompt_answers: args.prompts.into_iter().collect(),
+            vault_args: vault_playbook_args(
+                global_config.vault.as_deref(),
+                &global_config.vault_password_client,
+            )?,
             stack_name: StackName::from(args.stack_name.as_str()),
         })
     }
@@ -80,6 +97,7 @@ async fn deploy(
 ) -> Result<()> {
     let playbook_dir = Arc::new(global_config.playbook_dir.clone());
     let inventory_dir = Arc::new(global_config.inventory_dir.clone());
+    let roles_dir = Arc::new(global_config.roles_dir.clone());
 
     // Semaphore for limiting the number of concurrent ansible-playbook processes
     let pb_semaphore = Arc::new(Semaphore::new(deploy_config.max_procs));
@@ -92,6 +110,7 @@ async fn deploy(
         exe_playbook.clone(),
         Arc::clone(&playbook_dir),
         Arc::clone(&inventory_dir),
+        Arc::clone(&roles_dir),
         Arc::clone(deploy_config),
         Arc::clone(&pb_semaphore),
     )
@@ -103,6 +122,7 @@ fn recursive_deploy(
     exe_playbook: ExePlaybook,
     playbook_dir: Arc<PathBuf>,
     inventory_dir: Arc<PathBuf>,
+    roles_dir: Arc<PathBuf>,
     deploy_config: Arc<DeployConfig>,
     pb_semaphore: Arc<Semaphore>,
 ) -> BoxFuture<'static, Result<()>> {
@@ -133,8 +153,14 @@ fn recursive_deploy(
                     .acquire_owned()
                     .await
                     .with_context(|| "acquiring semaphore")?;
+                // Kept until ansible-playbook exits
+                let extra_vars_file =
+                    prompt_extra_vars(pb.iter_plays(), &deploy_config.prompt_answers)?;
+                let extra_vars = extra_vars_args(extra_vars_file.as_ref());
                 let output = Command::new(cmd)
                     .args(deploy_config.playbook_command.get(1..).unwrap_or_default())
+                    .args(extra_vars)
+                    .args(&deploy_config.vault_args)
                     .args([
                         "-i",
                         inventory_path
@@ -142,6 +168,7 @@ fn recursive_deploy(
                             .with_context(|| "stringifying path")?,
                         pb_path.to_str().with_context(|| "stringifying path")?,
                     ])
+                    .env("ANSIBLE_ROLES_PATH", roles_path_env(&roles_dir)?)
                     .output()
                     .await
                     .with_context(|| {
@@ -165,6 +192,7 @@ fn recursive_deploy(
                         pb,
                         Arc::clone(&playbook_dir),
                         Arc::clone(&inventory_dir),
+                        Arc::clone(&roles_dir),
                         Arc::clone(&deploy_config),
                         Arc::clone(&pb_semaphore),
                     )
@@ -178,6 +206,7 @@ fn recursive_deploy(
                         pb,
                         Arc::clone(&playbook_dir),
                         Arc::clone(&inventory_dir),
+                        Arc::clone(&roles_dir),
                         Arc::clone(&deploy_config),
                         Arc::clone(&pb_semaphore),
                     ));
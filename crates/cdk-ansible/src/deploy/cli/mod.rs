@@ -1,4 +1,5 @@
 use crate::deploy::App;
+use crate::utils::{LintConfig, SynthArgs, Vault};
 use anyhow::{Context as _, Result};
 use clap::{Args, Parser, Subcommand};
 use std::path::{PathBuf, absolute};
@@ -32,6 +33,8 @@ pub struct GlobalArgs {
     pub app_dir: PathBuf,
     #[arg(short, required = false)]
     pub uv_project: Option<PathBuf>,
+    #[command(flatten)]
+    pub synth_args: SynthArgs,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +42,14 @@ pub struct GlobalConfig {
     // pub app_dir: PathBuf,
     pub playbook_dir: PathBuf,
     pub inventory_dir: PathBuf,
+    /// Synthesized roles, passed to Ansible as `roles_path`
+    pub roles_dir: PathBuf,
+    /// Encrypts `Secret` values
+    pub vault: Option<Arc<Vault>>,
+    /// Script passing the vault password to `ansible-playbook`, written only by deploy
+    pub vault_password_client: PathBuf,
+    /// Severities of the lints run before writing playbooks
+    pub lint: LintConfig,
 }
 
 impl GlobalConfig {
@@ -46,10 +57,15 @@ impl GlobalConfig {
         let app_dir = absolute(&args.app_dir).with_context(|| "absolute path of app_dir")?;
         let playbook_dir = app_dir.join("playbooks");
         let inventory_dir = app_dir.join("inventory");
+        let roles_dir = app_dir.join("roles");
         Ok(Self {
             // app_dir,
             playbook_dir,
             inventory_dir,
+            roles_dir,
+            vault: args.synth_args.vault()?,
+            vault_password_client: app_dir.join("vault-password-client"),
+            lint: args.synth_args.lint_config(),
         })
     }
 }
@@ -1,9 +1,9 @@
 use crate::{
     App, ExePlaybook, Playbook,
     deploy::cli::GlobalConfig,
-    utils::{dump_json, json_to_yaml, playbook_dump},
+    utils::{RoleWriter, Vault, dump_yaml, playbook_dump, validate_plays_with},
 };
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use clap::Args;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -20,12 +20,16 @@ impl Synth {
 }
 
 pub async fn synth(app: &App, global_config: &Arc<GlobalConfig>) -> Result<()> {
-    let (inv_res, pb_res) = tokio::join!(
+    // Validate all playbooks before writing anything
+    validate_app(app, global_config)?;
+    let (inv_res, pb_res, role_res) = tokio::join!(
         synth_inventory(app, global_config),
         synth_playbooks(app, global_config),
+        synth_roles(app, global_config),
     );
     inv_res?;
     pb_res?;
+    role_res?;
     Ok(())
 }
 
@@ -40,13 +44,10 @@ pub async fn synth_inventory(app: &App, global_config: &GlobalConfig) -> Result<
     for (_, inventory) in app.inventories.iter() {
         let inventory_path = global_config
             .inventory_dir
-            .join(format!("{}.json", inventory.name));
+            .join(format!("{}.yaml", inventory.name));
         let inv_root = inventory.root.clone();
-        join_set.spawn(async move {
-            dump_json(inventory_path.clone(), inv_root).await?;
-            json_to_yaml(inventory_path).await?;
-            Ok(())
-        });
+        let vault = global_config.vault.clone();
+        join_set.spawn(async move { dump_yaml(inventory_path, inv_root, vault.as_deref()).await });
     }
     while let Some(res) = join_set.join_next().await {
         (res?)?;
@@ -73,7 +74,11 @@ pub async fn synth_playbooks(app: &App, global_config: &GlobalConfig) -> Result<
         })
         .for_each(|container| {
             container.into_iter().for_each(|pb| {
-                join_set.spawn(synth_playbook(pb, Arc::clone(&playbook_dir)));
+                join_set.spawn(synth_playbook(
+                    pb,
+                    Arc::clone(&playbook_dir),
+                    global_config.vault.clone(),
+                ));
             });
         });
     while let Some(res) = join_set.join_next().await {
@@ -82,10 +87,48 @@ pub async fn synth_playbooks(app: &App, global_config: &GlobalConfig) -> Result<
     Ok(())
 }
 
-async fn synth_playbook(pb: Playbook, playbook_dir: Arc<PathBuf>) -> Result<()> {
-    let json_path = playbook_dir.join(format!("{}.json", pb.name));
-    playbook_dump(pb, playbook_dir).await?;
-    json_to_yaml(json_path).await?;
+pub async fn synth_roles(app: &App, global_config: &GlobalConfig) -> Result<()> {
+    // Reset roles directory
+    if global_config.roles_dir.exists() {
+        tokio::fs::remove_dir_all(&global_config.roles_dir).await?;
+    }
+
+    let role_writer = RoleWriter::new(global_config.roles_dir.clone(), global_config.vault.clone());
+    for (_, exe_playbook) in app.exe_playbooks().iter() {
+        let mut container: Vec<Playbook> = Vec::new();
+        recursive_synth(&mut container, exe_playbook.clone());
+        for pb in container.iter() {
+            for play in pb.iter_plays() {
+                role_writer.dump_play_roles(play).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate the plays of each playbook, printing warnings
+fn validate_app(app: &App, global_config: &GlobalConfig) -> Result<()> {
+    for (_, exe_playbook) in app.exe_playbooks().iter() {
+        let mut container: Vec<Playbook> = Vec::new();
+        recursive_synth(&mut container, exe_playbook.clone());
+        for pb in container.iter() {
+            for warning in
+                validate_plays_with(&pb.iter_plays().collect::<Vec<_>>(), &global_config.lint)
+                    .with_context(|| format!("validating playbook {}", pb.name))?
+            {
+                eprintln!("warning: {warning}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn synth_playbook(
+    pb: Playbook,
+    playbook_dir: Arc<PathBuf>,
+    vault: Option<Arc<Vault>>,
+) -> Result<()> {
+    playbook_dump(pb, playbook_dir, vault.as_deref()).await?;
     Ok(())
 }
 
@@ -112,7 +155,7 @@ mod tests {
     fn create_playbook_helper(name: &str) -> Box<Playbook> {
         Box::new(Playbook {
             name: name.to_string(),
-            plays: vec![create_play_helper(name)],
+            plays: vec![create_play_helper(name).into()],
         })
     }
 
@@ -121,6 +164,20 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let playbook_dir = Arc::new(temp_dir.path().to_path_buf());
         let pb = create_playbook_helper("test");
-        synth_playbook(*pb, playbook_dir).await.unwrap();
+        synth_playbook(*pb, Arc::clone(&playbook_dir), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(playbook_dir.join("test.yaml"))
+                .await
+                .unwrap(),
+            String::new()
+                + "- name: test\n"
+                + "  hosts: localhost\n"
+                + "  tasks:\n"
+                + "    - name: debug\n"
+                + "      ansible.builtin.debug:\n"
+                + "        msg: Hello, world!\n"
+        );
     }
 }
@@ -4,12 +4,16 @@ use crate::{
         types::{ExePlayL2, LazyExePlayL2},
     },
     types::StackName,
-    utils::{dump_json, json_to_yaml},
+    utils::{
+        LintConfig, RoleWriter, Vault, dump_yaml, extra_vars_args, parse_prompt_answer,
+        prompt_extra_vars, roles_path_env, validate_plays_with, vault_playbook_args,
+    },
 };
 use anyhow::{Context as _, Result};
-use cdk_ansible_core::core::Play;
+use cdk_ansible_core::core::{InventoryRoot, Play};
 use clap::Args;
-use futures::future::{BoxFuture, FutureExt as _};
+use futures::future::{BoxFuture, FutureExt as _, try_join_all};
+use indexmap::IndexMap;
 use std::{path::PathBuf, process::ExitStatus, sync::Arc};
 use thiserror::Error;
 use tokio::{fs, process::Command, sync::Semaphore, task::JoinSet};
@@ -36,6 +40,11 @@ pub struct Deploy {
     /// Only synthesize playbooks and inventories.
     #[arg(long, exclusive = true, default_value = "false")]
     pub synth: bool,
+    /// Answer a `vars_prompt` as `NAME=VALUE`. Can be repeated.
+    ///
+    /// Prompts which are not answered here are looked up in `CDK_ANSIBLE_PROMPT_<NAME>` environment variables.
+    #[arg(long = "prompt", value_name = "NAME=VALUE", value_parser = parse_prompt_answer)]
+    pub prompts: Vec<(String, String)>,
     /// The stack name to deploy.
     /// If not specified, all stacks will be deployed.
     // TODO: support multiple stacks
@@ -44,7 +53,7 @@ pub struct Deploy {
 
 impl Deploy {
     pub async fn run(self, app: &AppL2, global_config: Arc<GlobalConfig>) -> Result<()> {
-        let deploy_config = Arc::new(DeployConfig::new(self)?);
+        let deploy_config = Arc::new(DeployConfig::new(self, &global_config)?);
         deploy(app, &global_config, &deploy_config).await?;
         Ok(())
     }
@@ -55,16 +64,31 @@ struct DeployConfig {
     playbook_command: Vec<String>,
     max_procs: usize,
     synth: bool,
+    prompt_answers: IndexMap<String, String>,
+    vault: Option<Arc<Vault>>,
+    vault_args: Vec<String>,
+    lint: LintConfig,
     stack_name: Option<StackName>,
 }
 
 impl DeployConfig {
-    pub fn new(args: Deploy) -> Result<Self> {
+    pub fn new(args: Deploy, global_config: &GlobalConfig) -> Result<Self> {
         Ok(Self {
             playbook_command: ::shlex::split(&args.playbook_command)
                 .with_context(|| "parsing playbook command")?,
             max_procs: args.max_procs,
             synth: args.synth,
+            prompt_answers: args.prompts.into_iter().collect(),
+            vault: global_config.vault.clone(),
+            vault_args: if args.synth {
+                Vec::new()
+            } else {
+                vault_playbook_args(
+                    global_config.vault.as_deref(),
+                    &global_config.vault_password_client,
+                )?
+            },
+            lint: global_config.lint.clone(),
             stack_name: args.stack_name.map(|s| StackName::from(s.as_str())),
         })
     }
@@ -75,6 +99,39 @@ async fn deploy(
     global_config: &Arc<GlobalConfig>,
     deploy_config: &Arc<DeployConfig>,
 ) -> Result<()> {
+    let stacks = deploy_config
+        .stack_name
+        .as_ref()
+        .map(|n| {
+            // Get a vec having the specified stack.
+            [app.inner
+                .stack_container
+                .get_stack(n)
+                .with_context(|| format!("getting stack: {n}"))]
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+        })
+        .unwrap_or_else(|| Ok(app.inner.stack_container.get_stacks().collect()))?;
+
+    // Resolve and validate all plays before anything is written or run
+    let mut exe_plays = Vec::new();
+    for stack in stacks {
+        let exe_play = resolve_lazy_exe_play(stack.exe_play().clone()).await?;
+        exe_plays.push((
+            stack.name().to_string().to_lowercase().replace(' ', "_"),
+            ResolvedExePlay::try_from(exe_play)?,
+        ));
+    }
+    for (_, exe_play) in exe_plays.iter() {
+        for play in exe_play.plays() {
+            for warning in validate_plays_with(&[&play.play], &deploy_config.lint)
+                .with_context(|| format!("validating play {}", play.play.name))?
+            {
+                eprintln!("warning: {warning}");
+            }
+        }
+    }
+
     let playbook_dir = Arc::new(global_config.playbook_dir.clone());
     let inventory_dir = Arc::new(global_config.inventory_dir.clone());
 
@@ -85,29 +142,21 @@ async fn deploy(
     if inventory_dir.exists() {
         fs::remove_dir_all(inventory_dir.as_ref().clone()).await?;
     }
+    if global_config.roles_dir.exists() {
+        fs::remove_dir_all(&global_config.roles_dir).await?;
+    }
+    let role_writer = RoleWriter::new(global_config.roles_dir.clone(), global_config.vault.clone());
 
     // Semaphore for limiting the number of concurrent ansible-playbook processes
     let cmd_semaphore = Arc::new(Semaphore::new(deploy_config.max_procs));
 
-    for stack in (deploy_config
-        .stack_name
-        .as_ref()
-        .map(|n| {
-            // Get a vec having the specified stack.
-            [app.inner
-                .stack_container
-                .get_stack(n)
-                .with_context(|| format!("getting stack: {n}"))]
-            .into_iter()
-            .collect::<Result<Vec<_>>>()
-        })
-        .unwrap_or_else(|| Ok(app.inner.stack_container.get_stacks().collect())))?
-    {
-        recursive_deploy(
-            stack.name().to_string().to_lowercase().replace(' ', "_"),
-            stack.exe_play().clone(),
+    for (name, exe_play) in exe_plays {
+        deploy_exe_play_l2(
+            name,
+            exe_play,
             Arc::clone(&playbook_dir),
             Arc::clone(&inventory_dir),
+            role_writer.clone(),
             Arc::clone(deploy_config),
             Arc::clone(&cmd_semaphore),
         )
@@ -117,102 +166,99 @@ async fn deploy(
     Ok(())
 }
 
-fn recursive_deploy(
-    name: String,
-    lazy_exe_play: LazyExePlayL2,
-    playbook_dir: Arc<PathBuf>,
-    inventory_dir: Arc<PathBuf>,
-    deploy_config: Arc<DeployConfig>,
-    cmd_semaphore: Arc<Semaphore>,
-) -> BoxFuture<'static, std::result::Result<(), DeployL2Error>> {
-    async move {
-        match lazy_exe_play {
-            LazyExePlayL2::Sequential(leps) => {
-                for (i, lep) in leps.into_iter().enumerate() {
-                    recursive_deploy(
-                        format!("{name}_s{i}"),
-                        lep,
-                        Arc::clone(&playbook_dir),
-                        Arc::clone(&inventory_dir),
-                        Arc::clone(&deploy_config),
-                        Arc::clone(&cmd_semaphore),
-                    )
-                    .await?;
-                }
-            }
-            LazyExePlayL2::Parallel(leps) => {
-                let mut set = JoinSet::new();
-                for (i, lep) in leps.into_iter().enumerate() {
-                    set.spawn(recursive_deploy(
-                        format!("{name}_p{i}"),
-                        lep,
-                        Arc::clone(&playbook_dir),
-                        Arc::clone(&inventory_dir),
-                        Arc::clone(&deploy_config),
-                        Arc::clone(&cmd_semaphore),
-                    ));
-                }
+/// A play of [`PlayL2`], with its inventory
+struct ResolvedPlay {
+    play: Play,
+    inventory: InventoryRoot,
+}
 
-                // Wait for all tasks to complete and then check the results.
-                let mut results = Vec::new();
-                while let Some(res) = set.join_next().await {
-                    results.push(res.map_err(|e| DeployL2Error::Other(e.into()))?);
-                }
-                // combine all error messages
-                let mut errors = Vec::new();
-                for res in results.into_iter() {
-                    if let Err(e) = res {
-                        errors.push(e);
-                    }
-                }
-                if !errors.is_empty() {
-                    return Err(DeployL2Error::Parallel {
-                        errors: errors.into_iter().map(|e| e.into()).collect(),
-                    });
-                }
+/// [`ExePlayL2`] whose plays are resolved, so that all of them are validated before any is deployed
+enum ResolvedExePlay {
+    Sequential(Vec<ResolvedExePlay>),
+    Parallel(Vec<ResolvedExePlay>),
+    Single(Box<ResolvedPlay>),
+}
+
+impl TryFrom<ExePlayL2> for ResolvedExePlay {
+    type Error = anyhow::Error;
+
+    fn try_from(exe_play: ExePlayL2) -> Result<Self> {
+        Ok(match exe_play {
+            ExePlayL2::Sequential(eps) => {
+                Self::Sequential(eps.into_iter().map(Self::try_from).collect::<Result<_>>()?)
             }
-            LazyExePlayL2::Single(lp) => {
-                let ep = lp.lazy_play_l2().await?;
-                deploy_exe_play_l2(
-                    name,
-                    ep,
-                    playbook_dir,
-                    inventory_dir,
-                    deploy_config,
-                    cmd_semaphore,
-                )
-                .await?;
+            ExePlayL2::Parallel(eps) => {
+                Self::Parallel(eps.into_iter().map(Self::try_from).collect::<Result<_>>()?)
             }
+            ExePlayL2::Single(play_l2) => {
+                let inventory = play_l2.hosts.to_inventory_root()?;
+                Self::Single(Box::new(ResolvedPlay {
+                    play: play_l2.try_play()?,
+                    inventory,
+                }))
+            }
+        })
+    }
+}
+
+impl ResolvedExePlay {
+    /// All plays, in order
+    fn plays(&self) -> Vec<&ResolvedPlay> {
+        match self {
+            Self::Sequential(eps) | Self::Parallel(eps) => {
+                eps.iter().flat_map(Self::plays).collect()
+            }
+            Self::Single(play) => vec![play],
         }
-        Ok(())
+    }
+}
+
+/// Generate the plays of [`LazyExePlayL2`]
+fn resolve_lazy_exe_play(lazy_exe_play: LazyExePlayL2) -> BoxFuture<'static, Result<ExePlayL2>> {
+    async move {
+        Ok(match lazy_exe_play {
+            LazyExePlayL2::Sequential(leps) => {
+                let mut eps = Vec::new();
+                for lep in leps {
+                    eps.push(resolve_lazy_exe_play(lep).await?);
+                }
+                ExePlayL2::Sequential(eps)
+            }
+            LazyExePlayL2::Parallel(leps) => ExePlayL2::Parallel(
+                try_join_all(leps.into_iter().map(resolve_lazy_exe_play)).await?,
+            ),
+            LazyExePlayL2::Single(lp) => lp.lazy_play_l2().await?,
+        })
     }
     .boxed()
 }
 
 fn deploy_exe_play_l2(
     name: String,
-    exe_play: ExePlayL2,
+    exe_play: ResolvedExePlay,
     playbook_dir: Arc<PathBuf>,
     inventory_dir: Arc<PathBuf>,
+    role_writer: RoleWriter,
     deploy_config: Arc<DeployConfig>,
     cmd_semaphore: Arc<Semaphore>,
 ) -> BoxFuture<'static, std::result::Result<(), DeployL2Error>> {
     async move {
         match exe_play {
-            ExePlayL2::Sequential(eps) => {
+            ResolvedExePlay::Sequential(eps) => {
                 for (i, ep) in eps.into_iter().enumerate() {
                     deploy_exe_play_l2(
                         format!("{name}_s{i}"),
                         ep,
                         Arc::clone(&playbook_dir),
                         Arc::clone(&inventory_dir),
+                        role_writer.clone(),
                         Arc::clone(&deploy_config),
                         Arc::clone(&cmd_semaphore),
                     )
                     .await?;
                 }
             }
-            ExePlayL2::Parallel(eps) => {
+            ResolvedExePlay::Parallel(eps) => {
                 let mut set = JoinSet::new();
                 for (i, ep) in eps.into_iter().enumerate() {
                     set.spawn(deploy_exe_play_l2(
@@ -220,6 +266,7 @@ fn deploy_exe_play_l2(
                         ep,
                         Arc::clone(&playbook_dir),
                         Arc::clone(&inventory_dir),
+                        role_writer.clone(),
                         Arc::clone(&deploy_config),
                         Arc::clone(&cmd_semaphore),
                     ));
@@ -246,46 +293,49 @@ fn deploy_exe_play_l2(
                     });
                 }
             }
-            ExePlayL2::Single(play_l2) => {
-                let inv_root = play_l2.hosts.to_inventory_root()?;
-                let play = play_l2.try_play()?;
+            ResolvedExePlay::Single(resolved) => {
+                let ResolvedPlay {
+                    play,
+                    inventory: inv_root,
+                } = *resolved;
+
+                // Create roles
+                role_writer.dump_play_roles(&play).await?;
 
                 // Create playbook
-                let pb_path_j = playbook_dir.join(&name).with_extension("json");
-                dump_json(
-                    pb_path_j.clone(),
+                let pb_path = playbook_dir.join(&name).with_extension("yaml");
+                dump_yaml(
+                    pb_path.clone(),
                     vec![Play {
                         name: format!("{} ({name})", play.name),
-                        ..play
+                        ..play.clone()
                     }],
+                    deploy_config.vault.as_deref(),
                 )
                 .await?;
-                json_to_yaml(pb_path_j.clone()).await?;
 
                 // Create inventory
-                let inv_path_j = inventory_dir.join(&name).with_extension("json");
-                dump_json(inv_path_j.clone(), inv_root).await?;
-                json_to_yaml(inv_path_j.clone()).await?;
+                let inv_path = inventory_dir.join(&name).with_extension("yaml");
+                dump_yaml(inv_path.clone(), inv_root, deploy_config.vault.as_deref()).await?;
 
                 if deploy_config.synth {
                     // Only synthesize playbooks and inventories.
                     return Ok(());
                 }
 
+                // Kept until ansible-playbook exits
+                let extra_vars_file = prompt_extra_vars([&play], &deploy_config.prompt_answers)?;
+                let extra_vars = extra_vars_args(extra_vars_file.as_ref());
                 let playbook_cmd_args = deploy_config
                     .playbook_command
                     .clone()
                     .into_iter()
+                    .chain(extra_vars)
+                    .chain(deploy_config.vault_args.clone())
                     .chain([
                         "-i".to_owned(),
-                        inv_path_j
-                            .with_extension("yaml")
-                            .to_string_lossy()
-                            .to_string(),
-                        pb_path_j
-                            .with_extension("yaml")
-                            .to_string_lossy()
-                            .to_string(),
+                        inv_path.to_string_lossy().to_string(),
+                        pb_path.to_string_lossy().to_string(),
                     ])
                     .collect::<Vec<_>>();
 
@@ -300,6 +350,10 @@ fn deploy_exe_play_l2(
                         .with_context(|| "getting 1st playbook command")?,
                 )
                 .args(playbook_cmd_args.get(1..).unwrap_or_default())
+                .env(
+                    "ANSIBLE_ROLES_PATH",
+                    roles_path_env(role_writer.roles_dir())?,
+                )
                 .output()
                 .await
                 .with_context(|| {
@@ -1,8 +1,9 @@
 pub(crate) mod trait_impl;
 use crate::HostInventoryVarsGenerator;
-use anyhow::Result;
+use anyhow::{Context as _, Result, bail};
 use cdk_ansible_core::core::{
-    InventoryChild, InventoryRoot, OptU, Play, PlayOptions, StringOrVecString, Task,
+    HostPattern, HostTerm, InventoryChild, InventoryRoot, OptU, Play, PlayOptions,
+    StringOrVecString, TaskOrBlock, WithPlayOptions,
 };
 use futures::future::BoxFuture;
 use std::fmt;
@@ -13,10 +14,35 @@ pub struct PlayL2 {
     pub name: String,
     pub hosts: HostsL2,
     pub options: PlayOptions,
-    pub tasks: Vec<Task>,
+    pub tasks: Vec<TaskOrBlock>,
 }
 
 impl PlayL2 {
+    pub fn new(name: impl Into<String>, hosts: HostsL2) -> Self {
+        Self {
+            name: name.into(),
+            hosts,
+            options: PlayOptions::default(),
+            tasks: vec![],
+        }
+    }
+
+    /// Append a task (or a block)
+    pub fn task(mut self, task: impl Into<TaskOrBlock>) -> Self {
+        self.tasks.push(task.into());
+        self
+    }
+
+    /// Append tasks (or blocks)
+    pub fn tasks<I>(mut self, tasks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TaskOrBlock>,
+    {
+        self.tasks.extend(tasks.into_iter().map(Into::into));
+        self
+    }
+
     pub fn try_play(self) -> Result<Play> {
         Ok(Play {
             name: self.name,
@@ -27,6 +53,12 @@ impl PlayL2 {
     }
 }
 
+impl WithPlayOptions for PlayL2 {
+    fn play_options_mut(&mut self) -> &mut PlayOptions {
+        &mut self.options
+    }
+}
+
 impl From<PlayL2> for ExePlayL2 {
     fn from(play_l2: PlayL2) -> Self {
         ExePlayL2::Single(Box::new(play_l2))
@@ -82,17 +114,67 @@ pub trait LazyPlayL2 {
     fn lazy_play_l2(&self) -> BoxFuture<'static, Result<ExePlayL2>>;
 }
 
+/// Hosts of a [`PlayL2`], which become the inventory of the play
+///
+/// By default, the play targets all the hosts.
+/// With [`HostsL2::pattern`], the play targets the hosts matching the pattern,
+/// and synth fails if the pattern matches no hosts of the inventory.
+/// The inventory of L2 has no groups other than `all` and `ungrouped`,
+/// so a pattern naming any other group is an error.
 #[derive(Clone)]
-pub struct HostsL2(Vec<Arc<dyn HostInventoryVarsGenerator + Send + Sync>>);
+pub struct HostsL2 {
+    hosts: Vec<Arc<dyn HostInventoryVarsGenerator + Send + Sync>>,
+    pattern: Option<HostPattern>,
+}
 
 impl HostsL2 {
     pub fn new(hosts: Vec<Arc<dyn HostInventoryVarsGenerator + Send + Sync>>) -> Self {
-        Self(hosts)
+        Self {
+            hosts,
+            pattern: None,
+        }
+    }
+
+    /// Target the hosts matching the pattern
+    pub fn pattern(mut self, pattern: impl Into<HostPattern>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
     }
 
     pub fn try_hosts(&self) -> Result<StringOrVecString> {
+        if let Some(pattern) = &self.pattern {
+            let inventory = self.to_inventory_root()?;
+            let names = inventory.all.hosts.as_option();
+            for term in pattern
+                .union
+                .iter()
+                .chain(pattern.intersect.iter())
+                .chain(pattern.exclude.iter())
+            {
+                let mut term = term;
+                while let HostTerm::Index(inner, _) | HostTerm::Slice(inner, _, _) = term {
+                    term = inner;
+                }
+                if let HostTerm::Name(name) = term
+                    && !name.contains(['*', '?'])
+                    && !["all", "ungrouped"].contains(&name.as_str())
+                    && !names.is_some_and(|names| names.contains_key(name))
+                {
+                    bail!(
+                        "groups are not supported in L2: '{name}' of host pattern '{pattern}' is not a host"
+                    );
+                }
+            }
+            let hosts = pattern
+                .resolve(&inventory)
+                .with_context(|| format!("resolving host pattern '{pattern}'"))?;
+            if hosts.is_empty() {
+                bail!("host pattern '{pattern}' matches no hosts");
+            }
+            return Ok(pattern.clone().into());
+        }
         Ok(self
-            .0
+            .hosts
             .iter()
             .map(|h| h.gen_host_vars())
             .collect::<Result<Vec<_>>>()?
@@ -106,7 +188,7 @@ impl HostsL2 {
         Ok(InventoryRoot {
             all: InventoryChild {
                 hosts: OptU::Some(
-                    self.0
+                    self.hosts
                         .iter()
                         .map(|h| h.gen_host_vars())
                         .collect::<Result<Vec<_>>>()?
@@ -383,3 +465,66 @@ mod test_exe_play_l2_push_play {
         }
     }
 }
+
+#[cfg(test)]
+mod test_hosts_l2_pattern {
+    use super::*;
+    use crate::utils::test::*;
+
+    #[test]
+    fn test_hosts_pattern() {
+        let hosts = play_l2_helper("sample").hosts;
+        assert_eq!(
+            hosts
+                .clone()
+                .pattern(HostPattern::all().exclude("host_b"))
+                .try_hosts()
+                .expect("failed to resolve"),
+            "all:!host_b".into()
+        );
+        assert!(
+            hosts
+                .clone()
+                .pattern(
+                    HostPattern::name("host_*")
+                        .exclude("host_a")
+                        .exclude("host_b")
+                )
+                .try_hosts()
+                .is_err()
+        );
+        assert_eq!(
+            hosts
+                .clone()
+                .pattern(HostPattern::index("ungrouped", -1))
+                .try_hosts()
+                .expect("failed to resolve"),
+            "ungrouped[-1]".into()
+        );
+        assert_eq!(
+            hosts.try_hosts().expect("failed to get hosts"),
+            vec!["host_a".to_string(), "host_b".to_string()].into()
+        );
+    }
+
+    #[test]
+    fn test_hosts_pattern_group() {
+        let hosts = play_l2_helper("sample").hosts;
+        for pattern in [
+            HostPattern::name("webservers"),
+            HostPattern::all().intersect("webservers"),
+            HostPattern::index("webservers", 0).into(),
+        ] {
+            let err = hosts
+                .clone()
+                .pattern(pattern)
+                .try_hosts()
+                .expect_err("groups should be rejected");
+            assert!(
+                err.to_string()
+                    .contains("groups are not supported in L2: 'webservers'"),
+                "{err}"
+            );
+        }
+    }
+}
@@ -105,6 +105,9 @@ pub use inventory::*;
 pub use l2::deploy::*;
 pub use l2::types::*;
 pub use types::*;
+pub use utils::{
+    Vault, YamlError, from_yaml_str, playbook_load, to_yaml_string, to_yaml_string_with,
+};
 
 // Re-export macros
 pub use cdk_ansible_macro::*;
@@ -1,2 +1,3 @@
 pub use crate::l2::types::trait_impl::*;
 pub use crate::types::trait_impl::*;
+pub use cdk_ansible_core::core::{WithBlockOptions, WithPlayOptions, WithTaskOptions};
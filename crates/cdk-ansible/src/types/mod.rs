@@ -252,7 +252,7 @@ impl ExePlaybook {
                     "{name}_{}",
                     play.name.as_str().to_lowercase().replace(' ', "_")
                 ),
-                plays: vec![*play],
+                plays: vec![(*play).into()],
             })),
         }
     }
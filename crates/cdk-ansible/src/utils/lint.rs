@@ -0,0 +1,537 @@
+//! Lints of keyword misuse, which Ansible reports only at runtime or silently ignores
+//!
+//! Each [`LintRule`] has a [`Severity`], which can be overridden by `--lint <rule>=<severity>`.
+
+use cdk_ansible_core::core::{
+    BoolOrString, OptU, Play, Role, StringOrRole, Task, TaskOptions, TaskOrBlock,
+};
+use indexmap::IndexMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A check of [`lint_plays`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// `retries` or `delay` without `until`
+    RetriesWithoutUntil,
+    /// `loop_control` without `loop` (or `with_*`)
+    LoopControlWithoutLoop,
+    /// `become_user` without `become` on the task or any of its parents
+    BecomeUserWithoutBecome,
+    /// `poll` without `async`
+    PollWithoutAsync,
+    /// `run_once` in a play with `serial`, which runs once per batch
+    RunOnceWithSerial,
+    /// Tasks (or handlers) with the same name in a play, except `meta` tasks
+    DuplicateTaskName,
+    /// A play without tasks, roles nor handlers
+    EmptyPlay,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 7] = [
+        LintRule::RetriesWithoutUntil,
+        LintRule::LoopControlWithoutLoop,
+        LintRule::BecomeUserWithoutBecome,
+        LintRule::PollWithoutAsync,
+        LintRule::RunOnceWithSerial,
+        LintRule::DuplicateTaskName,
+        LintRule::EmptyPlay,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintRule::RetriesWithoutUntil => "retries-without-until",
+            LintRule::LoopControlWithoutLoop => "loop-control-without-loop",
+            LintRule::BecomeUserWithoutBecome => "become-user-without-become",
+            LintRule::PollWithoutAsync => "poll-without-async",
+            LintRule::RunOnceWithSerial => "run-once-with-serial",
+            LintRule::DuplicateTaskName => "duplicate-task-name",
+            LintRule::EmptyPlay => "empty-play",
+        }
+    }
+
+    /// Severity unless configured
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            // Ansible ignores the keywords
+            LintRule::RetriesWithoutUntil
+            | LintRule::LoopControlWithoutLoop
+            | LintRule::PollWithoutAsync => Severity::Deny,
+            LintRule::BecomeUserWithoutBecome
+            | LintRule::RunOnceWithSerial
+            | LintRule::DuplicateTaskName
+            | LintRule::EmptyPlay => Severity::Warn,
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LintRule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LintRule::ALL
+            .into_iter()
+            .find(|rule| rule.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown lint '{s}', expected one of: {}",
+                    LintRule::ALL.map(|rule| rule.as_str()).join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Not reported
+    Allow,
+    /// Reported as a warning
+    Warn,
+    /// Fails synth
+    Deny,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Allow => "allow",
+            Severity::Warn => "warn",
+            Severity::Deny => "deny",
+        })
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Severity::Allow),
+            "warn" => Ok(Severity::Warn),
+            "deny" => Ok(Severity::Deny),
+            _ => Err(format!(
+                "unknown severity '{s}', expected one of: allow, warn, deny"
+            )),
+        }
+    }
+}
+
+/// Parse `--lint <rule>=<severity>`
+pub fn parse_lint_severity(s: &str) -> Result<(LintRule, Severity), String> {
+    let (rule, severity) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <RULE>=<SEVERITY>, got '{s}'"))?;
+    Ok((rule.parse()?, severity.parse()?))
+}
+
+/// Severities of the lints, overriding [`LintRule::default_severity`]
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    severities: IndexMap<LintRule, Severity>,
+}
+
+impl LintConfig {
+    pub fn severity(&self, rule: LintRule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+impl FromIterator<(LintRule, Severity)> for LintConfig {
+    fn from_iter<I: IntoIterator<Item = (LintRule, Severity)>>(iter: I) -> Self {
+        Self {
+            severities: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// A finding of [`lint_plays`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: LintRule,
+    pub severity: Severity,
+    /// `play 'web' > tasks[1] 'setup' > block[0] 'install'`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.path, self.message, self.rule)
+    }
+}
+
+/// Lint plays. Diagnostics of [`Severity::Allow`] are not returned.
+pub fn lint_plays(plays: &[&Play], config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        config,
+        diagnostics: Vec::new(),
+    };
+    for play in plays.iter() {
+        linter.play(play);
+    }
+    linter.diagnostics
+}
+
+/// Keywords inherited by the tasks
+#[derive(Clone, Copy, Default)]
+struct Inherited {
+    become_: bool,
+    serial: bool,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: LintRule, path: &str, message: String) {
+        let severity = self.config.severity(rule);
+        if severity != Severity::Allow {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                severity,
+                path: path.to_owned(),
+                message,
+            });
+        }
+    }
+
+    fn play(&mut self, play: &Play) {
+        let path = format!("play '{}'", play.name);
+        let options = &play.options;
+        let inherited = Inherited {
+            become_: is_set(&options.become_),
+            serial: !options.serial.is_unset(),
+        };
+
+        let sections = [
+            ("pre_tasks", items(&options.pre_tasks)),
+            ("tasks", play.tasks.as_slice()),
+            ("post_tasks", items(&options.post_tasks)),
+        ];
+        let roles = items(&options.roles);
+        if sections.iter().all(|(_, tasks)| tasks.is_empty())
+            && roles.is_empty()
+            && items(&options.handlers).is_empty()
+        {
+            self.report(
+                LintRule::EmptyPlay,
+                &path,
+                "the play has no tasks, roles nor handlers".to_owned(),
+            );
+        }
+        if inherited.serial && is_set(&options.run_once) {
+            self.report(
+                LintRule::RunOnceWithSerial,
+                &path,
+                "'run_once' runs once per batch of 'serial', not once per play".to_owned(),
+            );
+        }
+
+        let mut names: IndexMap<&str, String> = IndexMap::new();
+        for (section, tasks) in sections {
+            for (i, item) in tasks.iter().enumerate() {
+                self.item(
+                    item,
+                    &format!("{path} > {section}[{i}]"),
+                    inherited,
+                    &mut names,
+                );
+            }
+        }
+        let mut handler_names: IndexMap<&str, String> = IndexMap::new();
+        for (i, handler) in items(&options.handlers).iter().enumerate() {
+            let task_path = format!("{path} > handlers[{i}] '{}'", handler.name);
+            self.task(handler, &task_path, inherited);
+            self.duplicate(&handler.name, task_path, &mut handler_names);
+        }
+        for (i, role) in roles.iter().enumerate() {
+            if let StringOrRole::Role(role) = role {
+                self.role(role, &format!("{path} > roles[{i}]"), inherited);
+            }
+        }
+    }
+
+    fn role(&mut self, role: &Role, path: &str, inherited: Inherited) {
+        let path = format!("{path} '{}'", role.name);
+        let mut names = IndexMap::new();
+        for (i, item) in role.tasks.iter().enumerate() {
+            self.item(item, &format!("{path} > tasks[{i}]"), inherited, &mut names);
+        }
+        for (i, handler) in role.handlers.iter().enumerate() {
+            self.task(
+                handler,
+                &format!("{path} > handlers[{i}] '{}'", handler.name),
+                inherited,
+            );
+        }
+        for (i, dependency) in role.dependencies.iter().enumerate() {
+            self.role(
+                dependency,
+                &format!("{path} > dependencies[{i}]"),
+                inherited,
+            );
+        }
+    }
+
+    fn item<'a>(
+        &mut self,
+        item: &'a TaskOrBlock,
+        path: &str,
+        inherited: Inherited,
+        names: &mut IndexMap<&'a str, String>,
+    ) {
+        match item {
+            TaskOrBlock::Task(task) => {
+                let path = format!("{path} '{}'", task.name);
+                self.task(task, &path, inherited);
+                if !is_meta(task) {
+                    self.duplicate(&task.name, path, names);
+                }
+            }
+            TaskOrBlock::Block(block) => {
+                let path = format!("{path} '{}'", block.name);
+                let options = &block.options;
+                if !inherited.become_
+                    && !options.become_user.is_unset()
+                    && !is_set(&options.become_)
+                {
+                    self.become_user(&path);
+                }
+                if inherited.serial && is_set(&options.run_once) {
+                    self.run_once(&path);
+                }
+                let inherited = Inherited {
+                    become_: inherited.become_ || is_set(&options.become_),
+                    ..inherited
+                };
+                for (section, tasks) in [
+                    ("block", block.block.as_slice()),
+                    ("rescue", items(&block.rescue)),
+                    ("always", items(&block.always)),
+                ] {
+                    for (i, item) in tasks.iter().enumerate() {
+                        self.item(item, &format!("{path} > {section}[{i}]"), inherited, names);
+                    }
+                }
+            }
+        }
+    }
+
+    fn task(&mut self, task: &Task, path: &str, inherited: Inherited) {
+        let options: &TaskOptions = &task.options;
+        if options.until.is_unset() {
+            for (keyword, value) in [("retries", &options.retries), ("delay", &options.delay)] {
+                if !value.is_unset() {
+                    self.report(
+                        LintRule::RetriesWithoutUntil,
+                        path,
+                        format!("'{keyword}' has no effect without 'until'"),
+                    );
+                }
+            }
+        }
+        if !options.loop_control.is_unset()
+            && options.loop_.is_unset()
+            && options.with_lookup.is_unset()
+        {
+            self.report(
+                LintRule::LoopControlWithoutLoop,
+                path,
+                "'loop_control' has no effect without 'loop'".to_owned(),
+            );
+        }
+        if !inherited.become_ && !options.become_user.is_unset() && !is_set(&options.become_) {
+            self.become_user(path);
+        }
+        if !options.poll.is_unset() && options.async_.is_unset() {
+            self.report(
+                LintRule::PollWithoutAsync,
+                path,
+                "'poll' has no effect without 'async'".to_owned(),
+            );
+        }
+        if inherited.serial && is_set(&options.run_once) {
+            self.run_once(path);
+        }
+    }
+
+    fn become_user(&mut self, path: &str) {
+        self.report(
+            LintRule::BecomeUserWithoutBecome,
+            path,
+            "'become_user' has no effect unless 'become' is enabled".to_owned(),
+        );
+    }
+
+    fn run_once(&mut self, path: &str) {
+        self.report(
+            LintRule::RunOnceWithSerial,
+            path,
+            "'run_once' runs once per batch of 'serial', not once per play".to_owned(),
+        );
+    }
+
+    fn duplicate<'a>(
+        &mut self,
+        name: &'a str,
+        path: String,
+        names: &mut IndexMap<&'a str, String>,
+    ) {
+        // Unnamed tasks of loaded playbooks
+        if name.is_empty() {
+            return;
+        }
+        if let Some(first) = names.get(name) {
+            let message = format!("the name '{name}' is also used by {first}");
+            self.report(LintRule::DuplicateTaskName, &path, message);
+        } else {
+            names.insert(name, path);
+        }
+    }
+}
+
+/// Whether the keyword is set to anything but `false`. Templates may be true.
+fn is_set(value: &OptU<BoolOrString>) -> bool {
+    !matches!(value.as_option(), None | Some(BoolOrString::Bool(false)))
+}
+
+fn items<T: serde::Serialize>(list: &OptU<Vec<T>>) -> &[T] {
+    list.as_option().map_or(&[], Vec::as_slice)
+}
+
+/// Whether the task runs `meta`, such as [`cdk_ansible_core::core::Meta::task`], which may be repeated
+fn is_meta(task: &Task) -> bool {
+    match serde_json::to_value(&task.command) {
+        Ok(serde_json::Value::Object(map)) => map.keys().any(|key| {
+            matches!(
+                key.as_str(),
+                "meta" | "ansible.builtin.meta" | "ansible.legacy.meta"
+            )
+        }),
+        Ok(_) | Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::*;
+    use crate::utils::validate_plays_with;
+    use cdk_ansible_core::core::{
+        Block, Meta, WithBlockOptions as _, WithPlayOptions as _, WithTaskOptions as _,
+    };
+
+    fn debug(name: &str) -> Task {
+        Task::new(
+            name,
+            debug::Module {
+                module: debug::Args {
+                    options: debug::Opt::default(),
+                },
+            },
+        )
+    }
+
+    fn lint(play: &Play, config: &LintConfig) -> Vec<String> {
+        lint_plays(&[play], config)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_task_keywords() {
+        let play = Play::new("p", "all").tasks([
+            debug("retry").retries(3),
+            debug("loop").loop_control(IndexMap::new()),
+            debug("become").become_user("root"),
+            debug("poll").poll(5),
+            debug("ok").retries(3).until("result is success"),
+        ]);
+        assert_eq!(
+            lint(&play, &LintConfig::default()),
+            vec![
+                "play 'p' > tasks[0] 'retry': 'retries' has no effect without 'until' [retries-without-until]",
+                "play 'p' > tasks[1] 'loop': 'loop_control' has no effect without 'loop' [loop-control-without-loop]",
+                "play 'p' > tasks[2] 'become': 'become_user' has no effect unless 'become' is enabled [become-user-without-become]",
+                "play 'p' > tasks[3] 'poll': 'poll' has no effect without 'async' [poll-without-async]",
+            ]
+        );
+        assert!(validate_plays_with(&[&play], &LintConfig::default()).is_err());
+
+        let config: LintConfig = [
+            (LintRule::RetriesWithoutUntil, Severity::Allow),
+            (LintRule::LoopControlWithoutLoop, Severity::Warn),
+            (LintRule::PollWithoutAsync, Severity::Warn),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(lint(&play, &config).len(), 3);
+        assert_eq!(
+            validate_plays_with(&[&play], &config)
+                .expect("no lint should be denied")
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_inherited_keywords() {
+        let play = Play::new("p", "all")
+            .serial(2)
+            .task(Block::new("b", [debug("t").become_user("root").run_once(true)]).become_(true));
+        assert_eq!(
+            lint(&play, &LintConfig::default()),
+            vec![
+                "play 'p' > tasks[0] 'b' > block[0] 't': 'run_once' runs once per batch of 'serial', not once per play [run-once-with-serial]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_play_level() {
+        assert_eq!(
+            lint(&Play::new("p", "all"), &LintConfig::default()),
+            vec!["play 'p': the play has no tasks, roles nor handlers [empty-play]"]
+        );
+
+        let play = Play::new("p", "all")
+            .pre_tasks([debug("dup")])
+            .task(Block::new("b", [debug("dup")]));
+        assert_eq!(
+            lint(&play, &LintConfig::default()),
+            vec![
+                "play 'p' > tasks[0] 'b' > block[0] 'dup': the name 'dup' is also used by play 'p' > pre_tasks[0] 'dup' [duplicate-task-name]",
+            ]
+        );
+
+        let play = Play::new("p", "all").tasks([
+            debug("t"),
+            Meta::FlushHandlers.task(),
+            debug("u"),
+            Meta::FlushHandlers.task(),
+        ]);
+        assert!(lint(&play, &LintConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_lint_severity() {
+        assert_eq!(
+            parse_lint_severity("empty-play=deny"),
+            Ok((LintRule::EmptyPlay, Severity::Deny))
+        );
+        assert!(parse_lint_severity("empty-play").is_err());
+        assert!(parse_lint_severity("no-such-lint=deny").is_err());
+        assert!(parse_lint_severity("empty-play=error").is_err());
+    }
+}
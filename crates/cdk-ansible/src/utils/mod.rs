@@ -1,73 +1,75 @@
 use anyhow::{Context as _, Result};
 use cdk_ansible_core::core::Playbook;
 use serde::Serialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::process::Command;
+
+mod lint;
+mod prompt;
+mod role;
+mod synth_args;
+mod validate;
+mod vault;
+mod yaml;
+pub use lint::*;
+pub use prompt::*;
+pub use role::*;
+pub use synth_args::*;
+pub use validate::*;
+pub use vault::*;
+pub use yaml::*;
 
 #[cfg(test)]
 pub mod test;
 
-pub async fn dump_json(filepath: PathBuf, obj: impl Serialize) -> Result<()> {
+/// Serialize `obj` to YAML and write it to `filepath`.
+///
+/// Secret values are encrypted with `vault`.
+pub async fn dump_yaml(
+    filepath: PathBuf,
+    obj: impl Serialize,
+    vault: Option<&Vault>,
+) -> Result<()> {
     fs::create_dir_all(
         filepath
             .parent()
             .with_context(|| format!("getting parent directory of {}", filepath.display()))?,
     )
     .await?;
-    fs::write(&filepath, serde_json::to_string_pretty(&obj)?)
-        .await
-        .with_context(|| format!("writing to {}", filepath.display()))?;
-    Ok(())
-}
-
-/// @deprecated
-/// Use [`dump_json`] instead.
-pub async fn playbook_dump(playbook: Playbook, dirpath: Arc<PathBuf>) -> Result<()> {
-    let filepath = dirpath.join(format!("{}.json", playbook.name));
-    fs::create_dir_all(
-        filepath
-            .parent()
-            .with_context(|| format!("getting parent directory of {}", filepath.display()))?,
+    fs::write(
+        &filepath,
+        to_yaml_string_with(&obj, vault)
+            .with_context(|| format!("serializing {}", filepath.display()))?,
     )
     .await
-    .with_context(|| format!("creating directory {}", filepath.display()))?;
-
-    fs::write(&filepath, serde_json::to_string_pretty(&playbook.plays)?)
-        .await
-        .with_context(|| format!("writing to {}", filepath.display()))?;
+    .with_context(|| format!("writing to {}", filepath.display()))?;
     Ok(())
 }
 
-/// ```sh
-/// yq_script='yq -p json -o yaml "${1}" > "${1%.json}.yaml"'
-/// ```
-pub async fn json_to_yaml(json_path: PathBuf) -> Result<()> {
-    let yaml_path = json_path.with_extension("yaml");
-    let output = Command::new("yq")
-        .arg("-p")
-        .arg("json")
-        .arg("-o")
-        .arg("yaml")
-        .arg(json_path.clone())
-        .output()
-        .await
-        .with_context(|| {
-            format!(
-                "running 'yq' to convert {} to {}",
-                json_path.display(),
-                yaml_path.display()
-            )
-        })?;
-    if !output.status.success() {
-        anyhow::bail!(
-            "command failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
-    fs::write(&yaml_path, output.stdout)
+/// Write `<dirpath>/<playbook.name>.yaml`
+pub async fn playbook_dump(
+    playbook: Playbook,
+    dirpath: Arc<PathBuf>,
+    vault: Option<&Vault>,
+) -> Result<()> {
+    let filepath = dirpath.join(format!("{}.yaml", playbook.name));
+    dump_yaml(filepath, &playbook.plays, vault).await
+}
+
+/// Read a playbook file. The playbook name is the file stem.
+///
+/// Tasks of modules are loaded as [`cdk_ansible_core::core::RawModule`].
+pub async fn playbook_load(filepath: &Path) -> Result<Playbook> {
+    let name = filepath
+        .file_stem()
+        .with_context(|| format!("getting file stem of {}", filepath.display()))?
+        .to_string_lossy()
+        .to_string();
+    let content = fs::read_to_string(filepath)
         .await
-        .with_context(|| format!("writing to {}", yaml_path.display()))?;
-    Ok(())
+        .with_context(|| format!("reading {}", filepath.display()))?;
+    let plays =
+        from_yaml_str(&content).with_context(|| format!("parsing {}", filepath.display()))?;
+    Ok(Playbook { name, plays })
 }
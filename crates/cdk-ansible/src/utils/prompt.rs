@@ -0,0 +1,175 @@
+//! Answers for `vars_prompt`, to run playbooks non-interactively
+
+use anyhow::{Context as _, Result, bail};
+use cdk_ansible_core::core::Play;
+use indexmap::IndexMap;
+use std::io::Write as _;
+use tempfile::NamedTempFile;
+
+/// Prefix of the environment variables which answer prompts: `CDK_ANSIBLE_PROMPT_<NAME>`
+///
+/// `<NAME>` is the variable name in upper case.
+pub const PROMPT_ENV_PREFIX: &str = "CDK_ANSIBLE_PROMPT_";
+
+/// Parse `NAME=VALUE` of `--prompt`
+pub fn parse_prompt_answer(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        Some(_) | None => Err(format!("expected NAME=VALUE, got '{s}'")),
+    }
+}
+
+/// Extra vars answering the `vars_prompt` of the plays, written to a file for `-e @<file>`
+///
+/// An answer is taken from `answers` (`--prompt`), then from the environment variable [`PROMPT_ENV_PREFIX`]`<NAME>`.
+/// Ansible skips a prompt whose variable is given as an extra var.
+/// Answers may be secrets (`private` prompts), so they are not put on the command line,
+/// where `ps` and error messages would show them. The file is only readable by the owner,
+/// and is removed when dropped, so keep it until `ansible-playbook` exits.
+/// Returns `None` if no prompt is answered.
+pub fn prompt_extra_vars<'a>(
+    plays: impl IntoIterator<Item = &'a Play>,
+    answers: &IndexMap<String, String>,
+) -> Result<Option<NamedTempFile>> {
+    let Some(extra_vars) = prompt_extra_vars_with(plays, answers, |key| std::env::var(key).ok())?
+    else {
+        return Ok(None);
+    };
+    // Created with mode 0600 on Unix
+    let mut file = tempfile::Builder::new()
+        .prefix("cdk-ansible-prompt-")
+        .suffix(".json")
+        .tempfile()
+        .with_context(|| "creating extra vars file")?;
+    file.write_all(serde_json::Value::Object(extra_vars).to_string().as_bytes())
+        .with_context(|| "writing extra vars file")?;
+    file.flush()?;
+    Ok(Some(file))
+}
+
+/// `-e @<file>` arguments of [`prompt_extra_vars`]
+pub fn extra_vars_args(file: Option<&NamedTempFile>) -> Vec<String> {
+    file.map(|file| vec!["-e".to_owned(), format!("@{}", file.path().display())])
+        .unwrap_or_default()
+}
+
+fn prompt_extra_vars_with<'a>(
+    plays: impl IntoIterator<Item = &'a Play>,
+    answers: &IndexMap<String, String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let mut extra_vars = serde_json::Map::new();
+    for play in plays {
+        let Some(prompts) = play.options.vars_prompt.as_option() else {
+            continue;
+        };
+        for prompt in prompts.iter() {
+            let env_name = format!("{PROMPT_ENV_PREFIX}{}", prompt.name.to_ascii_uppercase());
+            let answer = answers
+                .get(&prompt.name)
+                .cloned()
+                .or_else(|| env(&env_name));
+            match answer {
+                // The prompt is skipped, so the answer would be used as is instead of its hash
+                Some(_) if prompt.encrypt.as_option().is_some() => bail!(
+                    "play '{}': prompt '{}' uses encrypt, which Ansible does not apply to an answer given by --prompt or {env_name}; \
+                     answer it interactively, or pass the hash instead and remove encrypt",
+                    play.name,
+                    prompt.name
+                ),
+                Some(answer) => {
+                    extra_vars.insert(prompt.name.clone(), answer.into());
+                }
+                None if prompt.default.is_unset() => {
+                    eprintln!(
+                        "warning: play '{}': prompt '{}' is not answered by --prompt nor {env_name}",
+                        play.name, prompt.name,
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+    Ok((!extra_vars.is_empty()).then_some(extra_vars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::create_play_helper;
+    use cdk_ansible_core::core::{OptU, VarsPrompt};
+
+    #[test]
+    fn test_prompt_extra_vars() {
+        let mut play = create_play_helper("play1");
+        play.options.vars_prompt = OptU::Some(vec![
+            VarsPrompt::new("user", "User"),
+            VarsPrompt::new("password", "Password").private(true),
+            VarsPrompt::new("port", "Port").default_value("22"),
+        ]);
+        let answers = IndexMap::from([("user".to_string(), "admin".to_string())]);
+        let extra_vars = prompt_extra_vars_with([&play], &answers, |key| {
+            (key == "CDK_ANSIBLE_PROMPT_PASSWORD").then(|| "secret".to_string())
+        })
+        .unwrap();
+        assert_eq!(
+            extra_vars.map(|vars| serde_json::Value::Object(vars).to_string()),
+            Some(r#"{"user":"admin","password":"secret"}"#.to_string())
+        );
+        assert_eq!(
+            prompt_extra_vars_with([&play], &IndexMap::new(), |_| None).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prompt_extra_vars_file() {
+        let mut play = create_play_helper("play1");
+        play.options.vars_prompt = OptU::Some(vec![VarsPrompt::new("password", "Password")]);
+        let answers = IndexMap::from([("password".to_string(), "secret".to_string())]);
+        let file = prompt_extra_vars(&[play], &answers).unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(file.path()).unwrap(),
+            r#"{"password":"secret"}"#
+        );
+        let args = extra_vars_args(Some(&file));
+        assert_eq!(args[1], format!("@{}", file.path().display()));
+        assert!(!args.concat().contains("secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = file.as_file().metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_prompt_encrypt() {
+        let mut play = create_play_helper("play1");
+        play.options.vars_prompt = OptU::Some(vec![
+            VarsPrompt::new("password", "Password").encrypt("sha512_crypt"),
+        ]);
+        let answers = IndexMap::from([("password".to_string(), "secret".to_string())]);
+        assert!(
+            prompt_extra_vars_with([&play], &answers, |_| None)
+                .unwrap_err()
+                .to_string()
+                .contains("prompt 'password' uses encrypt")
+        );
+        // Prompted interactively
+        assert_eq!(
+            prompt_extra_vars_with([&play], &IndexMap::new(), |_| None).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_prompt_answer() {
+        assert_eq!(
+            parse_prompt_answer("a=b=c"),
+            Ok(("a".to_string(), "b=c".to_string()))
+        );
+        assert!(parse_prompt_answer("a").is_err());
+        assert!(parse_prompt_answer("=b").is_err());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::utils::{Vault, to_yaml_string_with};
+use anyhow::{Context as _, Result, bail};
+use cdk_ansible_core::core::{Play, Role, StringOrRole, Task, TaskOrBlock};
+use indexmap::IndexMap;
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+use std::env;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::{fs, sync::Mutex};
+
+/// Files of a synthesized role. The paths are relative to `roles/<name>/`.
+type RoleFiles = Vec<(PathBuf, String)>;
+
+/// SHA-256 of the unencrypted contents of a role, see [`role_digest`]
+type RoleDigest = [u8; 32];
+
+/// Writes [`Role`]s into `roles/<name>/`
+///
+/// The same role may be referenced from several plays (and several roles), which are synthesized concurrently.
+/// Each role is written only once, and a role whose contents differ from an already written role of the same name is an error.
+/// The contents are compared before `Secret` values are encrypted, as the encryption differs each time.
+#[derive(Debug, Clone)]
+pub struct RoleWriter {
+    roles_dir: Arc<PathBuf>,
+    /// Encrypts `Secret` values
+    vault: Option<Arc<Vault>>,
+    written: Arc<Mutex<IndexMap<String, RoleDigest>>>,
+}
+
+impl RoleWriter {
+    pub fn new(roles_dir: PathBuf, vault: Option<Arc<Vault>>) -> Self {
+        Self {
+            roles_dir: Arc::new(roles_dir),
+            vault,
+            written: Arc::new(Mutex::new(IndexMap::new())),
+        }
+    }
+
+    /// The directory to be passed to Ansible as `roles_path`
+    pub fn roles_dir(&self) -> &Path {
+        &self.roles_dir
+    }
+
+    /// Write all roles referenced by the play, including their dependencies
+    pub async fn dump_play_roles(&self, play: &Play) -> Result<()> {
+        if let Some(roles) = play.options.roles.as_option() {
+            for role in roles {
+                if let StringOrRole::Role(role) = role {
+                    self.dump(role).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the role and its dependencies
+    pub async fn dump(&self, role: &Role) -> Result<()> {
+        let mut stack = vec![role];
+        while let Some(role) = stack.pop() {
+            stack.extend(role.dependencies.iter());
+
+            let digest =
+                role_digest(role).with_context(|| format!("synthesizing role {}", role.name))?;
+            let mut written = self.written.lock().await;
+            if let Some(existing) = written.get(&role.name) {
+                if *existing != digest {
+                    bail!(
+                        "role {} is defined more than once with different contents",
+                        role.name
+                    );
+                }
+                continue;
+            }
+            let files = role_files(role, self.vault.as_deref())
+                .with_context(|| format!("synthesizing role {}", role.name))?;
+            let role_dir = self.roles_dir.join(&role.name);
+            for (path, content) in files.iter() {
+                let filepath = role_dir.join(path);
+                fs::create_dir_all(filepath.parent().with_context(|| {
+                    format!("getting parent directory of {}", filepath.display())
+                })?)
+                .await?;
+                fs::write(&filepath, content)
+                    .await
+                    .with_context(|| format!("writing to {}", filepath.display()))?;
+            }
+            written.insert(role.name.clone(), digest);
+        }
+        Ok(())
+    }
+}
+
+/// Digest of the contents of the role, with `Secret` values in plaintext
+///
+/// The written files can't be compared, as each encryption uses a random salt.
+fn role_digest(role: &Role) -> Result<RoleDigest> {
+    #[derive(Serialize)]
+    struct Contents<'a> {
+        tasks: &'a [TaskOrBlock],
+        handlers: &'a [Task],
+        defaults: &'a IndexMap<String, serde_json::Value>,
+        vars: &'a IndexMap<String, serde_json::Value>,
+        files: &'a IndexMap<String, String>,
+        templates: &'a IndexMap<String, String>,
+        /// Serialized as the names
+        dependencies: &'a [Role],
+    }
+
+    let contents = serde_json::to_vec(&Contents {
+        tasks: &role.tasks,
+        handlers: &role.handlers,
+        defaults: &role.defaults,
+        vars: &role.vars,
+        files: &role.files,
+        templates: &role.templates,
+        dependencies: &role.dependencies,
+    })?;
+    Ok(Sha256::digest(contents).into())
+}
+
+/// Lay out the role as `(relative path, content)` pairs
+fn role_files(role: &Role, vault: Option<&Vault>) -> Result<RoleFiles> {
+    if !is_plain_relative(Path::new(&role.name)) || role.name.contains(['/', '\\']) {
+        bail!("invalid role name: {:?}", role.name);
+    }
+
+    #[derive(Serialize)]
+    struct Meta<'a> {
+        dependencies: &'a [Role],
+    }
+
+    let mut files: RoleFiles = vec![(
+        PathBuf::from("tasks/main.yaml"),
+        to_yaml_string_with(&role.tasks, vault)?,
+    )];
+    if !role.handlers.is_empty() {
+        files.push((
+            PathBuf::from("handlers/main.yaml"),
+            to_yaml_string_with(&role.handlers, vault)?,
+        ));
+    }
+    if !role.defaults.is_empty() {
+        files.push((
+            PathBuf::from("defaults/main.yaml"),
+            to_yaml_string_with(&role.defaults, vault)?,
+        ));
+    }
+    if !role.vars.is_empty() {
+        files.push((
+            PathBuf::from("vars/main.yaml"),
+            to_yaml_string_with(&role.vars, vault)?,
+        ));
+    }
+    files.push((
+        PathBuf::from("meta/main.yaml"),
+        to_yaml_string_with(
+            &Meta {
+                dependencies: &role.dependencies,
+            },
+            vault,
+        )?,
+    ));
+    for (dir, entries) in [("files", &role.files), ("templates", &role.templates)] {
+        for (path, content) in entries.iter() {
+            if !is_plain_relative(Path::new(path)) {
+                bail!("{dir} path must be relative and must not contain '..': {path:?}");
+            }
+            files.push((Path::new(dir).join(path), content.clone()));
+        }
+    }
+    Ok(files)
+}
+
+/// A non-empty relative path without `.`, `..` or a root
+fn is_plain_relative(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// `ANSIBLE_ROLES_PATH` with `roles_dir` in front of the user's roles path
+///
+/// The user's roles path is `ANSIBLE_ROLES_PATH`, or Ansible's default if it is not set.
+pub fn roles_path_env(roles_dir: &Path) -> Result<OsString> {
+    prepend_roles_path(
+        roles_dir,
+        env::var_os("ANSIBLE_ROLES_PATH"),
+        env::var_os("ANSIBLE_HOME"),
+    )
+}
+
+fn prepend_roles_path(
+    roles_dir: &Path,
+    roles_path: Option<OsString>,
+    ansible_home: Option<OsString>,
+) -> Result<OsString> {
+    let existing = match roles_path {
+        Some(roles_path) => env::split_paths(&roles_path).collect::<Vec<_>>(),
+        None => vec![
+            ansible_home
+                .map_or_else(|| PathBuf::from("~/.ansible"), PathBuf::from)
+                .join("roles"),
+            PathBuf::from("/usr/share/ansible/roles"),
+            PathBuf::from("/etc/ansible/roles"),
+        ],
+    };
+    env::join_paths(std::iter::once(roles_dir.to_path_buf()).chain(existing))
+        .with_context(|| format!("joining {} to ANSIBLE_ROLES_PATH", roles_dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::*;
+    use cdk_ansible_core::core::{OptU, TaskOptions};
+    use tempfile::TempDir;
+
+    fn debug_task(msg: &str) -> Task {
+        Task {
+            name: "debug".into(),
+            options: TaskOptions::default(),
+            command: Box::new(debug::Module {
+                module: debug::Args {
+                    options: debug::Opt {
+                        msg: OptU::Some(msg.into()),
+                        ..Default::default()
+                    },
+                },
+            }),
+        }
+    }
+
+    fn web_role() -> Role {
+        Role {
+            name: "web".to_string(),
+            tasks: vec![debug_task("web").into()],
+            handlers: vec![debug_task("restart")],
+            defaults: IndexMap::from([("port".to_string(), 8080.into())]),
+            templates: IndexMap::from([(
+                "conf/web.conf.j2".to_string(),
+                "port={{ port }}\n".to_string(),
+            )]),
+            dependencies: vec![Role {
+                name: "common".to_string(),
+                tasks: vec![debug_task("common").into()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dump_role() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = RoleWriter::new(temp_dir.path().to_path_buf(), None);
+        writer.dump(&web_role()).await.unwrap();
+
+        let read = |path: &str| std::fs::read_to_string(temp_dir.path().join(path)).unwrap();
+        assert_eq!(
+            read("web/tasks/main.yaml"),
+            String::new() + "- name: debug\n" + "  ansible.builtin.debug:\n" + "    msg: web\n"
+        );
+        assert_eq!(
+            read("web/handlers/main.yaml"),
+            String::new() + "- name: debug\n" + "  ansible.builtin.debug:\n" + "    msg: restart\n"
+        );
+        assert_eq!(read("web/defaults/main.yaml"), "port: 8080\n");
+        assert_eq!(read("web/meta/main.yaml"), "dependencies:\n  - common\n");
+        assert_eq!(read("web/templates/conf/web.conf.j2"), "port={{ port }}\n");
+        assert!(!temp_dir.path().join("web/vars").exists());
+        assert_eq!(read("common/meta/main.yaml"), "dependencies: []\n");
+        assert_eq!(
+            read("common/tasks/main.yaml"),
+            String::new() + "- name: debug\n" + "  ansible.builtin.debug:\n" + "    msg: common\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dump_role_secret() {
+        let mut role = web_role();
+        role.vars = IndexMap::from([(
+            "password".to_string(),
+            cdk_ansible_core::core::Secret::new("p@ssw0rd").into(),
+        )]);
+
+        let temp_dir = TempDir::new().unwrap();
+        assert!(
+            RoleWriter::new(temp_dir.path().to_path_buf(), None)
+                .dump(&role)
+                .await
+                .is_err()
+        );
+
+        let vault = Arc::new(Vault::new("password"));
+        let writer = RoleWriter::new(temp_dir.path().to_path_buf(), Some(Arc::clone(&vault)));
+        writer.dump(&role).await.unwrap();
+        let vars = std::fs::read_to_string(temp_dir.path().join("web/vars/main.yaml")).unwrap();
+        let vaulttext = vars
+            .strip_prefix("password: !vault |\n")
+            .unwrap()
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(vault.decrypt(&vaulttext).unwrap(), b"p@ssw0rd");
+
+        // The same role again, although its encryption differs each time
+        writer.dump(&role).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("web/vars/main.yaml")).unwrap(),
+            vars
+        );
+        role.vars = IndexMap::from([(
+            "password".to_string(),
+            cdk_ansible_core::core::Secret::new("other").into(),
+        )]);
+        assert!(writer.dump(&role).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dump_shared_role_secret() {
+        let common = Role {
+            name: "common".to_string(),
+            defaults: IndexMap::from([(
+                "token".to_string(),
+                cdk_ansible_core::core::Secret::new("t0ken").into(),
+            )]),
+            ..Default::default()
+        };
+        let depends = |name: &str| Role {
+            name: name.to_string(),
+            dependencies: vec![common.clone()],
+            ..Default::default()
+        };
+        let mut play = create_play_helper("play");
+        play.options.roles = OptU::Some(vec![depends("a").into(), depends("b").into()]);
+
+        let temp_dir = TempDir::new().unwrap();
+        let writer = RoleWriter::new(
+            temp_dir.path().to_path_buf(),
+            Some(Arc::new(Vault::new("password"))),
+        );
+        writer.dump_play_roles(&play).await.unwrap();
+        // Another play using the same role
+        writer.dump(&common).await.unwrap();
+        assert!(temp_dir.path().join("common/defaults/main.yaml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dump_role_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = RoleWriter::new(temp_dir.path().to_path_buf(), None);
+        writer.dump(&web_role()).await.unwrap();
+        // The same contents are skipped
+        writer.dump(&web_role()).await.unwrap();
+
+        let mut other = web_role();
+        other.tasks = vec![];
+        assert!(writer.dump(&other).await.is_err());
+    }
+
+    #[test]
+    fn test_role_files_rejects_escaping_paths() {
+        let mut role = web_role();
+        role.files = IndexMap::from([("../x".to_string(), String::new())]);
+        assert!(role_files(&role, None).is_err());
+        assert!(
+            role_files(
+                &Role {
+                    name: "../web".to_string(),
+                    ..Default::default()
+                },
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_prepend_roles_path() {
+        let roles_dir = Path::new("/app/roles");
+        assert_eq!(
+            prepend_roles_path(roles_dir, Some("/a:/b".into()), None).unwrap(),
+            "/app/roles:/a:/b"
+        );
+        assert_eq!(
+            prepend_roles_path(roles_dir, None, None).unwrap(),
+            "/app/roles:~/.ansible/roles:/usr/share/ansible/roles:/etc/ansible/roles"
+        );
+        assert_eq!(
+            prepend_roles_path(roles_dir, None, Some("/home".into())).unwrap(),
+            "/app/roles:/home/roles:/usr/share/ansible/roles:/etc/ansible/roles"
+        );
+    }
+}
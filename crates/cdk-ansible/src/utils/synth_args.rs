@@ -0,0 +1,38 @@
+use super::{LintConfig, LintRule, Severity, Vault, parse_lint_severity};
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Arguments on how plays are synthesized, shared by the CLIs of `App` and `AppL2`
+#[derive(Args, Debug, Clone)]
+pub struct SynthArgs {
+    /// A file containing the vault password, used to encrypt `Secret` values.
+    ///
+    /// If not given, the password is read from the `CDK_ANSIBLE_VAULT_PASSWORD` environment variable.
+    #[arg(long, required = false, env = "CDK_ANSIBLE_VAULT_PASSWORD_FILE")]
+    pub vault_password_file: Option<PathBuf>,
+    /// Vault ID of the encrypted values (`$ANSIBLE_VAULT;1.2;AES256;<vault_id>`)
+    #[arg(long, required = false)]
+    pub vault_id: Option<String>,
+    /// Override the severity of a lint as `RULE=SEVERITY` (`allow`, `warn` or `deny`). Can be repeated.
+    ///
+    /// Example: `--lint duplicate-task-name=deny --lint empty-play=allow`
+    #[arg(long = "lint", value_name = "RULE=SEVERITY", value_parser = parse_lint_severity)]
+    pub lints: Vec<(LintRule, Severity)>,
+}
+
+impl SynthArgs {
+    /// The vault encrypting `Secret` values, if a password is given
+    pub fn vault(&self) -> Result<Option<Arc<Vault>>> {
+        Ok(Vault::from_file_or_env(
+            self.vault_password_file.as_deref(),
+            self.vault_id.as_deref(),
+        )?
+        .map(Arc::new))
+    }
+
+    pub fn lint_config(&self) -> LintConfig {
+        self.lints.iter().copied().collect()
+    }
+}
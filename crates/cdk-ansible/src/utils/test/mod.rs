@@ -13,18 +13,21 @@ pub fn create_play_helper(name: &str) -> Play {
         name: name.to_string(),
         hosts: "localhost".into(),
         options: PlayOptions::default(),
-        tasks: vec![Task {
-            name: "debug".into(),
-            options: TaskOptions::default(),
-            command: Box::new(debug::Module {
-                module: debug::Args {
-                    options: debug::Opt {
-                        msg: OptU::Some("Hello, world!".into()),
-                        ..Default::default()
+        tasks: vec![
+            Task {
+                name: "debug".into(),
+                options: TaskOptions::default(),
+                command: Box::new(debug::Module {
+                    module: debug::Args {
+                        options: debug::Opt {
+                            msg: OptU::Some("Hello, world!".into()),
+                            ..Default::default()
+                        },
                     },
-                },
-            }),
-        }],
+                }),
+            }
+            .into(),
+        ],
     }
 }
 
@@ -65,18 +68,21 @@ pub fn play_l2_helper(name: &str) -> PlayL2 {
         name: name.to_string(),
         hosts,
         options: PlayOptions::default(),
-        tasks: vec![Task {
-            name: "debug".into(),
-            options: TaskOptions::default(),
-            command: Box::new(debug::Module {
-                module: debug::Args {
-                    options: debug::Opt {
-                        msg: OptU::Some("Hello, world!".into()),
-                        ..Default::default()
+        tasks: vec![
+            Task {
+                name: "debug".into(),
+                options: TaskOptions::default(),
+                command: Box::new(debug::Module {
+                    module: debug::Args {
+                        options: debug::Opt {
+                            msg: OptU::Some("Hello, world!".into()),
+                            ..Default::default()
+                        },
                     },
-                },
-            }),
-        }],
+                }),
+            }
+            .into(),
+        ],
     }
 }
 
@@ -0,0 +1,822 @@
+//! Checks on plays run at synth
+
+use super::{LintConfig, Severity, lint_plays};
+use anyhow::{Result, bail};
+use cdk_ansible_core::core::{Block, Handler, OptU, Play, Role, StringOrRole, Task, TaskOrBlock};
+use indexmap::IndexSet;
+use serde_json::Value;
+use std::borrow::Borrow;
+
+/// Validate plays before writing them, and return warnings
+///
+/// Lints of [`Severity::Deny`] are errors, and those of [`Severity::Warn`] are returned as warnings.
+pub fn validate_plays_with<P: Borrow<Play>>(
+    plays: &[P],
+    lint_config: &LintConfig,
+) -> Result<Vec<String>> {
+    let plays: Vec<&Play> = plays.iter().map(Borrow::borrow).collect();
+    let plays = plays.as_slice();
+    let mut warnings = Vec::new();
+    check_registers(plays, &mut warnings)?;
+    for play in plays.iter() {
+        check_handlers(play, &mut warnings)?;
+        check_control_tasks(play)?;
+        check_modules(play)?;
+    }
+
+    let mut denied = Vec::new();
+    for diagnostic in lint_plays(plays, lint_config) {
+        match diagnostic.severity {
+            Severity::Deny => denied.push(diagnostic.to_string()),
+            Severity::Warn => warnings.push(diagnostic.to_string()),
+            Severity::Allow => {}
+        }
+    }
+    if !denied.is_empty() {
+        bail!("lint errors:\n{}", denied.join("\n"));
+    }
+    Ok(warnings)
+}
+
+/// Keywords evaluated after the task has run, so that they can refer to the task's own `register`
+const POST_EVAL_KEYWORDS: [&str; 3] = ["changed_when", "failed_when", "until"];
+/// Keywords whose values are bare Jinja2 expressions
+const EXPR_KEYWORDS: [&str; 4] = ["when", "changed_when", "failed_when", "until"];
+
+/// A task or block keywords, in the order of execution
+struct Step {
+    /// `task '<name>'` or `block '<name>'`
+    label: String,
+    register: Option<String>,
+    /// Referenced variables, and whether the reference is evaluated after the task has run
+    refs: Vec<(String, bool)>,
+}
+
+/// Warn about registered variables used before the task which registers them, or in another play
+///
+/// Variables are found by their names in Jinja2 expressions, which may also be those of other variables
+/// (e.g. play `vars` or facts), so they are not errors.
+fn check_registers(plays: &[&Play], warnings: &mut Vec<String>) -> Result<()> {
+    let play_steps = plays
+        .iter()
+        .copied()
+        .map(play_steps)
+        .collect::<Result<Vec<_>>>()?;
+    let all_registers = play_steps
+        .iter()
+        .flatten()
+        .filter_map(|step| step.register.clone())
+        .collect::<IndexSet<_>>();
+
+    for (play, steps) in plays.iter().zip(play_steps.iter()) {
+        let play_registers = steps
+            .iter()
+            .filter_map(|step| step.register.as_deref())
+            .collect::<IndexSet<_>>();
+        let mut registered: IndexSet<&str> = IndexSet::new();
+        for step in steps.iter() {
+            for (name, post_eval) in step.refs.iter() {
+                if !all_registers.contains(name) || registered.contains(name.as_str()) {
+                    continue;
+                }
+                if *post_eval && step.register.as_deref() == Some(name.as_str()) {
+                    continue;
+                }
+                if play_registers.contains(name.as_str()) {
+                    warnings.push(format!(
+                        "play '{}': {} uses '{name}' before the task which registers it",
+                        play.name, step.label
+                    ));
+                } else {
+                    warnings.push(format!(
+                        "play '{}': {} uses '{name}', which is registered in another play",
+                        play.name, step.label
+                    ));
+                }
+            }
+            if let Some(register) = step.register.as_deref() {
+                registered.insert(register);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that each notified handler (or `listen` topic) exists in the play, and warn about handlers nothing notifies
+///
+/// Handlers of a role may also be notified as `<role> : <handler>`.
+/// Roles referenced only by name are not visible, so the play is not checked if it has such a role.
+/// If a task imports or includes a role, its handlers are not visible either,
+/// so an unknown handler is only a warning.
+fn check_handlers(play: &Play, warnings: &mut Vec<String>) -> Result<()> {
+    let mut tasks: Vec<&TaskOrBlock> = [
+        items(&play.options.pre_tasks),
+        &play.tasks,
+        items(&play.options.post_tasks),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    // (role, handler)
+    let mut handlers: Vec<(Option<&str>, &Task)> = items(&play.options.handlers)
+        .iter()
+        .map(|handler| (None, handler))
+        .collect();
+
+    let mut roles: Vec<&Role> = Vec::new();
+    for role in items(&play.options.roles).iter() {
+        match role {
+            StringOrRole::Role(role) => roles.push(role),
+            StringOrRole::String(_) | StringOrRole::Entry(_) => return Ok(()),
+        }
+    }
+    while let Some(role) = roles.pop() {
+        tasks.extend(role.tasks.iter());
+        handlers.extend(
+            role.handlers
+                .iter()
+                .map(|handler| (Some(role.name.as_str()), handler)),
+        );
+        roles.extend(role.dependencies.iter());
+    }
+
+    // (label, handler)
+    let mut notified: Vec<(String, &Handler)> = Vec::new();
+    for item in tasks {
+        collect_notifies(item, &mut notified);
+    }
+    for (_, handler) in handlers.iter() {
+        for notify in items(&handler.options.notify).iter() {
+            notified.push((format!("handler '{}'", handler.name), notify));
+        }
+    }
+
+    // Names by which each handler can be notified
+    let handler_names = |role: Option<&str>, handler: &Task| -> Vec<String> {
+        let names = std::iter::once(handler.name.as_str())
+            .chain(items(&handler.options.listen).iter().map(Handler::name));
+        names
+            .flat_map(|name| {
+                std::iter::once(name.to_string()).chain(role.map(|role| format!("{role} : {name}")))
+            })
+            .collect()
+    };
+    let defined = handlers
+        .iter()
+        .flat_map(|(role, handler)| handler_names(*role, handler))
+        .collect::<IndexSet<_>>();
+    let dynamic_roles = has_dynamic_roles(play)?;
+    for (label, notify) in notified.iter() {
+        if defined.contains(notify.name()) {
+            continue;
+        }
+        if dynamic_roles {
+            warnings.push(format!(
+                "play '{}': {label} notifies '{}', which is not a handler or a listen topic in the play (it may be in a role imported or included by a task)",
+                play.name,
+                notify.name()
+            ));
+            continue;
+        }
+        bail!(
+            "play '{}': {label} notifies '{}', which is neither a handler nor a listen topic in the play",
+            play.name,
+            notify.name()
+        );
+    }
+
+    let notified = notified
+        .iter()
+        .map(|(_, notify)| notify.name())
+        .collect::<IndexSet<_>>();
+    for (role, handler) in handlers.iter() {
+        if !handler_names(*role, handler)
+            .iter()
+            .any(|name| notified.contains(name.as_str()))
+        {
+            warnings.push(format!(
+                "play '{}': handler '{}' is not notified by any task",
+                play.name, handler.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a task of the play runs `import_role` or `include_role`
+fn has_dynamic_roles(play: &Play) -> Result<bool> {
+    for task in play_tasks(play) {
+        let Value::Object(map) = serde_json::to_value(task)? else {
+            continue;
+        };
+        if map
+            .keys()
+            .any(|key| matches!(action_name(key), "import_role" | "include_role"))
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Module name without the `ansible.builtin.` (or `ansible.legacy.`) prefix
+fn action_name(key: &str) -> &str {
+    key.strip_prefix("ansible.builtin.")
+        .or_else(|| key.strip_prefix("ansible.legacy."))
+        .unwrap_or(key)
+}
+
+/// Keywords for loops, which `import_*` don't support
+const LOOP_KEYWORDS: [&str; 2] = ["loop", "loop_control"];
+
+/// All tasks and handlers of the play, including those in blocks and roles
+fn play_tasks(play: &Play) -> Vec<&Task> {
+    let mut pending: Vec<&TaskOrBlock> = [
+        items(&play.options.pre_tasks),
+        &play.tasks,
+        items(&play.options.post_tasks),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let mut tasks: Vec<&Task> = items(&play.options.handlers).iter().collect();
+    let mut roles: Vec<&Role> = items(&play.options.roles)
+        .iter()
+        .filter_map(|role| match role {
+            StringOrRole::Role(role) => Some(role),
+            StringOrRole::String(_) | StringOrRole::Entry(_) => None,
+        })
+        .collect();
+    while let Some(role) = roles.pop() {
+        pending.extend(role.tasks.iter());
+        tasks.extend(role.handlers.iter());
+        roles.extend(role.dependencies.iter());
+    }
+    while let Some(item) = pending.pop() {
+        match item {
+            TaskOrBlock::Task(task) => tasks.push(task),
+            TaskOrBlock::Block(block) => {
+                for list in [&block.block, items(&block.rescue), items(&block.always)] {
+                    pending.extend(list.iter());
+                }
+            }
+        }
+    }
+    tasks
+}
+
+/// Check the arguments of each module ([`cdk_ansible_core::core::TaskModule::validate`])
+fn check_modules(play: &Play) -> Result<()> {
+    for task in play_tasks(play) {
+        if let Err(e) = task.command.validate() {
+            bail!("play '{}': task '{}': {e}", play.name, task.name);
+        }
+    }
+    Ok(())
+}
+
+/// Check the combinations of keywords on `import_*` and `include_*`
+fn check_control_tasks(play: &Play) -> Result<()> {
+    for task in play_tasks(play) {
+        let Value::Object(map) = serde_json::to_value(task)? else {
+            continue;
+        };
+        for (key, args) in map.iter() {
+            let action = action_name(key);
+            match action {
+                "import_playbook" => bail!(
+                    "play '{}': task '{}' uses import_playbook, which is only allowed as a playbook entry",
+                    play.name,
+                    task.name
+                ),
+                "import_tasks" | "import_role" => {
+                    if let Some(keyword) = map
+                        .keys()
+                        .find(|k| LOOP_KEYWORDS.contains(&k.as_str()) || k.starts_with("with_"))
+                    {
+                        bail!(
+                            "play '{}': task '{}' uses {action} with '{keyword}', but loops are not supported by import_*; use include_* instead",
+                            play.name,
+                            task.name
+                        );
+                    }
+                    if args.get("apply").is_some() {
+                        bail!(
+                            "play '{}': task '{}' uses {action} with 'apply', which is only supported by include_*",
+                            play.name,
+                            task.name
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_notifies<'a>(item: &'a TaskOrBlock, out: &mut Vec<(String, &'a Handler)>) {
+    match item {
+        TaskOrBlock::Task(task) => {
+            for notify in items(&task.options.notify).iter() {
+                out.push((format!("task '{}'", task.name), notify));
+            }
+        }
+        TaskOrBlock::Block(block) => {
+            for notify in items(&block.options.notify).iter() {
+                out.push((format!("block '{}'", block.name), notify));
+            }
+            for items in [&block.block, items(&block.rescue), items(&block.always)] {
+                for item in items.iter() {
+                    collect_notifies(item, out);
+                }
+            }
+        }
+    }
+}
+
+fn play_steps(play: &Play) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    for items in [
+        items(&play.options.pre_tasks),
+        &play.tasks,
+        items(&play.options.post_tasks),
+    ] {
+        for item in items.iter() {
+            push_steps(&mut steps, item)?;
+        }
+    }
+    // Handlers run after the tasks
+    for handler in items(&play.options.handlers).iter() {
+        steps.push(task_step(handler)?);
+    }
+    Ok(steps)
+}
+
+fn push_steps(steps: &mut Vec<Step>, item: &TaskOrBlock) -> Result<()> {
+    match item {
+        TaskOrBlock::Task(task) => steps.push(task_step(task)?),
+        TaskOrBlock::Block(block) => {
+            steps.push(block_step(block)?);
+            for items in [&block.block, items(&block.rescue), items(&block.always)] {
+                for item in items.iter() {
+                    push_steps(steps, item)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn items<T: serde::Serialize>(list: &OptU<Vec<T>>) -> &[T] {
+    list.as_option().map_or(&[], Vec::as_slice)
+}
+
+fn task_step(task: &Task) -> Result<Step> {
+    let value = serde_json::to_value(task)?;
+    Ok(Step {
+        label: format!("task '{}'", task.name),
+        register: task
+            .options
+            .register
+            .as_option()
+            .map(|register| register.name().to_owned()),
+        refs: keyword_refs(&value),
+    })
+}
+
+fn block_step(block: &Block) -> Result<Step> {
+    let value = serde_json::to_value(&block.options)?;
+    Ok(Step {
+        label: format!("block '{}'", block.name),
+        register: None,
+        refs: keyword_refs(&value),
+    })
+}
+
+/// Variables referenced from the keywords and module arguments of a task (or a block)
+fn keyword_refs(value: &Value) -> Vec<(String, bool)> {
+    let mut refs = Vec::new();
+    let Value::Object(map) = value else {
+        return refs;
+    };
+    for (key, value) in map.iter() {
+        if key == "register" {
+            continue;
+        }
+        let post_eval = POST_EVAL_KEYWORDS.contains(&key.as_str());
+        let mut strings = Vec::new();
+        collect_strings(value, &mut strings);
+        for s in strings {
+            if EXPR_KEYWORDS.contains(&key.as_str()) {
+                refs.extend(
+                    jinja_identifiers(s)
+                        .into_iter()
+                        .map(|name| (name.to_owned(), post_eval)),
+                );
+            } else {
+                for segment in template_segments(s) {
+                    refs.extend(
+                        jinja_identifiers(segment)
+                            .into_iter()
+                            .map(|name| (name.to_owned(), post_eval)),
+                    );
+                }
+            }
+        }
+    }
+    refs
+}
+
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Contents of `{{ ... }}` and `{% ... %}` in a templated string
+fn template_segments(s: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('{') {
+        let (open, close) = match rest.get(start..start + 2) {
+            Some("{{") => ("{{", "}}"),
+            Some("{%") => ("{%", "%}"),
+            _ => {
+                rest = rest.get(start + 1..).unwrap_or_default();
+                continue;
+            }
+        };
+        let body = rest.get(start + open.len()..).unwrap_or_default();
+        let Some(end) = body.find(close) else {
+            break;
+        };
+        segments.push(body.get(..end).unwrap_or_default());
+        rest = body.get(end + close.len()..).unwrap_or_default();
+    }
+    segments
+}
+
+const JINJA_KEYWORDS: [&str; 20] = [
+    "and",
+    "or",
+    "not",
+    "in",
+    "is",
+    "if",
+    "else",
+    "elif",
+    "endif",
+    "for",
+    "endfor",
+    "set",
+    "true",
+    "false",
+    "none",
+    "True",
+    "False",
+    "None",
+    "recursive",
+    "with",
+];
+
+/// Top-level names in a Jinja2 expression
+///
+/// String literals, attributes (`x.name`), filters (`| name`) and tests (`is name`) are skipped.
+fn jinja_identifiers(expr: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut prev: Option<&str> = None;
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\'' || c == '"' {
+            let mut escaped = false;
+            for (_, d) in chars.by_ref() {
+                if escaped {
+                    escaped = false;
+                } else if d == '\\' {
+                    escaped = true;
+                } else if d == c {
+                    break;
+                }
+            }
+            prev = Some("'");
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, d)) = chars.peek() {
+                if d.is_ascii_alphanumeric() || d == '_' {
+                    end = j + d.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let name = expr.get(i..end).unwrap_or_default();
+            if !matches!(prev, Some("." | "|" | "is")) && !JINJA_KEYWORDS.contains(&name) {
+                names.push(name);
+            }
+            // `is not <test>`
+            if !(prev == Some("is") && name == "not") {
+                prev = Some(name);
+            }
+        } else if !c.is_whitespace() {
+            prev = expr.get(i..i + c.len_utf8());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::*;
+    use cdk_ansible_core::core::{
+        BlockOptions, ImportRole, ImportTasks, IncludeTasks, RawModule, Register, TaskOptions,
+        WithBlockOptions as _, WithLookup, WithTaskOptions as _,
+    };
+
+    /// Validate with the default lint severities
+    fn validate_plays<P: Borrow<Play>>(plays: &[P]) -> Result<Vec<String>> {
+        validate_plays_with(plays, &LintConfig::default())
+    }
+
+    fn task(name: &str, options: TaskOptions, msg: &str) -> TaskOrBlock {
+        Task {
+            name: name.to_string(),
+            options,
+            command: Box::new(debug::Module {
+                module: debug::Args {
+                    options: debug::Opt {
+                        msg: OptU::Some(msg.into()),
+                        ..Default::default()
+                    },
+                },
+            }),
+        }
+        .into()
+    }
+
+    fn play(name: &str, tasks: Vec<TaskOrBlock>) -> Play {
+        Play {
+            tasks,
+            ..create_play_helper(name)
+        }
+    }
+
+    fn register_task(result: &Register) -> TaskOrBlock {
+        task(
+            "register",
+            TaskOptions {
+                register: OptU::Some(result.clone()),
+                until: OptU::Some(result.rc().equals(0).to_string()),
+                ..Default::default()
+            },
+            "",
+        )
+    }
+
+    #[test]
+    fn test_jinja_identifiers() {
+        assert_eq!(
+            jinja_identifiers("a.b | default(c) is not defined and 'd' in e['f']"),
+            vec!["a", "c", "e"]
+        );
+        assert_eq!(
+            template_segments("x {{ a }} {% if b %}{ c }{% endif %}"),
+            vec![" a ", " if b ", " endif "]
+        );
+    }
+
+    #[test]
+    fn test_register_used_after() {
+        let result = Register::new("result");
+        let plays = vec![play(
+            "play1",
+            vec![
+                register_task(&result),
+                task(
+                    "use",
+                    TaskOptions {
+                        when: OptU::Some(result.changed().into()),
+                        ..Default::default()
+                    },
+                    &result.stdout().template(),
+                ),
+            ],
+        )];
+        validate_plays(&plays).unwrap();
+    }
+
+    #[test]
+    fn test_register_used_before() {
+        let result = Register::new("result");
+        let plays = vec![play(
+            "play1",
+            vec![
+                task("use", TaskOptions::default(), &result.stdout().template()),
+                register_task(&result),
+            ],
+        )];
+        assert!(
+            validate_plays(&plays)
+                .unwrap()
+                .iter()
+                .any(|w| w.contains("task 'use' uses 'result' before the task which registers it"))
+        );
+
+        // `when` is evaluated before the task runs
+        let plays = vec![play(
+            "play1",
+            vec![task(
+                "self",
+                TaskOptions {
+                    register: OptU::Some(result.clone()),
+                    when: OptU::Some(result.failed().into()),
+                    ..Default::default()
+                },
+                "",
+            )],
+        )];
+        assert!(
+            validate_plays(&plays)
+                .unwrap()
+                .iter()
+                .any(|w| w.contains("task 'self' uses 'result' before"))
+        );
+    }
+
+    #[test]
+    fn test_register_in_block() {
+        let result = Register::new("result");
+        let plays = vec![play(
+            "play1",
+            vec![
+                Block {
+                    name: "block".to_string(),
+                    options: BlockOptions::default(),
+                    block: vec![register_task(&result)],
+                    rescue: OptU::Unset,
+                    always: OptU::Some(vec![task(
+                        "use",
+                        TaskOptions::default(),
+                        &result.msg().template(),
+                    )]),
+                }
+                .into(),
+            ],
+        )];
+        validate_plays(&plays).unwrap();
+    }
+
+    #[test]
+    fn test_register_in_another_play() {
+        let result = Register::new("result");
+        let plays = vec![
+            play("play1", vec![register_task(&result)]),
+            play(
+                "play2",
+                vec![task(
+                    "use",
+                    TaskOptions {
+                        when: OptU::Some(result.rc().equals(0).into()),
+                        ..Default::default()
+                    },
+                    "",
+                )],
+            ),
+        ];
+        assert!(
+            validate_plays(&plays)
+                .unwrap()
+                .iter()
+                .any(|w| w.contains("registered in another play"))
+        );
+    }
+
+    fn handler_play(notify: &str) -> Play {
+        let restart = Task {
+            name: "restart".to_string(),
+            options: TaskOptions {
+                listen: OptU::Some(vec![Handler::topic("web changed")]),
+                ..Default::default()
+            },
+            command: Box::new(debug::Module {
+                module: debug::Args {
+                    options: debug::Opt::default(),
+                },
+            }),
+        };
+        Play {
+            options: cdk_ansible_core::core::PlayOptions {
+                handlers: OptU::Some(vec![restart]),
+                ..Default::default()
+            },
+            ..play(
+                "play1",
+                vec![task(
+                    "configure",
+                    TaskOptions {
+                        notify: OptU::Some(vec![notify.into()]),
+                        ..Default::default()
+                    },
+                    "",
+                )],
+            )
+        }
+    }
+
+    #[test]
+    fn test_handlers() {
+        assert!(
+            validate_plays(&[handler_play("restart")])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            validate_plays(&[handler_play("web changed")])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            validate_plays(&[handler_play("restrat")])
+                .unwrap_err()
+                .to_string()
+                .contains("task 'configure' notifies 'restrat'")
+        );
+    }
+
+    #[test]
+    fn test_handlers_not_notified() {
+        let mut play = handler_play("restart");
+        play.tasks = vec![];
+        assert_eq!(
+            validate_plays(&[play.clone()]).unwrap(),
+            vec!["play 'play1': handler 'restart' is not notified by any task".to_string()]
+        );
+
+        // Handlers of roles referenced by name are unknown
+        play.options.roles = OptU::Some(vec!["existing".into()]);
+        assert!(validate_plays(&[play]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_handlers_of_roles() {
+        // Handlers of a role can be notified by '<role> : <handler>'
+        let mut play = handler_play("nginx : restart");
+        let mut handlers = play.options.handlers.as_option().cloned().unwrap();
+        handlers[0].options.listen = OptU::Unset;
+        play.options.handlers = OptU::Unset;
+        play.options.roles = OptU::Some(vec![
+            Role {
+                name: "nginx".to_string(),
+                handlers,
+                ..Default::default()
+            }
+            .into(),
+        ]);
+        assert!(validate_plays(&[&play]).unwrap().is_empty());
+
+        // Handlers of a role imported by a task are unknown
+        let mut play = handler_play("reload");
+        play.options.handlers = OptU::Unset;
+        play.tasks
+            .push(Task::new("import", ImportRole::new("x")).into());
+        assert_eq!(
+            validate_plays(&[&play]).unwrap(),
+            vec![
+                "play 'play1': task 'configure' notifies 'reload', which is not a handler or a listen topic in the play (it may be in a role imported or included by a task)"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_with_loop() {
+        let loop_task = Task::new("import", ImportTasks::new("x.yaml"))
+            .loop_(vec![serde_json::json!(1)])
+            .into();
+        assert!(validate_plays(&[&play("play1", vec![loop_task])]).is_err());
+
+        let include_task = Task::new("include", IncludeTasks::new("x.yaml"))
+            .with_lookup(WithLookup::items(vec![1]))
+            .into();
+        assert!(validate_plays(&[&play("play1", vec![include_task])]).is_ok());
+
+        let block = Block::new("block1", [Task::new("import", ImportRole::new("r1"))])
+            .tags(["t"])
+            .into();
+        assert!(validate_plays(&[&play("play1", vec![block])]).is_ok());
+
+        let raw_apply = Task::new(
+            "import",
+            RawModule::new(
+                "import_role",
+                serde_json::json!({ "name": "r1", "apply": { "tags": ["t"] } }),
+            ),
+        )
+        .into();
+        assert!(validate_plays(&[&play("play1", vec![raw_apply])]).is_err());
+    }
+}
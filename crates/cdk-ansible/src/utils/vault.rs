@@ -0,0 +1,306 @@
+//! [Ansible Vault](https://docs.ansible.com/ansible/latest/vault_guide/index.html) `AES256` encryption
+//!
+//! Values of [`cdk_ansible_core::core::Secret`] are encrypted by the YAML writer with the vault passed to [`super::to_yaml_string_with`].
+
+use aes::cipher::{KeyIvInit as _, StreamCipher as _};
+use anyhow::{Context as _, Result, bail};
+use hmac::Mac as _;
+use std::path::{Path, PathBuf};
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+/// Environment variable of the vault password
+pub const VAULT_PASSWORD_ENV: &str = "CDK_ANSIBLE_VAULT_PASSWORD";
+
+const PBKDF2_ROUNDS: u32 = 10000;
+const SALT_LEN: usize = 32;
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 16;
+const BLOCK_LEN: usize = 16;
+/// Width of the lines of the vault text
+const LINE_WIDTH: usize = 80;
+
+/// A vault password, with an optional vault ID
+#[derive(Clone)]
+pub struct Vault {
+    password: Vec<u8>,
+    vault_id: Option<String>,
+    password_file: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vault")
+            .field("vault_id", &self.vault_id)
+            .field("password_file", &self.password_file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Vault {
+    pub fn new(password: impl Into<Vec<u8>>) -> Self {
+        Self {
+            password: password.into(),
+            vault_id: None,
+            password_file: None,
+        }
+    }
+
+    /// Read the password from a file. Surrounding whitespace is stripped, as Ansible does.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read(path)
+            .with_context(|| format!("reading vault password file {}", path.display()))?;
+        let password = content.trim_ascii().to_vec();
+        if password.is_empty() {
+            bail!("vault password file {} is empty", path.display());
+        }
+        Ok(Self {
+            password_file: Some(path.to_path_buf()),
+            ..Self::new(password)
+        })
+    }
+
+    /// Read the password from `password_file`, or from [`VAULT_PASSWORD_ENV`]
+    pub fn from_file_or_env(
+        password_file: Option<&Path>,
+        vault_id: Option<&str>,
+    ) -> Result<Option<Self>> {
+        let vault = match password_file {
+            Some(path) => Self::from_file(path)?,
+            None => match std::env::var(VAULT_PASSWORD_ENV) {
+                Ok(password) if !password.is_empty() => Self::new(password),
+                Ok(_) | Err(_) => return Ok(None),
+            },
+        };
+        Ok(Some(match vault_id {
+            Some(id) => vault.vault_id(id),
+            None => vault,
+        }))
+    }
+
+    /// Label the vault text with the vault ID (format 1.2)
+    pub fn vault_id(mut self, vault_id: &str) -> Self {
+        self.vault_id = Some(vault_id.to_owned());
+        self
+    }
+
+    /// The file which the password was read from
+    pub fn password_file(&self) -> Option<&Path> {
+        self.password_file.as_deref()
+    }
+
+    /// Arguments of `ansible-playbook` to decrypt the values
+    ///
+    /// `password_file` is used if the password was not read from a file.
+    pub fn playbook_args(&self, password_file: &Path) -> Vec<String> {
+        let file = self
+            .password_file
+            .as_deref()
+            .unwrap_or(password_file)
+            .to_string_lossy()
+            .to_string();
+        match &self.vault_id {
+            Some(id) => vec!["--vault-id".to_owned(), format!("{id}@{file}")],
+            None => vec!["--vault-password-file".to_owned(), file],
+        }
+    }
+
+    /// Encrypt `plaintext` into a vault text (`$ANSIBLE_VAULT;1.1;AES256` or `1.2` with the vault ID)
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let mut salt = [0u8; SALT_LEN];
+        getrandom::fill(&mut salt).map_err(|e| anyhow::anyhow!("generating salt: {e}"))?;
+        self.encrypt_with_salt(plaintext, &salt)
+    }
+
+    fn encrypt_with_salt(&self, plaintext: &[u8], salt: &[u8]) -> Result<String> {
+        let (key, hmac_key, iv) = self.derive_keys(salt);
+
+        // PKCS#7 padding, although CTR mode doesn't need it
+        let pad = BLOCK_LEN - plaintext.len() % BLOCK_LEN;
+        let mut data = plaintext.to_vec();
+        data.extend(std::iter::repeat_n(
+            u8::try_from(pad).unwrap_or_default(),
+            pad,
+        ));
+        Aes256Ctr::new(&key.into(), &iv.into()).apply_keystream(&mut data);
+
+        let mut mac = HmacSha256::new_from_slice(&hmac_key).context("creating HMAC")?;
+        mac.update(&data);
+        let tag = mac.finalize().into_bytes();
+
+        let inner = format!("{}\n{}\n{}", hex(salt), hex(&tag), hex(&data));
+        let mut out = match &self.vault_id {
+            Some(id) => format!("$ANSIBLE_VAULT;1.2;AES256;{id}\n"),
+            None => "$ANSIBLE_VAULT;1.1;AES256\n".to_owned(),
+        };
+        let body = hex(inner.as_bytes());
+        for line in body.as_bytes().chunks(LINE_WIDTH) {
+            out.push_str(&String::from_utf8_lossy(line));
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Decrypt a vault text
+    pub fn decrypt(&self, vaulttext: &str) -> Result<Vec<u8>> {
+        let mut lines = vaulttext.trim().lines();
+        let header = lines.next().context("empty vault text")?;
+        let fields: Vec<&str> = header.trim().split(';').collect();
+        match fields.as_slice() {
+            ["$ANSIBLE_VAULT", "1.1" | "1.2", cipher, ..] if cipher.trim() == "AES256" => {}
+            ["$ANSIBLE_VAULT", ..] => bail!("unsupported vault format: {header}"),
+            _ => bail!("not a vault text"),
+        }
+        let body: String = lines.map(str::trim).collect();
+        let inner = String::from_utf8(unhex(&body)?).context("decoding vault text")?;
+        let parts: Vec<&str> = inner.split('\n').collect();
+        let [salt, tag, data] = parts.as_slice() else {
+            bail!("malformed vault text");
+        };
+        let (salt, tag, mut data) = (unhex(salt)?, unhex(tag)?, unhex(data)?);
+
+        let (key, hmac_key, iv) = self.derive_keys(&salt);
+        let mut mac = HmacSha256::new_from_slice(&hmac_key).context("creating HMAC")?;
+        mac.update(&data);
+        mac.verify_slice(&tag)
+            .map_err(|_| anyhow::anyhow!("HMAC verification failed (wrong password?)"))?;
+
+        Aes256Ctr::new(&key.into(), &iv.into()).apply_keystream(&mut data);
+        let pad = usize::from(data.last().copied().unwrap_or_default());
+        if pad == 0 || pad > BLOCK_LEN || pad > data.len() {
+            bail!("invalid padding");
+        }
+        data.truncate(data.len() - pad);
+        Ok(data)
+    }
+
+    /// (AES key, HMAC key, IV)
+    fn derive_keys(&self, salt: &[u8]) -> ([u8; KEY_LEN], [u8; KEY_LEN], [u8; IV_LEN]) {
+        let mut derived = [0u8; KEY_LEN * 2 + IV_LEN];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(&self.password, salt, PBKDF2_ROUNDS, &mut derived);
+        let mut key = [0u8; KEY_LEN];
+        let mut hmac_key = [0u8; KEY_LEN];
+        let mut iv = [0u8; IV_LEN];
+        let (k, rest) = derived.split_at(KEY_LEN);
+        let (h, i) = rest.split_at(KEY_LEN);
+        key.copy_from_slice(k);
+        hmac_key.copy_from_slice(h);
+        iv.copy_from_slice(i);
+        (key, hmac_key, iv)
+    }
+}
+
+/// Arguments of `ansible-playbook` to decrypt the `!vault` values of `vault`
+///
+/// If the password is not in a file, the script of [`write_vault_password_client`] is written at `client`.
+pub fn vault_playbook_args(vault: Option<&Vault>, client: &Path) -> Result<Vec<String>> {
+    let Some(vault) = vault else {
+        return Ok(Vec::new());
+    };
+    if vault.password_file().is_none() {
+        write_vault_password_client(client)?;
+    }
+    Ok(vault.playbook_args(client))
+}
+
+/// Write a script printing [`VAULT_PASSWORD_ENV`], passed to `ansible-playbook` as the password file
+///
+/// The password itself is never written to disk.
+pub fn write_vault_password_client(path: &Path) -> Result<()> {
+    let script = format!("#!/bin/sh\nprintf '%s\\n' \"${VAULT_PASSWORD_ENV}\"\n");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, script).with_context(|| format!("writing {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write as _;
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn unhex(s: &str) -> Result<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        bail!("odd length of hex string");
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .context("invalid hex string")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let vault = Vault::new("password");
+        let text = vault.encrypt(b"secret value").expect("failed to encrypt");
+        assert!(text.starts_with("$ANSIBLE_VAULT;1.1;AES256\n"));
+        assert!(text.lines().skip(1).all(|line| line.len() <= LINE_WIDTH));
+        assert_eq!(
+            vault.decrypt(&text).expect("failed to decrypt"),
+            b"secret value"
+        );
+        assert!(Vault::new("wrong").decrypt(&text).is_err());
+
+        let vault = vault.vault_id("prod");
+        let text = vault.encrypt(b"").expect("failed to encrypt");
+        assert!(text.starts_with("$ANSIBLE_VAULT;1.2;AES256;prod\n"));
+        assert_eq!(vault.decrypt(&text).expect("failed to decrypt"), b"");
+    }
+
+    #[test]
+    fn test_fixed_vector() {
+        // Generated by the VaultAES256 algorithm of Ansible with the password 'password'
+        let text = "$ANSIBLE_VAULT;1.1;AES256
+30303031303230333034303530363037303830393061306230633064306530663130313131323133
+3134313531363137313831393161316231633164316531660a643037656630346536633535313538
+31373561643264396134386437623563303430616633643762303831656565643562663863663635
+6566303837326566370a663539626631666135363164656639393231666238346563353463646533
+6137
+";
+        let vault = Vault::new("password");
+        let salt: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            vault
+                .encrypt_with_salt(b"hello", &salt)
+                .expect("failed to encrypt"),
+            text
+        );
+        assert_eq!(vault.decrypt(text).expect("failed to decrypt"), b"hello");
+    }
+
+    #[test]
+    fn test_vault_playbook_args() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let client = temp_dir.path().join("vault-password-client");
+        assert!(vault_playbook_args(None, &client).unwrap().is_empty());
+        assert!(!client.exists());
+
+        let password_file = temp_dir.path().join("password");
+        std::fs::write(&password_file, "password").unwrap();
+        let vault = Vault::from_file(&password_file).unwrap();
+        vault_playbook_args(Some(&vault), &client).unwrap();
+        assert!(!client.exists());
+
+        let args = vault_playbook_args(Some(&Vault::new("password")), &client).unwrap();
+        assert!(client.exists());
+        assert!(args.contains(&client.display().to_string()));
+    }
+}
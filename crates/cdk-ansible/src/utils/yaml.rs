@@ -0,0 +1,1216 @@
+//! In-process YAML writer
+//!
+//! Serializes any [`Serialize`] value (including `dyn TaskModule` trait objects) straight to YAML.
+//! The output follows the style of `yq -p json -o yaml`, which was used before:
+//!
+//! * keys keep their insertion order ([`indexmap::IndexMap`], `serde_json` with `preserve_order`)
+//! * 2-space indentation, sequences are indented under their parent key
+//! * multi-line strings are written as literal block scalars (`|`, `|-`, `|+`)
+//! * strings which would be resolved as another type (`"true"`, `"10"`, `""`, ...) are double-quoted
+//! * strings which can not be plain scalars (`{{ ... }}`, `a: b`, ...) are single-quoted
+//! * tagged values ([`cdk_ansible_core::core::Secret`], [`cdk_ansible_core::core::Unsafe`])
+//!   are written with their tag (`!vault |`, `!unsafe '{{ ... }}'`)
+//!
+//! Reading YAML is delegated to `serde_yaml_ng`.
+
+use super::Vault;
+use cdk_ansible_core::core::TAG_KEY_PREFIX;
+use serde::{Serialize, de::DeserializeOwned, ser};
+use std::fmt::Write as _;
+use thiserror::Error;
+
+/// Indentation width of the block collections.
+const INDENT: usize = 2;
+
+#[derive(Error, Debug)]
+pub enum YamlError {
+    #[error("{0}")]
+    Custom(String),
+    #[error("mapping key must be a scalar: {0:?}")]
+    NonScalarKey(Node),
+    #[error("unknown tag: !{0}")]
+    UnknownTag(String),
+    #[error(
+        "secret values require a vault password (--vault-password-file or CDK_ANSIBLE_VAULT_PASSWORD)"
+    )]
+    NoVault,
+    #[error("encrypting a secret value: {0}")]
+    Vault(String),
+}
+
+impl ser::Error for YamlError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Serialize `value` into a YAML document.
+///
+/// Secret values are an error ([`YamlError::NoVault`]); use [`to_yaml_string_with`] to encrypt them.
+pub fn to_yaml_string<T: Serialize + ?Sized>(value: &T) -> Result<String, YamlError> {
+    to_yaml_string_with(value, None)
+}
+
+/// Serialize `value` into a YAML document, encrypting secret values with `vault`.
+pub fn to_yaml_string_with<T: Serialize + ?Sized>(
+    value: &T,
+    vault: Option<&Vault>,
+) -> Result<String, YamlError> {
+    let mut node = value.serialize(NodeSerializer)?;
+    resolve_tags(&mut node, vault)?;
+    let mut out = String::new();
+    Emitter { out: &mut out }.document(&node)?;
+    Ok(out)
+}
+
+/// Deserialize `T` from a YAML document.
+pub fn from_yaml_str<T: DeserializeOwned>(s: &str) -> Result<T, serde_yaml_ng::Error> {
+    serde_yaml_ng::from_str(s)
+}
+
+/// YAML node tree built by [`NodeSerializer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    String(String),
+    Seq(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    /// `!<tag> <node>`
+    Tagged(String, Box<Node>),
+}
+
+/// Encrypt the contents of `!vault` nodes, and move `!unsafe` tags onto strings.
+fn resolve_tags(node: &mut Node, vault: Option<&Vault>) -> Result<(), YamlError> {
+    match node {
+        Node::Seq(items) => items.iter_mut().try_for_each(|n| resolve_tags(n, vault)),
+        Node::Map(entries) => entries
+            .iter_mut()
+            .try_for_each(|(_, n)| resolve_tags(n, vault)),
+        Node::Tagged(tag, inner) => match (tag.as_str(), inner.as_mut()) {
+            ("vault", Node::String(s)) => {
+                let vault = vault.ok_or(YamlError::NoVault)?;
+                *s = vault
+                    .encrypt(s.as_bytes())
+                    .map_err(|e| YamlError::Vault(format!("{e:#}")))?;
+                Ok(())
+            }
+            ("unsafe", _) => {
+                resolve_tags(inner, vault)?;
+                *node = mark_unsafe(std::mem::replace(inner.as_mut(), Node::Null));
+                Ok(())
+            }
+            _ => Err(YamlError::UnknownTag(tag.clone())),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Tag the strings in `node` with `!unsafe`. Only strings can be templated.
+fn mark_unsafe(node: Node) -> Node {
+    match node {
+        Node::String(_) => Node::Tagged("unsafe".to_owned(), Box::new(node)),
+        Node::Seq(items) => Node::Seq(items.into_iter().map(mark_unsafe).collect()),
+        Node::Map(entries) => Node::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k, mark_unsafe(v)))
+                .collect(),
+        ),
+        _ => node,
+    }
+}
+
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, YamlError> {
+        Ok(Node::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Node, YamlError> {
+        Ok(Node::Int(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Node, YamlError> {
+        Ok(Node::Int(v.into()))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Node, YamlError> {
+        i128::try_from(v)
+            .map(Node::Int)
+            .map_err(|_| YamlError::Custom(format!("integer out of range: {v}")))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Node, YamlError> {
+        Ok(Node::Float(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Node, YamlError> {
+        Ok(Node::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Node, YamlError> {
+        Ok(Node::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Node, YamlError> {
+        Ok(Node::String(v.to_owned()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, YamlError> {
+        Ok(Node::Seq(
+            v.iter().map(|b| Node::Int((*b).into())).collect(),
+        ))
+    }
+    fn serialize_none(self) -> Result<Node, YamlError> {
+        Ok(Node::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, YamlError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Node, YamlError> {
+        Ok(Node::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, YamlError> {
+        Ok(Node::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Node, YamlError> {
+        Ok(Node::String(variant.to_owned()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, YamlError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, YamlError> {
+        Ok(Node::Map(vec![(
+            Node::String(variant.to_owned()),
+            value.serialize(self)?,
+        )]))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, YamlError> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, YamlError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, YamlError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<SeqSerializer>, YamlError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, YamlError> {
+        Ok(MapSerializer {
+            entries: Vec::new(),
+            next_key: None,
+        })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, YamlError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<MapSerializer>, YamlError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SeqSerializer(Vec<Node>);
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), YamlError> {
+        self.0.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        Ok(Node::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), YamlError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), YamlError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapSerializer {
+    entries: Vec<(Node, Node)>,
+    next_key: Option<Node>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), YamlError> {
+        self.next_key = Some(key.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), YamlError> {
+        let key = self.next_key.take().ok_or_else(|| {
+            YamlError::Custom("serialize_value called before serialize_key".into())
+        })?;
+        self.entries.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        // `{"$cdk_ansible::tag::<tag>": <value>}` is a tagged value
+        if let [(Node::String(key), _)] = self.entries.as_slice() {
+            if let Some(tag) = key.strip_prefix(TAG_KEY_PREFIX) {
+                let tag = tag.to_owned();
+                let value = self.entries.into_iter().map(|(_, v)| v).next();
+                return Ok(Node::Tagged(tag, Box::new(value.unwrap_or(Node::Null))));
+            }
+        }
+        Ok(Node::Map(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), YamlError> {
+        self.entries.push((
+            Node::String(key.to_owned()),
+            value.serialize(NodeSerializer)?,
+        ));
+        Ok(())
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Wraps the content of an enum variant as `{ <variant>: <content> }`
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), YamlError> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        Ok(Node::Map(vec![(
+            Node::String(self.variant.to_owned()),
+            ser::SerializeSeq::end(self.inner)?,
+        )]))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = Node;
+    type Error = YamlError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), YamlError> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+    fn end(self) -> Result<Node, YamlError> {
+        Ok(Node::Map(vec![(
+            Node::String(self.variant.to_owned()),
+            ser::SerializeMap::end(self.inner)?,
+        )]))
+    }
+}
+
+/// Writes a [`Node`] tree as a block style YAML document.
+struct Emitter<'a> {
+    out: &'a mut String,
+}
+
+impl Emitter<'_> {
+    fn document(&mut self, node: &Node) -> Result<(), YamlError> {
+        match node {
+            Node::Seq(items) if !items.is_empty() => self.seq(items, 0, false)?,
+            Node::Map(entries) if !entries.is_empty() => self.map(entries, 0, false)?,
+            _ => {
+                self.inline(node, 0, false)?;
+                self.out.push('\n');
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `node` right after `- ` or `key:` (or at the document root).
+    ///
+    /// Non-empty collections are not handled here.
+    fn inline(&mut self, node: &Node, indent: usize, is_key: bool) -> Result<(), YamlError> {
+        match node {
+            Node::Null => self.out.push_str("null"),
+            Node::Bool(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            Node::Int(i) => {
+                let _ = write!(self.out, "{i}");
+            }
+            Node::Float(f) => self.float(*f),
+            Node::String(s) => self.string(s, indent, is_key),
+            Node::Seq(_) => self.out.push_str("[]"),
+            Node::Map(_) => self.out.push_str("{}"),
+            Node::Tagged(tag, inner) => {
+                let _ = write!(self.out, "!{tag} ");
+                self.inline(inner, indent, is_key)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn float(&mut self, f: f64) {
+        if f.is_nan() {
+            self.out.push_str(".nan");
+        } else if f.is_infinite() {
+            self.out.push_str(if f.is_sign_positive() {
+                ".inf"
+            } else {
+                "-.inf"
+            });
+        } else if let Some(n) = serde_json::Number::from_f64(f) {
+            let _ = write!(self.out, "{n}");
+        }
+    }
+
+    /// Write sequence items at `indent`.
+    /// If `continued`, the first item is written on the current line (after `- `).
+    fn seq(&mut self, items: &[Node], indent: usize, continued: bool) -> Result<(), YamlError> {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 || !continued {
+                self.pad(indent);
+            }
+            self.out.push('-');
+            self.block_value(item, indent + INDENT, true)?;
+        }
+        Ok(())
+    }
+
+    /// Write mapping entries at `indent`.
+    /// If `continued`, the first entry is written on the current line (after `- `).
+    fn map(
+        &mut self,
+        entries: &[(Node, Node)],
+        indent: usize,
+        continued: bool,
+    ) -> Result<(), YamlError> {
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if i > 0 || !continued {
+                self.pad(indent);
+            }
+            match key {
+                Node::Seq(_) | Node::Map(_) | Node::Tagged(..) => {
+                    return Err(YamlError::NonScalarKey(key.clone()));
+                }
+                _ => self.inline(key, indent, true)?,
+            }
+            self.out.push(':');
+            self.block_value(value, indent + INDENT, false)?;
+        }
+        Ok(())
+    }
+
+    /// Write a value following `-` (`in_seq`) or `key:`.
+    ///
+    /// `indent` is the indentation of the nested block.
+    fn block_value(&mut self, node: &Node, indent: usize, in_seq: bool) -> Result<(), YamlError> {
+        match node {
+            Node::Seq(items) if !items.is_empty() => {
+                if in_seq {
+                    self.out.push(' ');
+                    self.seq(items, indent, true)
+                } else {
+                    self.out.push('\n');
+                    self.seq(items, indent, false)
+                }
+            }
+            Node::Map(entries) if !entries.is_empty() => {
+                if in_seq {
+                    self.out.push(' ');
+                    self.map(entries, indent, true)
+                } else {
+                    self.out.push('\n');
+                    self.map(entries, indent, false)
+                }
+            }
+            _ => {
+                self.out.push(' ');
+                self.inline(node, indent, false)?;
+                // Literal block scalars already end with a line break
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn pad(&mut self, indent: usize) {
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+
+    fn string(&mut self, s: &str, indent: usize, is_key: bool) {
+        match ScalarStyle::select(s, is_key) {
+            ScalarStyle::Plain => self.out.push_str(s),
+            ScalarStyle::SingleQuoted => {
+                self.out.push('\'');
+                self.out.push_str(&s.replace('\'', "''"));
+                self.out.push('\'');
+            }
+            ScalarStyle::DoubleQuoted => self.double_quoted(s),
+            ScalarStyle::Literal => self.literal(s, indent),
+        }
+    }
+
+    fn double_quoted(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '\0' => self.out.push_str("\\0"),
+                '\x07' => self.out.push_str("\\a"),
+                '\x08' => self.out.push_str("\\b"),
+                '\t' => self.out.push_str("\\t"),
+                '\n' => self.out.push_str("\\n"),
+                '\x0b' => self.out.push_str("\\v"),
+                '\x0c' => self.out.push_str("\\f"),
+                '\r' => self.out.push_str("\\r"),
+                '\x1b' => self.out.push_str("\\e"),
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\u{85}' => self.out.push_str("\\N"),
+                '\u{2028}' => self.out.push_str("\\L"),
+                '\u{2029}' => self.out.push_str("\\P"),
+                c if c == '\u{feff}' || !is_printable(c) => {
+                    let code = u32::from(c);
+                    let _ = if code <= 0xff {
+                        write!(self.out, "\\x{code:02X}")
+                    } else if code <= 0xffff {
+                        write!(self.out, "\\u{code:04X}")
+                    } else {
+                        write!(self.out, "\\U{code:08X}")
+                    };
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+
+    /// Literal block scalar. The content is indented at `indent`.
+    fn literal(&mut self, s: &str, indent: usize) {
+        self.out.push('|');
+        if s.starts_with([' ', '\n']) {
+            let _ = write!(self.out, "{INDENT}");
+        }
+        let body = match s.strip_suffix('\n') {
+            None => {
+                self.out.push('-');
+                s
+            }
+            Some(body) if body.is_empty() || body.ends_with('\n') => {
+                self.out.push('+');
+                body
+            }
+            Some(body) => body,
+        };
+        self.out.push('\n');
+        for line in body.split('\n') {
+            if !line.is_empty() {
+                self.pad(indent);
+                self.out.push_str(line);
+            }
+            self.out.push('\n');
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    Literal,
+}
+
+impl ScalarStyle {
+    /// Choose the scalar style for a string in the same way as go-yaml v3 (used by yq).
+    fn select(s: &str, is_key: bool) -> Self {
+        let requested = if s.contains('\n') {
+            Self::Literal
+        } else if resolves_to_non_string(s) {
+            Self::DoubleQuoted
+        } else {
+            Self::Plain
+        };
+        let analysis = Analysis::new(s);
+        match requested {
+            Self::Plain if analysis.plain_allowed && !s.is_empty() => Self::Plain,
+            Self::Plain | Self::SingleQuoted if analysis.single_quoted_allowed => {
+                Self::SingleQuoted
+            }
+            Self::Literal if analysis.block_allowed && !is_key => Self::Literal,
+            _ => Self::DoubleQuoted,
+        }
+    }
+}
+
+/// Result of the scalar analysis of libyaml (`yaml_emitter_analyze_scalar`) in block context.
+struct Analysis {
+    plain_allowed: bool,
+    single_quoted_allowed: bool,
+    block_allowed: bool,
+}
+
+impl Analysis {
+    fn new(s: &str) -> Self {
+        if s.is_empty() {
+            return Self {
+                plain_allowed: false,
+                single_quoted_allowed: true,
+                block_allowed: false,
+            };
+        }
+        let chars = s.chars().collect::<Vec<_>>();
+        let is_blankz = |i: usize| {
+            chars
+                .get(i)
+                .is_none_or(|c| matches!(c, ' ' | '\t') || is_break(*c))
+        };
+
+        let mut block_indicators = s.starts_with("---") || s.starts_with("...");
+        block_indicators &= is_blankz(3);
+        let mut special_characters = false;
+        let mut line_breaks = false;
+        let mut leading_space = false;
+        let mut leading_break = false;
+        let mut trailing_space = false;
+        let mut trailing_break = false;
+        let mut break_space = false;
+        let mut space_break = false;
+        let mut previous_space = false;
+        let mut previous_break = false;
+        let mut preceded_by_whitespace = true;
+
+        let last = chars.len() - 1;
+        for (i, &c) in chars.iter().enumerate() {
+            let followed_by_whitespace = is_blankz(i + 1);
+            if i == 0 {
+                match c {
+                    '#' | ',' | '[' | ']' | '{' | '}' | '&' | '*' | '!' | '|' | '>' | '\''
+                    | '"' | '%' | '@' | '`' => block_indicators = true,
+                    '?' | ':' | '-' if followed_by_whitespace => block_indicators = true,
+                    _ => {}
+                }
+            } else {
+                match c {
+                    ':' if followed_by_whitespace => block_indicators = true,
+                    '#' if preceded_by_whitespace => block_indicators = true,
+                    _ => {}
+                }
+            }
+            if !is_printable(c) || c == '\u{feff}' {
+                special_characters = true;
+            }
+            if is_break(c) {
+                line_breaks = true;
+            }
+            if c == ' ' {
+                leading_space |= i == 0;
+                trailing_space |= i == last;
+                break_space |= previous_break;
+                previous_space = true;
+                previous_break = false;
+            } else if is_break(c) {
+                leading_break |= i == 0;
+                trailing_break |= i == last;
+                space_break |= previous_space;
+                previous_space = false;
+                previous_break = true;
+            } else {
+                previous_space = false;
+                previous_break = false;
+            }
+            preceded_by_whitespace = matches!(c, ' ' | '\t') || is_break(c);
+        }
+
+        let mut plain_allowed = !(leading_space
+            || leading_break
+            || trailing_space
+            || trailing_break
+            || break_space
+            || line_breaks
+            || block_indicators);
+        let mut single_quoted_allowed = !break_space;
+        let mut block_allowed = !trailing_space;
+        if space_break || special_characters {
+            plain_allowed = false;
+            single_quoted_allowed = false;
+            block_allowed = false;
+        }
+        Self {
+            plain_allowed,
+            single_quoted_allowed,
+            block_allowed,
+        }
+    }
+}
+
+fn is_break(c: char) -> bool {
+    matches!(c, '\r' | '\n' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+fn is_printable(c: char) -> bool {
+    matches!(c,
+        '\n'
+        | '\x20'..='\x7e'
+        | '\u{85}'
+        | '\u{a0}'..='\u{d7ff}'
+        | '\u{e000}'..='\u{fffd}'
+        | '\u{10000}'..='\u{10ffff}')
+}
+
+/// Whether a plain scalar `s` is resolved as a non-string value (null, bool, number, timestamp)
+/// or is a YAML 1.1 value which must be quoted for compatibility (`yes`, `on`, `1:20`, ...).
+fn resolves_to_non_string(s: &str) -> bool {
+    matches!(
+        s,
+        "" | "~"
+            | "null"
+            | "Null"
+            | "NULL"
+            | "true"
+            | "True"
+            | "TRUE"
+            | "false"
+            | "False"
+            | "FALSE"
+            | ".nan"
+            | ".NaN"
+            | ".NAN"
+            | ".inf"
+            | ".Inf"
+            | ".INF"
+            | "+.inf"
+            | "+.Inf"
+            | "+.INF"
+            | "-.inf"
+            | "-.Inf"
+            | "-.INF"
+            | "<<"
+            // YAML 1.1 booleans
+            | "y"
+            | "Y"
+            | "yes"
+            | "Yes"
+            | "YES"
+            | "n"
+            | "N"
+            | "no"
+            | "No"
+            | "NO"
+            | "on"
+            | "On"
+            | "ON"
+            | "off"
+            | "Off"
+            | "OFF"
+    ) || is_number(s)
+        || is_base60_float(s)
+        || is_timestamp(s)
+}
+
+fn is_number(s: &str) -> bool {
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    if first == '.' {
+        return is_float(s);
+    }
+    if !(first.is_ascii_digit() || first == '+' || first == '-') {
+        return false;
+    }
+    let plain = s.replace('_', "");
+    let unsigned = plain.trim_start_matches(['+', '-']);
+    if plain.len() - unsigned.len() > 1 {
+        return false;
+    }
+    let is_radix = |prefix: &str, radix: u32| {
+        unsigned
+            .strip_prefix(prefix)
+            .is_some_and(|d| !d.is_empty() && d.chars().all(|c| c.is_digit(radix)))
+    };
+    is_radix("0x", 16)
+        || is_radix("0X", 16)
+        || is_radix("0o", 8)
+        || is_radix("0O", 8)
+        || is_radix("0b", 2)
+        || is_radix("0B", 2)
+        || (!unsigned.is_empty() && unsigned.chars().all(|c| c.is_ascii_digit()))
+        || is_float(&plain)
+}
+
+/// `^[-+]?(\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?$`
+fn is_float(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let digits = |d: &str| !d.is_empty() && d.chars().all(|c| c.is_ascii_digit());
+    let mantissa_ok = match mantissa.split_once('.') {
+        Some(("", frac)) => digits(frac),
+        Some((int, frac)) => digits(int) && frac.chars().all(|c| c.is_ascii_digit()),
+        None => digits(mantissa),
+    };
+    mantissa_ok && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+/// `^[-+]?[0-9][0-9_]*(?::[0-5]?[0-9])+(?:\.[0-9_]*)?$`
+fn is_base60_float(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (s, frac) = match s.split_once('.') {
+        Some((s, frac)) => (s, Some(frac)),
+        None => (s, None),
+    };
+    let mut parts = s.split(':');
+    let head_ok = parts.next().is_some_and(|h| {
+        h.starts_with(|c: char| c.is_ascii_digit())
+            && h.chars().all(|c| c.is_ascii_digit() || c == '_')
+    });
+    let mut n_parts = 0;
+    let tail_ok = parts.all(|p| {
+        n_parts += 1;
+        match p.as_bytes() {
+            [d] => d.is_ascii_digit(),
+            [a, b] => (b'0'..=b'5').contains(a) && b.is_ascii_digit(),
+            _ => false,
+        }
+    });
+    head_ok
+        && tail_ok
+        && n_parts > 0
+        && frac.is_none_or(|f| f.chars().all(|c| c.is_ascii_digit() || c == '_'))
+}
+
+/// Dates (`2001-12-14`) and date times (`2001-12-14t21:59:43.10-05:00`, `2001-12-14 21:59:43.10`)
+fn is_timestamp(s: &str) -> bool {
+    let (date, time) = match s.find(['T', 't', ' ']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let mut parts = date.split('-');
+    let date_ok = matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(y), Some(m), Some(d), None)
+            if y.len() == 4
+                && (1..=2).contains(&m.len())
+                && (1..=2).contains(&d.len())
+                && [y, m, d].iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+    );
+    date_ok
+        && time.is_none_or(|t| {
+            let mut hms = t.splitn(3, ':');
+            matches!(
+                (hms.next(), hms.next(), hms.next()),
+                (Some(h), Some(m), Some(_))
+                    if (1..=2).contains(&h.len())
+                        && (1..=2).contains(&m.len())
+                        && h.chars().chain(m.chars()).all(|c| c.is_ascii_digit())
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::*;
+    use cdk_ansible_core::core::{
+        InventoryChild, InventoryRoot, OptU, Play, RawModule, Secret, StringOrRole, Task,
+        TaskOptions, Unsafe,
+    };
+    use indexmap::IndexMap;
+    use serde_json::json;
+
+    #[test]
+    fn test_play() {
+        assert_eq!(
+            to_yaml_string(&vec![create_play_helper("sample0")]).expect("failed to serialize"),
+            String::new()
+                + "- name: sample0\n"
+                + "  hosts: localhost\n"
+                + "  tasks:\n"
+                + "    - name: debug\n"
+                + "      ansible.builtin.debug:\n"
+                + "        msg: Hello, world!\n"
+        );
+    }
+
+    #[test]
+    fn test_load_playbook() {
+        let src = String::new()
+            + "- name: legacy\n"
+            + "  hosts: all\n"
+            + "  tasks:\n"
+            + "    - name: install\n"
+            + "      ansible.builtin.apt:\n"
+            + "        name: '{{ item }}'\n"
+            + "        state: present\n"
+            + "      loop:\n"
+            + "        - nginx\n"
+            + "        - curl\n"
+            + "    - name: check\n"
+            + "      block:\n"
+            + "        - name: run\n"
+            + "          ansible.builtin.shell: |\n"
+            + "            set -e\n"
+            + "            true\n"
+            + "      always:\n"
+            + "        - name: done\n"
+            + "          ansible.builtin.debug:\n"
+            + "            msg: done\n";
+        let mut plays: Vec<Play> = from_yaml_str(&src).expect("failed to deserialize");
+        // Mix with a typed task
+        if let Some(play) = plays.first_mut() {
+            play.tasks.extend(create_play_helper("typed").tasks);
+        }
+        assert_eq!(
+            to_yaml_string(&plays).expect("failed to serialize"),
+            String::new()
+                + "- name: legacy\n"
+                + "  hosts: all\n"
+                + "  tasks:\n"
+                + "    - name: install\n"
+                + "      loop:\n"
+                + "        - nginx\n"
+                + "        - curl\n"
+                + "      ansible.builtin.apt:\n"
+                + "        name: '{{ item }}'\n"
+                + "        state: present\n"
+                + "    - name: check\n"
+                + "      block:\n"
+                + "        - name: run\n"
+                + "          ansible.builtin.shell: |\n"
+                + "            set -e\n"
+                + "            true\n"
+                + "      always:\n"
+                + "        - name: done\n"
+                + "          ansible.builtin.debug:\n"
+                + "            msg: done\n"
+                + "    - name: debug\n"
+                + "      ansible.builtin.debug:\n"
+                + "        msg: Hello, world!\n"
+        );
+    }
+
+    #[test]
+    fn test_load_legacy_forms() {
+        // Roles as mappings, and tasks and blocks without names
+        let src = String::new()
+            + "- hosts: all\n"
+            + "  roles:\n"
+            + "    - common\n"
+            + "    - role: web\n"
+            + "      vars:\n"
+            + "        port: 80\n"
+            + "      when: ansible_os_family == 'Debian'\n"
+            + "      tags:\n"
+            + "        - web\n"
+            + "    - name: db\n"
+            + "  tasks:\n"
+            + "    - debug:\n"
+            + "        msg: hi\n"
+            + "    - block:\n"
+            + "        - command: 'true'\n"
+            + "      rescue:\n"
+            + "        - debug:\n"
+            + "            msg: failed\n";
+        let plays: Vec<Play> = from_yaml_str(&src).expect("failed to deserialize");
+        let roles = plays
+            .first()
+            .and_then(|play| play.options.roles.as_option())
+            .expect("roles");
+        match roles.get(1) {
+            Some(StringOrRole::Entry(entry)) => {
+                assert_eq!(entry.role, "web");
+                assert_eq!(
+                    entry.vars,
+                    OptU::Some(IndexMap::from([("port".to_string(), 80.into())]))
+                );
+                assert_eq!(
+                    entry.params,
+                    IndexMap::from([("tags".to_string(), json!(["web"]))])
+                );
+            }
+            other => panic!("expected a role entry: {other:?}"),
+        }
+        assert_eq!(
+            to_yaml_string(&plays).expect("failed to serialize"),
+            String::new()
+                + "- hosts: all\n"
+                + "  roles:\n"
+                + "    - common\n"
+                + "    - role: web\n"
+                + "      vars:\n"
+                + "        port: 80\n"
+                + "      when: ansible_os_family == 'Debian'\n"
+                + "      tags:\n"
+                + "        - web\n"
+                + "    - role: db\n"
+                + "  tasks:\n"
+                + "    - debug:\n"
+                + "        msg: hi\n"
+                + "    - block:\n"
+                + "        - command: \"true\"\n"
+                + "      rescue:\n"
+                + "        - debug:\n"
+                + "            msg: failed\n"
+        );
+    }
+
+    #[test]
+    fn test_inventory() {
+        let root = InventoryRoot {
+            all: InventoryChild {
+                hosts: OptU::Some(IndexMap::from([
+                    (
+                        "localhost".to_owned(),
+                        Some(IndexMap::from([(
+                            "ansible_connection".to_owned(),
+                            json!("local"),
+                        )])),
+                    ),
+                    ("host_a".to_owned(), None),
+                ])),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            to_yaml_string(&root).expect("failed to serialize"),
+            String::new()
+                + "all:\n"
+                + "  hosts:\n"
+                + "    localhost:\n"
+                + "      ansible_connection: local\n"
+                + "    host_a: null\n"
+        );
+    }
+
+    #[test]
+    fn test_collections() {
+        let value = json!({
+            "empty_list": [],
+            "empty_map": {},
+            "nested": [[1, 2], {"a": [true, null]}],
+            "float": 1.5,
+        });
+        assert_eq!(
+            to_yaml_string(&value).expect("failed to serialize"),
+            String::new()
+                + "empty_list: []\n"
+                + "empty_map: {}\n"
+                + "nested:\n"
+                + "  - - 1\n"
+                + "    - 2\n"
+                + "  - a:\n"
+                + "      - true\n"
+                + "      - null\n"
+                + "float: 1.5\n"
+        );
+        assert_eq!(to_yaml_string(&json!([])).expect("failed"), "[]\n");
+    }
+
+    #[test]
+    fn test_string_quoting() {
+        for (input, expected) in [
+            ("plain text", "plain text"),
+            ("", r#""""#),
+            ("true", r#""true""#),
+            ("yes", r#""yes""#),
+            ("10", r#""10""#),
+            ("1.5", r#""1.5""#),
+            ("0x1F", r#""0x1F""#),
+            ("1:20", r#""1:20""#),
+            ("2001-12-14", r#""2001-12-14""#),
+            ("null", r#""null""#),
+            ("{{ item }}", "'{{ item }}'"),
+            ("key: value", "'key: value'"),
+            ("it's {{ x }}", "it's {{ x }}"),
+            ("{{ x }}'s", "'{{ x }}''s'"),
+            ("- item", "'- item'"),
+            ("-item", "-item"),
+            ("a #comment", "'a #comment'"),
+            ("a#b", "a#b"),
+            ("http://example.com", "http://example.com"),
+            (" leading", "' leading'"),
+            ("tab\there", r#""tab\there""#),
+            ("日本語", "日本語"),
+        ] {
+            assert_eq!(
+                to_yaml_string(&json!({ "k": input })).expect("failed to serialize"),
+                format!("k: {expected}\n"),
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_multiline_string() {
+        assert_eq!(
+            to_yaml_string(&json!({ "k": "line1\nline2" })).expect("failed"),
+            "k: |-\n  line1\n  line2\n"
+        );
+        assert_eq!(
+            to_yaml_string(&json!({ "k": "line1\n\nline3\n" })).expect("failed"),
+            "k: |\n  line1\n\n  line3\n"
+        );
+        assert_eq!(
+            to_yaml_string(&json!({ "k": "line1\n\n" })).expect("failed"),
+            "k: |+\n  line1\n\n"
+        );
+        assert_eq!(
+            to_yaml_string(&json!([" indented\nline"])).expect("failed"),
+            "- |2-\n   indented\n  line\n"
+        );
+        assert_eq!(
+            to_yaml_string(&json!({ "k": "trailing \nspace" })).expect("failed"),
+            "k: \"trailing \\nspace\"\n"
+        );
+    }
+
+    #[test]
+    fn test_vault() {
+        let vars = json!({ "user": "admin", "password": Secret::new("p@ssw0rd") });
+        assert!(matches!(
+            to_yaml_string_with(&vars, None),
+            Err(YamlError::NoVault)
+        ));
+
+        let vault = Vault::new("password");
+        let yaml = to_yaml_string_with(&vars, Some(&vault)).expect("failed to serialize");
+        let vaulttext = yaml
+            .strip_prefix("user: admin\npassword: !vault |\n")
+            .expect("not a vault value");
+        let vaulttext = vaulttext
+            .lines()
+            .map(|line| line.strip_prefix("  ").expect("not indented"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(vaulttext.starts_with("$ANSIBLE_VAULT;1.1;AES256\n"));
+        assert_eq!(
+            vault.decrypt(&vaulttext).expect("failed to decrypt"),
+            b"p@ssw0rd"
+        );
+    }
+
+    #[test]
+    fn test_unsafe() {
+        let vars = json!({
+            "script": Unsafe("echo {{ x }}"),
+            "list": Unsafe(json!(["{% raw %}", 1])),
+            "plain": "{{ x }}",
+        });
+        assert_eq!(
+            to_yaml_string(&vars).expect("failed to serialize"),
+            String::new()
+                + "script: !unsafe echo {{ x }}\n"
+                + "list:\n"
+                + "  - !unsafe '{% raw %}'\n"
+                + "  - 1\n"
+                + "plain: '{{ x }}'\n"
+        );
+
+        let task = Task {
+            name: "unsafe".into(),
+            options: TaskOptions::default(),
+            command: Box::new(RawModule::new(
+                "ansible.builtin.shell",
+                json!({ "cmd": OptU::Some(Unsafe("echo '{{ x }}'")) }),
+            )),
+        };
+        assert_eq!(
+            to_yaml_string(&task).expect("failed to serialize"),
+            "name: unsafe\nansible.builtin.shell:\n  cmd: !unsafe echo '{{ x }}'\n"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use cdk_ansible::{
     AppL2, ExePlayL2, HostInventoryVars, HostInventoryVarsGenerator, HostsL2, LEP, LazyPlayL2,
-    OptU, PlayL2, PlayOptions, StackL2, Task, TaskOptions, prelude::*,
+    OptU, PlayL2, PlayOptions, StackL2, TaskOptions, TaskOrBlock, prelude::*,
 };
 use chrono::{DateTime, Utc};
 use futures::future::{BoxFuture, FutureExt as _};
@@ -70,44 +70,50 @@ impl LazyPlayL2 for Sample1LazyPlayL2Helper {
                     name: name.clone(),
                     hosts: HostsL2::new(vec![Arc::clone(&hp.localhost) as _]),
                     options: PlayOptions::default(),
-                    tasks: vec![::cdk_ansible::Task {
-                        name: "sleep".into(),
-                        options: TaskOptions {
-                            changed_when: OptU::Some(false.into()),
-                            // failed_when: OptU::Some("true".into()), // interruption test
-                            ..Default::default()
-                        },
-                        command: Box::new(::sample_cdkam_ansible::builtin::command::Module {
-                            module: ::sample_cdkam_ansible::builtin::command::Args {
-                                options: ::sample_cdkam_ansible::builtin::command::Opt {
-                                    cmd: OptU::Some("sleep 1".into()),
-                                    ..Default::default()
-                                },
+                    tasks: vec![
+                        ::cdk_ansible::Task {
+                            name: "sleep".into(),
+                            options: TaskOptions {
+                                changed_when: OptU::Some(false.into()),
+                                // failed_when: OptU::Some("true".into()), // interruption test
+                                ..Default::default()
                             },
-                        }),
-                    }],
+                            command: Box::new(::sample_cdkam_ansible::builtin::command::Module {
+                                module: ::sample_cdkam_ansible::builtin::command::Args {
+                                    options: ::sample_cdkam_ansible::builtin::command::Opt {
+                                        cmd: OptU::Some("sleep 1".into()),
+                                        ..Default::default()
+                                    },
+                                },
+                            }),
+                        }
+                        .into(),
+                    ],
                 }
                 .into(),
                 PlayL2 {
                     name: name.clone(),
                     hosts: HostsL2::new(vec![Arc::clone(&hp.localhost) as _]),
                     options: PlayOptions::default(),
-                    tasks: vec![::cdk_ansible::Task {
-                        name: "sleep".into(),
-                        options: TaskOptions {
-                            changed_when: OptU::Some(false.into()),
-                            // failed_when: OptU::Some("true".into()), // interruption test
-                            ..Default::default()
-                        },
-                        command: Box::new(::sample_cdkam_ansible::builtin::command::Module {
-                            module: ::sample_cdkam_ansible::builtin::command::Args {
-                                options: ::sample_cdkam_ansible::builtin::command::Opt {
-                                    cmd: OptU::Some("sleep 1".into()),
-                                    ..Default::default()
-                                },
+                    tasks: vec![
+                        ::cdk_ansible::Task {
+                            name: "sleep".into(),
+                            options: TaskOptions {
+                                changed_when: OptU::Some(false.into()),
+                                // failed_when: OptU::Some("true".into()), // interruption test
+                                ..Default::default()
                             },
-                        }),
-                    }],
+                            command: Box::new(::sample_cdkam_ansible::builtin::command::Module {
+                                module: ::sample_cdkam_ansible::builtin::command::Args {
+                                    options: ::sample_cdkam_ansible::builtin::command::Opt {
+                                        cmd: OptU::Some("sleep 1".into()),
+                                        ..Default::default()
+                                    },
+                                },
+                            }),
+                        }
+                        .into(),
+                    ],
                 }
                 .into(),
                 ExePlayL2::Sequential(vec![
@@ -287,7 +293,7 @@ pub trait Host: Send + Sync {
     fn common_field(&self) -> &CommonField;
 }
 
-fn create_tasks_helper(h: Arc<dyn Host>, n: usize) -> Result<Vec<Task>> {
+fn create_tasks_helper(h: Arc<dyn Host>, n: usize) -> Result<Vec<TaskOrBlock>> {
     let mut tasks = vec![::cdk_ansible::Task {
         name: "debug".into(),
         options: TaskOptions::default(),
@@ -324,5 +330,5 @@ fn create_tasks_helper(h: Arc<dyn Host>, n: usize) -> Result<Vec<Task>> {
         }));
     }
 
-    Ok(tasks)
+    Ok(tasks.into_iter().map(Into::into).collect())
 }
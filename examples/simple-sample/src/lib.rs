@@ -1,7 +1,7 @@
 use ::anyhow::Result;
-use ::cdk_ansible::{OptU, Play, PlayOptions, StringOrVecString, Task, TaskOptions};
+use ::cdk_ansible::{OptU, Play, PlayOptions, StringOrVecString, TaskOptions, TaskOrBlock};
 
-pub fn create_tasks_helper(n: usize) -> Result<Vec<Task>> {
+pub fn create_tasks_helper(n: usize) -> Result<Vec<TaskOrBlock>> {
     let mut tasks = vec![::cdk_ansible::Task {
         name: "debug".into(),
         options: TaskOptions::default(),
@@ -49,7 +49,7 @@ pub fn create_tasks_helper(n: usize) -> Result<Vec<Task>> {
     //         },
     //     }),
     // });
-    Ok(tasks)
+    Ok(tasks.into_iter().map(Into::into).collect())
 }
 
 pub fn create_play_helper(name: &str, hosts: StringOrVecString, n: usize) -> Result<Play> {